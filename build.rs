@@ -0,0 +1,4 @@
+fn main() {
+    // Set by the build script of error-chain, and checked by the code generated by `error_chain!`.
+    println!("cargo:rustc-check-cfg=cfg(has_error_description_deprecated)");
+}
@@ -5,7 +5,8 @@ extern crate windows_service;
 fn main() -> windows_service::Result<()> {
     use std::ffi::OsString;
     use windows_service::service::{
-        ServiceAccess, ServiceErrorControl, ServiceInfo, ServiceStartType, ServiceType,
+        ServiceAccess, ServiceErrorControl, ServiceInfo, ServiceSetting, ServiceStartType,
+        ServiceType,
     };
     use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
 
@@ -27,8 +28,13 @@ fn main() -> windows_service::Result<()> {
         error_control: ServiceErrorControl::Normal,
         executable_path: service_binary_path,
         launch_arguments: vec![],
+        load_order_group: None,
+        dependencies: vec![],
         account_name: None, // run as System
         account_password: None,
+        settings: vec![ServiceSetting::Description(OsString::from(
+            "Sends a text message to local UDP port 1234 once a second",
+        ))],
     };
     let _service = service_manager.create_service(service_info, ServiceAccess::empty())?;
     Ok(())
@@ -1,44 +1,44 @@
 #[cfg(windows)]
-extern crate windows_service;
-#[macro_use]
-extern crate serde_derive;
-extern crate serde;
-extern crate serde_json;
 extern crate flate2;
-
-use std::io::prelude::*;
-use flate2::Compression;
-use flate2::write::ZlibEncoder;
-
-
-use windows_service::ErrorKind;
-use flate2::write::GzEncoder;
+#[cfg(windows)]
+extern crate serde_json;
+#[cfg(windows)]
+extern crate windows_service;
 
 #[cfg(windows)]
 fn main() -> windows_service::Result<()> {
-    use std::ffi::OsString;
-    use windows_service::service::{
-        ServiceAccess, ServiceErrorControl, ServiceInfo, ServiceStartType, ServiceType,
-    };
+    use flate2::write::{GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+    use std::io::prelude::*;
+    use windows_service::schema::Versioned;
+    use windows_service::service::ServiceListing;
     use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
 
-
-    let list = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::ENUMERATE_SERVICE)
-        .and_then(|service_manager| {
-            service_manager.list_services()
-        }).map_err(|err| ErrorKind::InvalidAccountName)?;
-
-    println!("{}", serde_json::to_string(&list).unwrap());
-    println!("size : {}", serde_json::to_string(&list).unwrap().len());
+    let service_manager =
+        ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::ENUMERATE_SERVICE)?;
+    let listing = service_manager.list_services()?;
+    for service in &listing {
+        if let Err(ref error) = service.config {
+            eprintln!(
+                "Cannot query config of {}: {}",
+                service.entry.name.to_string_lossy(),
+                error
+            );
+        }
+    }
+    let list = Versioned::new(listing.iter().map(ServiceListing::to_detail).collect::<Vec<_>>());
+
+    let json = serde_json::to_string(&list).unwrap();
+    println!("{}", json);
+    println!("size : {}", json.len());
 
     let mut e = GzEncoder::new(Vec::new(), Compression::default());
-    e.write_all(serde_json::to_string(&list).unwrap().as_bytes()).unwrap();
-    println!("GzEncoder : {:?}", e.finish().unwrap().len());
+    e.write_all(json.as_bytes())?;
+    println!("GzEncoder : {:?}", e.finish()?.len());
 
     let mut e = ZlibEncoder::new(Vec::new(), Compression::default());
-    e.write_all(serde_json::to_string(&list).unwrap().as_bytes());
-    let compressed_bytes = e.finish();
-    println!("ZlibEncoder : {:?}", compressed_bytes.unwrap().len());
+    e.write_all(json.as_bytes())?;
+    println!("ZlibEncoder : {:?}", e.finish()?.len());
 
     Ok(())
 }
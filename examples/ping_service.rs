@@ -31,23 +31,18 @@ mod ping_service {
     use std::ffi::OsString;
     use std::net::{IpAddr, SocketAddr, UdpSocket};
     use std::sync::mpsc;
+    use std::thread;
     use std::time::Duration;
 
-    use windows_service::service::{
-        ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus,
-        ServiceType,
-    };
-    use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
     use windows_service::service_dispatcher;
+    use windows_service::service_runner::{self, ServiceRunner};
     use windows_service::Result;
 
-
-    const SERVICE_NAME: &'static str = "ping_service";
-    const SERVICE_TYPE: ServiceType = ServiceType::OwnProcess;
+    const SERVICE_NAME: &str = "ping_service";
 
     const LOOPBACK_ADDR: [u8; 4] = [127, 0, 0, 1];
     const RECEIVER_PORT: u16 = 1234;
-    const PING_MESSAGE: &'static str = "ping\n";
+    const PING_MESSAGE: &str = "ping\n";
 
     pub fn run() -> Result<()> {
         // Register generated `ffi_service_main` with the system and start the service, blocking
@@ -64,78 +59,64 @@ mod ping_service {
     // Service entry function which is called on background thread by the system with service
     // parameters. There is no stdout or stderr at this point so make sure to configure the log
     // output to file if needed.
-    pub fn my_service_main(_arguments: Vec<OsString>) {
-        if let Err(_e) = run_service() {
+    pub fn my_service_main(arguments: Vec<OsString>) {
+        // The runner registers the event handler and reports the service status to the system,
+        // calling the hooks of `PingService` as the service is started and stopped.
+        if let Err(_e) = service_runner::run_service(SERVICE_NAME, PingService::new(), arguments)
+        {
             // Handle the error, by logging or something.
         }
     }
 
-    pub fn run_service() -> Result<()> {
-        // Create a channel to be able to poll a stop event from the service worker loop.
-        let (shutdown_tx, shutdown_rx) = mpsc::channel();
+    struct PingService {
+        // Used to tell the worker thread to stop.
+        shutdown_tx: Option<mpsc::Sender<()>>,
+        worker: Option<thread::JoinHandle<()>>,
+    }
 
-        // Define system service event handler that will be receiving service events.
-        let event_handler = move |control_event| -> ServiceControlHandlerResult {
-            match control_event {
-                // Notifies a service to report its current status information to the service
-                // control manager. Always return NoError even if not implemented.
-                ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+    impl PingService {
+        fn new() -> Self {
+            PingService {
+                shutdown_tx: None,
+                worker: None,
+            }
+        }
+    }
 
-                // Handle stop
-                ServiceControl::Stop => {
-                    shutdown_tx.send(()).unwrap();
-                    ServiceControlHandlerResult::NoError
-                }
+    impl ServiceRunner for PingService {
+        type Error = std::io::Error;
+
+        fn on_start(&mut self, _arguments: Vec<OsString>) -> std::io::Result<()> {
+            // For demo purposes this service sends a UDP packet once a second.
+            let loopback_ip = IpAddr::from(LOOPBACK_ADDR);
+            let sender_addr = SocketAddr::new(loopback_ip, 0);
+            let receiver_addr = SocketAddr::new(loopback_ip, RECEIVER_PORT);
+            let socket = UdpSocket::bind(sender_addr)?;
+
+            let (shutdown_tx, shutdown_rx) = mpsc::channel();
+            self.shutdown_tx = Some(shutdown_tx);
+            self.worker = Some(thread::spawn(move || loop {
+                let _ = socket.send_to(PING_MESSAGE.as_bytes(), receiver_addr);
+
+                // Poll shutdown event.
+                match shutdown_rx.recv_timeout(Duration::from_secs(1)) {
+                    // Break the loop either upon stop or channel disconnect
+                    Ok(_) | Err(mpsc::RecvTimeoutError::Disconnected) => break,
+
+                    // Continue work if no events were received within the timeout
+                    Err(mpsc::RecvTimeoutError::Timeout) => (),
+                };
+            }));
+            Ok(())
+        }
 
-                _ => ServiceControlHandlerResult::NotImplemented,
+        fn on_stop(&mut self) -> std::io::Result<()> {
+            // Dropping the sender disconnects the channel, which stops the worker.
+            self.shutdown_tx = None;
+            if let Some(worker) = self.worker.take() {
+                let _ = worker.join();
             }
-        };
-
-        // Register system service event handler.
-        // The returned status handle should be used to report service status changes to the system.
-        let status_handle = service_control_handler::register(SERVICE_NAME, event_handler)?;
-
-        // Tell the system that service is running
-        status_handle.set_service_status(ServiceStatus {
-            service_type: SERVICE_TYPE,
-            current_state: ServiceState::Running,
-            controls_accepted: ServiceControlAccept::STOP,
-            exit_code: ServiceExitCode::Win32(0),
-            checkpoint: 0,
-            wait_hint: Duration::default(),
-        })?;
-
-        // For demo purposes this service sends a UDP packet once a second.
-        let loopback_ip = IpAddr::from(LOOPBACK_ADDR);
-        let sender_addr = SocketAddr::new(loopback_ip, 0);
-        let receiver_addr = SocketAddr::new(loopback_ip, RECEIVER_PORT);
-        let msg = PING_MESSAGE.as_bytes();
-        let socket = UdpSocket::bind(sender_addr).unwrap();
-
-        loop {
-            let _ = socket.send_to(msg, receiver_addr);
-
-            // Poll shutdown event.
-            match shutdown_rx.recv_timeout(Duration::from_secs(1)) {
-                // Break the loop either upon stop or channel disconnect
-                Ok(_) | Err(mpsc::RecvTimeoutError::Disconnected) => break,
-
-                // Continue work if no events were received within the timeout
-                Err(mpsc::RecvTimeoutError::Timeout) => (),
-            };
+            Ok(())
         }
-
-        // Tell the system that service has stopped.
-        status_handle.set_service_status(ServiceStatus {
-            service_type: SERVICE_TYPE,
-            current_state: ServiceState::Stopped,
-            controls_accepted: ServiceControlAccept::empty(),
-            exit_code: ServiceExitCode::Win32(0),
-            checkpoint: 0,
-            wait_hint: Duration::default(),
-        })?;
-
-        Ok(())
     }
-
 }
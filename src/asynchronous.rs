@@ -0,0 +1,776 @@
+//! Futures and streams for controlling services from asynchronous code.
+//!
+//! This module is only available with the `async` feature. It does not depend on a particular
+//! async runtime: the futures are woken by the threads that wait on behalf of them, so they can
+//! be awaited from any executor.
+//!
+//! * [`Service::start_async`], [`Service::stop_async`] and [`Service::wait_for_state_async`]
+//!   return a [`ServiceStateFuture`] that waits for the service to reach a state without blocking
+//!   the executor between two status queries.
+//! * [`ServiceControlStream`] is a [`Stream`] of the control events sent to a service, to be used
+//!   in place of the event handler closure.
+//! * [`report_pending`] keeps reporting the progress of a pending operation to the system while
+//!   a future runs.
+//!
+//! [`Service::start_async`]: super::service::Service::start_async
+//! [`Service::stop_async`]: super::service::Service::stop_async
+//! [`Service::wait_for_state_async`]: super::service::Service::wait_for_state_async
+//! [`Stream`]: futures_core::Stream
+
+use std::collections::VecDeque;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures_core::Stream;
+
+use backend::ServiceBackend;
+use handler_context::{HandlerFn, ServiceControlHandlerResult};
+use service::{Service, ServiceControl, ServiceState, ServiceStatus};
+use wait::{Wait, WaitStep};
+use Result;
+
+/// Wakes the future it belongs to once a deadline is reached.
+///
+/// The deadlines are timed by a single thread, started on the first [`Timer::sleep`] and stopped
+/// when the timer is dropped, so a future polling at a regular interval does not spawn a thread
+/// for every interval.
+struct Timer {
+    shared: Arc<TimerShared>,
+    started: bool,
+}
+
+struct TimerShared {
+    state: Mutex<TimerState>,
+    condvar: Condvar,
+}
+
+struct TimerState {
+    /// The deadline of the current delay, `None` when the timer is not armed.
+    deadline: Option<Instant>,
+    waker: Option<Waker>,
+    dropped: bool,
+}
+
+impl Timer {
+    fn new() -> Self {
+        Timer {
+            shared: Arc::new(TimerShared {
+                state: Mutex::new(TimerState {
+                    deadline: None,
+                    waker: None,
+                    dropped: false,
+                }),
+                condvar: Condvar::new(),
+            }),
+            started: false,
+        }
+    }
+
+    /// Arm the timer to elapse after the duration.
+    fn sleep(&mut self, duration: Duration) {
+        self.shared.state.lock().unwrap().deadline = Some(Instant::now() + duration);
+        self.shared.condvar.notify_one();
+        if !self.started {
+            let shared = self.shared.clone();
+            thread::spawn(move || shared.run());
+            self.started = true;
+        }
+    }
+
+    fn is_armed(&self) -> bool {
+        self.shared.state.lock().unwrap().deadline.is_some()
+    }
+
+    /// Poll the current delay, disarming the timer once it has elapsed. Ready right away when the
+    /// timer is not armed.
+    fn poll(&mut self, cx: &mut Context) -> Poll<()> {
+        let mut state = self.shared.state.lock().unwrap();
+        match state.deadline {
+            Some(deadline) if Instant::now() < deadline => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            _ => {
+                state.deadline = None;
+                Poll::Ready(())
+            }
+        }
+    }
+}
+
+impl TimerShared {
+    /// Wake the future at every deadline until the timer is dropped.
+    fn run(&self) {
+        let mut state = self.state.lock().unwrap();
+        // The deadline the future was woken for, so that it is only woken once.
+        let mut woken = None;
+        while !state.dropped {
+            match state.deadline {
+                Some(deadline) if woken != Some(deadline) => {
+                    let now = Instant::now();
+                    if now < deadline {
+                        state = self.condvar.wait_timeout(state, deadline - now).unwrap().0;
+                    } else {
+                        woken = Some(deadline);
+                        if let Some(waker) = state.waker.take() {
+                            waker.wake();
+                        }
+                    }
+                }
+                _ => state = self.condvar.wait(state).unwrap(),
+            }
+        }
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().dropped = true;
+        self.shared.condvar.notify_one();
+    }
+}
+
+/// The request that a [`ServiceStateFuture`] sends before waiting.
+enum Request {
+    Start(Vec<OsString>),
+    Stop,
+}
+
+/// A future that resolves to the status of a service once it reaches a state.
+///
+/// The future is returned by [`Service::start_async`], [`Service::stop_async`] and
+/// [`Service::wait_for_state_async`], and fails in the same cases as [`Service::wait_for_state`].
+/// Nothing is sent to the service until the future is polled for the first time.
+///
+/// The requests to the service control manager can block for a long time, so they are made from
+/// a thread of the future, which is started on the first poll and ends once the future completes
+/// or is dropped.
+///
+/// [`Service::start_async`]: super::service::Service::start_async
+/// [`Service::stop_async`]: super::service::Service::stop_async
+/// [`Service::wait_for_state_async`]: super::service::Service::wait_for_state_async
+/// [`Service::wait_for_state`]: super::service::Service::wait_for_state
+#[must_use = "futures do nothing unless polled"]
+pub struct ServiceStateFuture<'a> {
+    service: &'a Service,
+    request: Option<Request>,
+    target_state: ServiceState,
+    timeout: Duration,
+    /// The state shared with the thread of the future, `None` before the first poll.
+    shared: Option<Arc<WaitShared>>,
+}
+
+/// The state shared by a [`ServiceStateFuture`] and its thread.
+struct WaitShared {
+    state: Mutex<WaitState>,
+    condvar: Condvar,
+}
+
+struct WaitState {
+    result: Option<Result<ServiceStatus>>,
+    waker: Option<Waker>,
+    dropped: bool,
+}
+
+impl<'a> ServiceStateFuture<'a> {
+    fn new(
+        service: &'a Service,
+        request: Option<Request>,
+        target_state: ServiceState,
+        timeout: Duration,
+    ) -> Self {
+        ServiceStateFuture {
+            service,
+            request,
+            target_state,
+            timeout,
+            shared: None,
+        }
+    }
+
+    pub(crate) fn wait(
+        service: &'a Service,
+        target_state: ServiceState,
+        timeout: Duration,
+    ) -> Self {
+        Self::new(service, None, target_state, timeout)
+    }
+
+    pub(crate) fn start(service: &'a Service, arguments: Vec<OsString>, timeout: Duration) -> Self {
+        let request = Some(Request::Start(arguments));
+        Self::new(service, request, ServiceState::Running, timeout)
+    }
+
+    pub(crate) fn stop(service: &'a Service, timeout: Duration) -> Self {
+        Self::new(service, Some(Request::Stop), ServiceState::Stopped, timeout)
+    }
+
+    /// Start the thread that sends the request and waits for the service to reach the state.
+    fn spawn(&mut self) -> Arc<WaitShared> {
+        let shared = Arc::new(WaitShared {
+            state: Mutex::new(WaitState {
+                result: None,
+                waker: None,
+                dropped: false,
+            }),
+            condvar: Condvar::new(),
+        });
+        let backend = self.service.shared_backend();
+        let request = self.request.take();
+        let (target_state, timeout) = (self.target_state, self.timeout);
+        let thread_shared = shared.clone();
+        thread::spawn(move || {
+            let result = thread_shared.run(&*backend, request, target_state, timeout);
+            let mut state = thread_shared.state.lock().unwrap();
+            if let Some(result) = result {
+                state.result = Some(result);
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+            }
+        });
+        shared
+    }
+}
+
+impl WaitShared {
+    /// Send the request and wait for the service to reach the state. Returns `None` if the future
+    /// was dropped in the meantime.
+    fn run(
+        &self,
+        backend: &dyn ServiceBackend,
+        request: Option<Request>,
+        target_state: ServiceState,
+        timeout: Duration,
+    ) -> Option<Result<ServiceStatus>> {
+        let requested = match request {
+            Some(Request::Start(arguments)) => {
+                let arguments: Vec<&OsStr> = arguments.iter().map(AsRef::as_ref).collect();
+                backend.start(&arguments)
+            }
+            Some(Request::Stop) => backend.control(ServiceControl::Stop).map(|_| ()),
+            None => Ok(()),
+        };
+        if let Err(error) = requested {
+            return Some(Err(error));
+        }
+
+        let mut wait = Wait::new(target_state, timeout, Instant::now());
+        loop {
+            let step = backend
+                .query_status()
+                .and_then(|status| wait.check(status, Instant::now()));
+            match step {
+                Ok(WaitStep::Done(status)) => return Some(Ok(status)),
+                Ok(WaitStep::Sleep(duration)) => {
+                    if !self.sleep(duration) {
+                        return None;
+                    }
+                }
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+
+    /// Sleep for the duration, or until the future is dropped. Returns `false` in that case.
+    fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            if state.dropped {
+                return false;
+            } else if now >= deadline {
+                return true;
+            }
+            state = self.condvar.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+}
+
+impl<'a> Future for ServiceStateFuture<'a> {
+    type Output = Result<ServiceStatus>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let shared = match this.shared {
+            Some(ref shared) => shared.clone(),
+            None => {
+                let shared = this.spawn();
+                this.shared = Some(shared.clone());
+                shared
+            }
+        };
+        let mut state = shared.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<'a> Drop for ServiceStateFuture<'a> {
+    fn drop(&mut self) {
+        if let Some(ref shared) = self.shared {
+            shared.state.lock().unwrap().dropped = true;
+            shared.condvar.notify_one();
+        }
+    }
+}
+
+impl<'a> fmt::Debug for ServiceStateFuture<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ServiceStateFuture")
+            .field("target_state", &self.target_state)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+/// The queue shared by the two ends of a control channel.
+struct ControlQueue {
+    controls: VecDeque<ServiceControl>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+/// The end of a control channel that the event handler sends the controls through. Dropping it
+/// ends the stream.
+pub(crate) struct ControlSender {
+    queue: Arc<Mutex<ControlQueue>>,
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+impl ControlSender {
+    pub(crate) fn send(&self, control: ServiceControl) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.controls.push_back(control);
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Turn the sender into an event handler that forwards every control to the stream and
+    /// reports it as handled. The stream ends when the handler is dropped, which happens once the
+    /// service reports the `Stopped` state.
+    pub(crate) fn into_handler(self) -> Box<HandlerFn> {
+        Box::new(move |control, _, _| {
+            self.send(control);
+            ServiceControlHandlerResult::NoError
+        })
+    }
+}
+
+impl Drop for ControlSender {
+    fn drop(&mut self) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.closed = true;
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Create a stream of controls along with the sender feeding it.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub(crate) fn control_channel() -> (ControlSender, ServiceControlStream) {
+    let queue = Arc::new(Mutex::new(ControlQueue {
+        controls: VecDeque::new(),
+        waker: None,
+        closed: false,
+    }));
+    (
+        ControlSender {
+            queue: queue.clone(),
+        },
+        ServiceControlStream { queue },
+    )
+}
+
+/// A stream of the control events sent to a service.
+///
+/// The stream is returned by [`service_control_handler::register_stream`]. It ends once the
+/// service reports the [`ServiceState::Stopped`] state through the status handle returned along
+/// with it, after delivering the controls received until then.
+///
+/// [`service_control_handler::register_stream`]:
+///     super::service_control_handler::register_stream
+#[must_use = "streams do nothing unless polled"]
+pub struct ServiceControlStream {
+    queue: Arc<Mutex<ControlQueue>>,
+}
+
+impl ServiceControlStream {
+    /// Wait for the next control event, without depending on a stream extension trait. Resolves
+    /// to `None` once the stream has ended.
+    pub fn recv(&mut self) -> RecvControl<'_> {
+        RecvControl { stream: self }
+    }
+}
+
+impl Stream for ServiceControlStream {
+    type Item = ServiceControl;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<ServiceControl>> {
+        let mut queue = self.queue.lock().unwrap();
+        if let Some(control) = queue.controls.pop_front() {
+            Poll::Ready(Some(control))
+        } else if queue.closed {
+            Poll::Ready(None)
+        } else {
+            queue.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl fmt::Debug for ServiceControlStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ServiceControlStream").finish()
+    }
+}
+
+/// A future that resolves to the next control event, returned by [`ServiceControlStream::recv`].
+#[must_use = "futures do nothing unless polled"]
+#[derive(Debug)]
+pub struct RecvControl<'a> {
+    stream: &'a mut ServiceControlStream,
+}
+
+impl<'a> Future for RecvControl<'a> {
+    type Output = Option<ServiceControl>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<ServiceControl>> {
+        Pin::new(&mut *self.get_mut().stream).poll_next(cx)
+    }
+}
+
+/// How often the progress is reported when the pending status has no wait hint.
+const DEFAULT_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Report the progress of a pending operation while a future runs.
+///
+/// The pending status is reported right away, and then again with an incremented checkpoint
+/// every half of its wait hint until the future completes, so that the system does not consider
+/// the service hung. Once the future completes, its output is returned and it is up to the
+/// caller to report the next status. The future fails if reporting the status fails.
+///
+/// Use [`ServiceStatusHandle::report_pending`] to report the progress of a running service.
+///
+/// # Example
+///
+/// ```rust
+/// use std::future;
+/// use std::time::Duration;
+/// use windows_service::asynchronous::report_pending;
+/// use windows_service::service::{
+///     ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType,
+/// };
+///
+/// let stop_pending = ServiceStatus {
+///     service_type: ServiceType::OwnProcess,
+///     current_state: ServiceState::StopPending,
+///     controls_accepted: ServiceControlAccept::empty(),
+///     exit_code: ServiceExitCode::default(),
+///     checkpoint: 1,
+///     wait_hint: Duration::from_secs(5),
+/// };
+/// // Report the progress while the service flushes its state to disk, then stop.
+/// let flush_state = future::ready(());
+/// let stopping = report_pending(stop_pending, |status| Ok(println!("{:?}", status)), flush_state);
+/// ```
+///
+/// [`ServiceStatusHandle::report_pending`]:
+///     super::service_control_handler::ServiceStatusHandle::report_pending
+pub fn report_pending<F, R>(status: ServiceStatus, report: R, future: F) -> ReportPending<F, R>
+where
+    F: Future,
+    R: FnMut(ServiceStatus) -> io::Result<()>,
+{
+    ReportPending {
+        future: Box::pin(future),
+        report,
+        status,
+        timer: Timer::new(),
+    }
+}
+
+/// A future that reports the progress of a pending operation, returned by [`report_pending`].
+#[must_use = "futures do nothing unless polled"]
+pub struct ReportPending<F, R> {
+    future: Pin<Box<F>>,
+    report: R,
+    status: ServiceStatus,
+    /// The time until the next report, not armed before the first report.
+    timer: Timer,
+}
+
+impl<F, R> Future for ReportPending<F, R>
+where
+    F: Future,
+    R: FnMut(ServiceStatus) -> io::Result<()> + Unpin,
+{
+    type Output = Result<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if let Poll::Ready(output) = this.future.as_mut().poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        loop {
+            if this.timer.is_armed() {
+                if this.timer.poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                this.status.checkpoint += 1;
+            }
+            (this.report)(this.status.clone())?;
+
+            let interval = if this.status.wait_hint == Duration::default() {
+                DEFAULT_REPORT_INTERVAL
+            } else {
+                this.status.wait_hint / 2
+            };
+            this.timer.sleep(interval);
+        }
+    }
+}
+
+impl<F, R> fmt::Debug for ReportPending<F, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ReportPending")
+            .field("status", &self.status)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backend::FakeScm;
+    use handler_context::HandlerContext;
+    use service::{
+        ServiceAccess, ServiceControlAccept, ServiceErrorControl, ServiceExitCode, ServiceInfo,
+        ServiceStartType, ServiceType,
+    };
+    use service_manager::ServiceManagerAccess;
+    use std::cell::Cell;
+    use std::future;
+    use std::path::PathBuf;
+    use std::ptr;
+    use std::task::Wake;
+    use sys::winerror::NO_ERROR;
+    use sys::winsvc;
+    use ErrorKind;
+
+    /// Wakes the thread blocked in [`block_on`].
+    struct ThreadWaker(thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Poll the future on the current thread until it completes.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    /// A future that polls a closure, in place of `std::future::poll_fn` which needs Rust 1.64.
+    struct PollFn<F>(F);
+
+    impl<T, F: FnMut(&mut Context) -> Poll<T> + Unpin> Future for PollFn<F> {
+        type Output = T;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<T> {
+            (self.0)(cx)
+        }
+    }
+
+    fn pending_status(wait_hint: Duration) -> ServiceStatus {
+        ServiceStatus {
+            service_type: ServiceType::OwnProcess,
+            current_state: ServiceState::StopPending,
+            controls_accepted: ServiceControlAccept::empty(),
+            exit_code: ServiceExitCode::default(),
+            checkpoint: 1,
+            wait_hint,
+        }
+    }
+
+    #[test]
+    fn test_service_state_future() {
+        let scm = FakeScm::new();
+        let service_info = ServiceInfo {
+            name: OsString::from("test"),
+            display_name: OsString::from("Test service"),
+            service_type: ServiceType::OwnProcess,
+            start_type: ServiceStartType::OnDemand,
+            error_control: ServiceErrorControl::Normal,
+            executable_path: PathBuf::from(r"C:\Program Files\Test\service.exe"),
+            launch_arguments: vec![],
+            load_order_group: None,
+            dependencies: vec![],
+            account_name: None,
+            account_password: None,
+            settings: vec![],
+        };
+        let service = scm
+            .connect(ServiceManagerAccess::CREATE_SERVICE)
+            .create_service(
+                service_info,
+                ServiceAccess::START | ServiceAccess::STOP | ServiceAccess::QUERY_STATUS,
+            )
+            .unwrap();
+        scm.set_state("test", ServiceState::Running).unwrap();
+        let timeout = Duration::from_secs(5);
+
+        let stop = service.stop_async(timeout);
+        assert_eq!(
+            scm.status("test").unwrap().current_state,
+            ServiceState::Running
+        );
+        assert_eq!(block_on(stop).unwrap().current_state, ServiceState::Stopped);
+
+        // The fake reports the service as starting until it is moved to another state.
+        let service_scm = scm.clone();
+        let service_thread = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            service_scm
+                .set_state("test", ServiceState::Running)
+                .unwrap();
+        });
+        let mut start = Box::pin(service.start_async(&["--verbose"], timeout));
+        // The first poll hands the request to the thread of the future and returns at once.
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        assert!(start.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(
+            block_on(start).unwrap().current_state,
+            ServiceState::Running
+        );
+        service_thread.join().unwrap();
+
+        let wait = service.wait_for_state_async(ServiceState::Paused, timeout);
+        match block_on(wait).unwrap_err().kind() {
+            ErrorKind::UnexpectedServiceState(ServiceState::Running) => (),
+            other => panic!("Unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_service_control_stream() {
+        let (sender, mut stream) = control_channel();
+        let handler_thread = thread::spawn(move || {
+            sender.send(ServiceControl::Interrogate);
+            thread::sleep(Duration::from_millis(50));
+            sender.send(ServiceControl::Stop);
+        });
+
+        let mut controls = Vec::new();
+        while let Some(control) = block_on(stream.recv()) {
+            controls.push(control);
+        }
+        handler_thread.join().unwrap();
+        assert_eq!(
+            controls,
+            vec![ServiceControl::Interrogate, ServiceControl::Stop]
+        );
+    }
+
+    #[test]
+    fn test_service_control_stream_ends_when_handler_is_released() {
+        let (sender, mut stream) = control_channel();
+        let context = HandlerContext::new(sender.into_handler());
+
+        // Controls after `Stop` are still delivered until the service reports `Stopped`.
+        for &control in &[
+            winsvc::SERVICE_CONTROL_STOP,
+            winsvc::SERVICE_CONTROL_INTERROGATE,
+        ] {
+            assert_eq!(context.dispatch(control, 0, ptr::null_mut()), NO_ERROR);
+        }
+        context.release();
+
+        let mut controls = Vec::new();
+        while let Some(control) = block_on(stream.recv()) {
+            controls.push(control);
+        }
+        assert_eq!(
+            controls,
+            vec![ServiceControl::Stop, ServiceControl::Interrogate]
+        );
+    }
+
+    #[test]
+    fn test_timer() {
+        let mut timer = Timer::new();
+        assert!(!timer.is_armed());
+
+        // The same timer is armed again after each delay elapsed.
+        for _ in 0..3 {
+            let start = Instant::now();
+            timer.sleep(Duration::from_millis(20));
+            assert!(timer.is_armed());
+            block_on(PollFn(|cx: &mut Context| timer.poll(cx)));
+            assert!(start.elapsed() >= Duration::from_millis(20));
+            assert!(!timer.is_armed());
+        }
+    }
+
+    #[test]
+    fn test_report_pending() {
+        // The operation completes once three reports were made.
+        let reports = Cell::new(0);
+        let operation = PollFn(|cx: &mut Context| {
+            if reports.get() == 3 {
+                return Poll::Ready("done");
+            }
+            let waker = cx.waker().clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(10));
+                waker.wake();
+            });
+            Poll::Pending
+        });
+
+        let mut checkpoints = Vec::new();
+        let output = block_on(report_pending(
+            pending_status(Duration::from_millis(100)),
+            |status| {
+                checkpoints.push(status.checkpoint);
+                reports.set(reports.get() + 1);
+                Ok(())
+            },
+            operation,
+        ));
+        assert_eq!(output.unwrap(), "done");
+        assert_eq!(checkpoints, vec![1, 2, 3]);
+
+        let failing = report_pending(
+            pending_status(Duration::from_secs(1)),
+            |_| Err(io::Error::from(io::ErrorKind::Other)),
+            future::pending::<()>(),
+        );
+        assert!(block_on(failing).is_err());
+    }
+}
@@ -0,0 +1,2173 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use widestring::{NulError, WideCString};
+use sys::winerror::{
+    ERROR_ACCESS_DENIED, ERROR_DEPENDENT_SERVICES_RUNNING, ERROR_INVALID_SERVICE_CONTROL,
+    ERROR_SERVICE_ALREADY_RUNNING, ERROR_SERVICE_CANNOT_ACCEPT_CTRL, ERROR_SERVICE_DISABLED,
+    ERROR_SERVICE_DOES_NOT_EXIST, ERROR_SERVICE_EXISTS, ERROR_SERVICE_MARKED_FOR_DELETE,
+    ERROR_SERVICE_NEVER_STARTED, ERROR_SERVICE_NOT_ACTIVE,
+};
+
+use backend::{ServiceBackend, ServiceManagerBackend, ServicePage, WatchHandle};
+use service::{
+    ServiceAccess, ServiceConfig, ServiceConfigChange, ServiceControl, ServiceControlAccept,
+    ServiceDependency, ServiceEntry, ServiceEntryExt, ServiceExitCode, ServiceFailureActions,
+    ServiceFailureResetPeriod, ServiceInfo, ServiceSetting, ServiceSettingKind, ServiceSidType,
+    ServiceStartType, ServiceState, ServiceStateFilter, ServiceStatus, ServiceStatusExt,
+};
+use service_manager::{
+    dependencies_multi_string, launch_command, privileges_multi_string, split_launch_command,
+    ServiceFilter, ServiceManager, ServiceManagerAccess,
+};
+use watch::{ServiceEvent, ServiceNotifyMask};
+use {ErrorKind, Result, ResultExt};
+
+/// An in-memory stand-in for the service control manager.
+///
+/// The fake keeps a database of service records and mimics the behaviour of the system: access
+/// rights are checked for every operation, deleted services linger as marked for deletion until
+/// they are stopped and all their handles are closed, watches are notified of every change, and
+/// failures are reported with the same system error codes that the real service control manager
+/// would use.
+///
+/// Clones of `FakeScm` share the same database.
+///
+/// # Example
+///
+/// ```rust
+/// use std::ffi::OsString;
+/// use std::path::PathBuf;
+/// use windows_service::backend::FakeScm;
+/// use windows_service::service::{
+///     ServiceAccess, ServiceErrorControl, ServiceInfo, ServiceStartType, ServiceState,
+///     ServiceType,
+/// };
+/// use windows_service::service_manager::ServiceManagerAccess;
+///
+/// # fn main() -> windows_service::Result<()> {
+/// let scm = FakeScm::new();
+/// let manager = scm.connect(ServiceManagerAccess::CREATE_SERVICE);
+///
+/// let service_info = ServiceInfo {
+///     name: OsString::from("my_service"),
+///     display_name: OsString::from("My service"),
+///     service_type: ServiceType::OwnProcess,
+///     start_type: ServiceStartType::OnDemand,
+///     error_control: ServiceErrorControl::Normal,
+///     executable_path: PathBuf::from(r"C:\path\to\my\service.exe"),
+///     launch_arguments: vec![],
+///     load_order_group: None,
+///     dependencies: vec![],
+///     account_name: None,
+///     account_password: None,
+///     settings: vec![],
+/// };
+/// let service = manager.create_service(service_info, ServiceAccess::QUERY_STATUS)?;
+///
+/// scm.set_state("my_service", ServiceState::Running)?;
+/// assert_eq!(service.query_status()?.current_state, ServiceState::Running);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FakeScm {
+    database: Arc<Mutex<Database>>,
+}
+
+/// The number of services returned per page of enumeration. Kept small so that callers exercise
+/// the paging.
+const PAGE_SIZE: usize = 4;
+
+#[derive(Debug, Default)]
+struct Database {
+    services: BTreeMap<String, ServiceRecord>,
+    next_id: u64,
+    watches: Vec<Watch>,
+    /// Created and deleted services that the watches have not been notified of yet.
+    pending_events: Vec<ServiceEvent>,
+}
+
+/// Locked database that notifies the watches of the changes when it is unlocked.
+struct DatabaseGuard<'a>(MutexGuard<'a, Database>);
+
+#[derive(Debug)]
+struct Watch {
+    id: u64,
+    target: WatchTarget,
+    events: mpsc::Sender<Result<ServiceEvent>>,
+}
+
+#[derive(Debug)]
+enum WatchTarget {
+    /// The service record with the given id, along with the last state reported to the watch.
+    Service {
+        id: u64,
+        mask: ServiceNotifyMask,
+        last_state: Option<ServiceState>,
+    },
+    /// Services being created and deleted.
+    CreatedDeleted,
+}
+
+#[derive(Debug)]
+struct ServiceRecord {
+    id: u64,
+    info: ServiceInfo,
+    /// The command line as the system stores it, which supersedes the executable path and launch
+    /// arguments of `info`.
+    binary_path: OsString,
+    status: ServiceStatus,
+    process_id: u32,
+    allowed_access: ServiceAccess,
+    start_arguments: Option<Vec<OsString>>,
+    marked_for_delete: bool,
+    open_handles: usize,
+}
+
+impl FakeScm {
+    /// Create an empty service database.
+    pub fn new() -> Self {
+        FakeScm::default()
+    }
+
+    /// Connect to the fake database with the given access permissions.
+    pub fn connect(&self, request_access: ServiceManagerAccess) -> ServiceManager {
+        ServiceManager::with_backend(FakeServiceManager {
+            scm: self.clone(),
+            access: request_access,
+        })
+    }
+
+    /// Returns `true` if the database contains a service with the given name, including
+    /// services that are marked for deletion.
+    pub fn contains<T: AsRef<OsStr>>(&self, name: T) -> bool {
+        self.lock().services.contains_key(&key(name.as_ref()))
+    }
+
+    /// Returns the current status of the service with the given name.
+    pub fn status<T: AsRef<OsStr>>(&self, name: T) -> Option<ServiceStatus> {
+        self.lock()
+            .services
+            .get(&key(name.as_ref()))
+            .map(|record| record.status.clone())
+    }
+
+    /// Returns the arguments the `service_main` of the service received when it was last
+    /// started, beginning with the service name, or `None` if it was never started.
+    pub fn start_arguments<T: AsRef<OsStr>>(&self, name: T) -> Option<Vec<OsString>> {
+        self.lock()
+            .services
+            .get(&key(name.as_ref()))
+            .and_then(|record| record.start_arguments.clone())
+    }
+
+    /// Replace the status of the service, as if it was reported by the service itself.
+    pub fn set_status<T: AsRef<OsStr>>(&self, name: T, status: ServiceStatus) -> Result<()> {
+        let mut database = self.lock();
+        let record = database.get_mut(name.as_ref())?;
+        record.process_id = match status.current_state {
+            ServiceState::Stopped => 0,
+            _ if record.process_id == 0 => record.id as u32 + 1000,
+            _ => record.process_id,
+        };
+        record.status = status;
+        database.collect_garbage();
+        Ok(())
+    }
+
+    /// Move the service to the given state.
+    ///
+    /// Services that are moved to any state other than stopped accept the stop and
+    /// pause/continue controls.
+    pub fn set_state<T: AsRef<OsStr>>(&self, name: T, state: ServiceState) -> Result<()> {
+        let mut status = self
+            .status(name.as_ref())
+            .ok_or_else(|| system_error(ERROR_SERVICE_DOES_NOT_EXIST))?;
+        status.current_state = state;
+        status.checkpoint = 0;
+        status.wait_hint = Duration::default();
+        if state == ServiceState::Stopped {
+            status.controls_accepted = ServiceControlAccept::empty();
+        } else {
+            status.controls_accepted =
+                ServiceControlAccept::STOP | ServiceControlAccept::PAUSE_CONTINUE;
+            status.exit_code = ServiceExitCode::default();
+        }
+        self.set_status(name, status)
+    }
+
+    /// Limit the permissions that can be granted when opening the service, much like the
+    /// security descriptor of a real service would.
+    ///
+    /// All permissions are allowed by default.
+    pub fn set_allowed_access<T: AsRef<OsStr>>(
+        &self,
+        name: T,
+        allowed_access: ServiceAccess,
+    ) -> Result<()> {
+        let mut database = self.lock();
+        database.get_mut(name.as_ref())?.allowed_access = allowed_access;
+        Ok(())
+    }
+
+    /// Replace the command line the service is launched with, as if it was edited in the system
+    /// services registry, bypassing the escaping applied by [`ServiceManager::create_service`] and
+    /// [`Service::change_config`].
+    ///
+    /// [`ServiceManager::create_service`]: ::service_manager::ServiceManager::create_service
+    /// [`Service::change_config`]: ::service::Service::change_config
+    pub fn set_binary_path<T: AsRef<OsStr>, P: Into<OsString>>(
+        &self,
+        name: T,
+        binary_path: P,
+    ) -> Result<()> {
+        let mut database = self.lock();
+        database.get_mut(name.as_ref())?.binary_path = binary_path.into();
+        Ok(())
+    }
+
+    fn open_handle(&self, name: &OsStr, request_access: ServiceAccess) -> Result<ServiceHandle> {
+        let mut database = self.lock();
+        let record = database.get_mut(name)?;
+        if record.marked_for_delete {
+            Err(system_error(ERROR_SERVICE_MARKED_FOR_DELETE))?;
+        }
+        if !record.allowed_access.contains(request_access) {
+            Err(system_error(ERROR_ACCESS_DENIED))?;
+        }
+        record.open_handles += 1;
+        Ok(ServiceHandle {
+            scm: self.clone(),
+            key: key(name),
+            id: record.id,
+            access: request_access,
+        })
+    }
+
+    fn lock(&self) -> DatabaseGuard<'_> {
+        // A panic while holding the lock cannot leave the database in an inconsistent state.
+        DatabaseGuard(match self.database.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        })
+    }
+
+    fn add_watch(
+        &self,
+        target: WatchTarget,
+        events: mpsc::Sender<Result<ServiceEvent>>,
+    ) -> WatchHandle {
+        let mut database = self.lock();
+        database.next_id += 1;
+        let id = database.next_id;
+        database.watches.push(Watch { id, target, events });
+
+        let scm = self.clone();
+        WatchHandle::new(move || scm.lock().watches.retain(|watch| watch.id != id))
+    }
+}
+
+impl<'a> Deref for DatabaseGuard<'a> {
+    type Target = Database;
+
+    fn deref(&self) -> &Database {
+        &self.0
+    }
+}
+
+impl<'a> DerefMut for DatabaseGuard<'a> {
+    fn deref_mut(&mut self) -> &mut Database {
+        &mut self.0
+    }
+}
+
+impl<'a> Drop for DatabaseGuard<'a> {
+    fn drop(&mut self) {
+        self.0.notify_watches();
+    }
+}
+
+impl Database {
+    fn get(&self, name: &OsStr) -> io::Result<&ServiceRecord> {
+        self.services
+            .get(&key(name))
+            .ok_or_else(|| system_error(ERROR_SERVICE_DOES_NOT_EXIST))
+    }
+
+    fn get_mut(&mut self, name: &OsStr) -> io::Result<&mut ServiceRecord> {
+        self.services
+            .get_mut(&key(name))
+            .ok_or_else(|| system_error(ERROR_SERVICE_DOES_NOT_EXIST))
+    }
+
+    /// Look up the service record that the given handle was opened for.
+    fn record(&mut self, handle: &ServiceHandle) -> io::Result<&mut ServiceRecord> {
+        match self.services.get_mut(&handle.key) {
+            Some(record) if record.id == handle.id => Ok(record),
+            // The handle outlives its record only if the service was deleted.
+            _ => Err(system_error(ERROR_SERVICE_MARKED_FOR_DELETE)),
+        }
+    }
+
+    /// Collect the services that depend on the service with the given key, directly or
+    /// indirectly, so that each service comes before the services it depends on.
+    fn dependents(&self, key: &str) -> Vec<&ServiceRecord> {
+        let mut visited = BTreeSet::new();
+        visited.insert(key.to_owned());
+        let mut dependents = Vec::new();
+        self.visit_dependents(key, &mut visited, &mut dependents);
+        dependents
+            .iter()
+            .map(|dependent_key| &self.services[dependent_key])
+            .collect()
+    }
+
+    fn visit_dependents(
+        &self,
+        key: &str,
+        visited: &mut BTreeSet<String>,
+        dependents: &mut Vec<String>,
+    ) {
+        let record = &self.services[key];
+        for (dependent_key, dependent) in &self.services {
+            if dependent.depends_on(record) && visited.insert(dependent_key.clone()) {
+                self.visit_dependents(dependent_key, visited, dependents);
+                dependents.push(dependent_key.clone());
+            }
+        }
+    }
+
+    /// Remove the services that are marked for deletion, stopped and no longer referenced.
+    fn collect_garbage(&mut self) {
+        let deleted: Vec<String> = self
+            .services
+            .iter()
+            .filter(|&(_, record)| {
+                record.marked_for_delete
+                    && record.open_handles == 0
+                    && record.status.current_state == ServiceState::Stopped
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in deleted {
+            if let Some(record) = self.services.remove(&key) {
+                self.pending_events.push(ServiceEvent::Deleted(record.info.name));
+            }
+        }
+    }
+
+    /// Notify the watches of the changes since they were last notified, and drop the watches
+    /// that ended.
+    fn notify_watches(&mut self) {
+        let created_deleted: Vec<ServiceEvent> = self.pending_events.drain(..).collect();
+        let services = &self.services;
+        self.watches.retain_mut(|watch| match watch.target {
+            WatchTarget::CreatedDeleted => created_deleted
+                .iter()
+                .all(|event| watch.events.send(Ok(event.clone())).is_ok()),
+            WatchTarget::Service {
+                id,
+                mask,
+                ref mut last_state,
+            } => match services.values().find(|record| record.id == id) {
+                Some(record) if !record.marked_for_delete => {
+                    let state = record.status.current_state;
+                    if *last_state == Some(state) {
+                        return true;
+                    }
+                    *last_state = Some(state);
+                    !mask.contains(ServiceNotifyMask::from_state(state))
+                        || watch
+                            .events
+                            .send(Ok(ServiceEvent::StateChanged(record.status_ext())))
+                            .is_ok()
+                }
+                _ => {
+                    if mask.contains(ServiceNotifyMask::DELETE_PENDING) {
+                        let _ = watch.events.send(Ok(ServiceEvent::DeletePending));
+                    }
+                    false
+                }
+            },
+        });
+    }
+}
+
+/// Backend for [`ServiceManager`] connected to [`FakeScm`].
+struct FakeServiceManager {
+    scm: FakeScm,
+    access: ServiceManagerAccess,
+}
+
+impl FakeServiceManager {
+    fn check_access(&self, required_access: ServiceManagerAccess) -> io::Result<()> {
+        if self.access.contains(required_access) {
+            Ok(())
+        } else {
+            Err(system_error(ERROR_ACCESS_DENIED))
+        }
+    }
+}
+impl ServiceManagerBackend for FakeServiceManager {
+    fn create_service(
+        &self,
+        service_info: &ServiceInfo,
+        service_access: ServiceAccess,
+    ) -> Result<Box<dyn ServiceBackend>> {
+        WideCString::from_str(&service_info.name).chain_err(|| ErrorKind::InvalidServiceName)?;
+        WideCString::from_str(&service_info.display_name)
+            .chain_err(|| ErrorKind::InvalidDisplayName)?;
+        let binary_path = launch_command(
+            service_info.service_type,
+            &service_info.executable_path,
+            &service_info.launch_arguments,
+        )?.to_os_string();
+        to_wide(service_info.load_order_group.as_ref())
+            .chain_err(|| ErrorKind::InvalidLoadOrderGroup)?;
+        dependencies_multi_string(&service_info.dependencies)?;
+        for setting in &service_info.settings {
+            validate_setting(setting)?;
+        }
+        self.check_access(ServiceManagerAccess::CREATE_SERVICE)?;
+
+        {
+            let mut database = self.scm.lock();
+            if let Ok(record) = database.get(&service_info.name) {
+                Err(system_error(if record.marked_for_delete {
+                    ERROR_SERVICE_MARKED_FOR_DELETE
+                } else {
+                    ERROR_SERVICE_EXISTS
+                }))?;
+            }
+
+            database.next_id += 1;
+            let mut record = ServiceRecord {
+                id: database.next_id,
+                info: ServiceInfo {
+                    account_password: None,
+                    settings: vec![],
+                    ..service_info.clone()
+                },
+                binary_path,
+                status: ServiceStatus {
+                    service_type: service_info.service_type,
+                    current_state: ServiceState::Stopped,
+                    controls_accepted: ServiceControlAccept::empty(),
+                    exit_code: ServiceExitCode::Win32(ERROR_SERVICE_NEVER_STARTED),
+                    checkpoint: 0,
+                    wait_hint: Duration::default(),
+                },
+                process_id: 0,
+                allowed_access: ServiceAccess::all(),
+                start_arguments: None,
+                marked_for_delete: false,
+                open_handles: 0,
+            };
+            for setting in &service_info.settings {
+                record.change_setting(setting);
+            }
+            database.services.insert(key(&service_info.name), record);
+            database
+                .pending_events
+                .push(ServiceEvent::Created(service_info.name.clone()));
+        }
+
+        Ok(Box::new(self.scm.open_handle(&service_info.name, service_access)?))
+    }
+
+    fn open_service(
+        &self,
+        name: &OsStr,
+        request_access: ServiceAccess,
+    ) -> Result<Box<dyn ServiceBackend>> {
+        WideCString::from_str(name).chain_err(|| ErrorKind::InvalidServiceName)?;
+        Ok(Box::new(self.scm.open_handle(name, request_access)?))
+    }
+
+    fn enumerate_page(&self, filter: &ServiceFilter, resume_handle: u32) -> Result<ServicePage> {
+        self.check_access(ServiceManagerAccess::ENUMERATE_SERVICE)?;
+        to_wide(filter.load_order_group.as_ref()).chain_err(|| ErrorKind::InvalidLoadOrderGroup)?;
+
+        // The resume handle is the position of the next record in the database, whether or not
+        // it passes the filter.
+        let database = self.scm.lock();
+        let mut services = Vec::new();
+        let records = database.services.values().enumerate();
+        for (index, record) in records.skip(resume_handle as usize) {
+            if !record.passes(filter) {
+                continue;
+            }
+            if services.len() == PAGE_SIZE {
+                return Ok(ServicePage {
+                    services,
+                    resume_handle: Some(index as u32),
+                });
+            }
+            services.push(record.entry_ext());
+        }
+        Ok(ServicePage {
+            services,
+            resume_handle: None,
+        })
+    }
+
+    fn watch_created_deleted(
+        &self,
+        events: mpsc::Sender<Result<ServiceEvent>>,
+    ) -> Result<WatchHandle> {
+        self.check_access(ServiceManagerAccess::ENUMERATE_SERVICE)?;
+        Ok(self.scm.add_watch(WatchTarget::CreatedDeleted, events))
+    }
+}
+
+impl ServiceRecord {
+    fn depends_on(&self, other: &ServiceRecord) -> bool {
+        self.info
+            .dependencies
+            .iter()
+            .any(|dependency| match *dependency {
+                ServiceDependency::Service(ref name) => key(name) == key(&other.info.name),
+                ServiceDependency::Group(ref group) => other
+                    .info
+                    .load_order_group
+                    .as_ref()
+                    .map_or(false, |other_group| key(other_group) == key(group)),
+            })
+    }
+
+    fn entry(&self) -> ServiceEntry {
+        ServiceEntry {
+            name: self.info.name.clone(),
+            display_name: self.info.display_name.clone(),
+            status: self.status.clone(),
+        }
+    }
+
+    fn entry_ext(&self) -> ServiceEntryExt {
+        ServiceEntryExt {
+            name: self.info.name.clone(),
+            display_name: self.info.display_name.clone(),
+            status: self.status_ext(),
+        }
+    }
+
+    fn status_ext(&self) -> ServiceStatusExt {
+        ServiceStatusExt {
+            status: self.status.clone(),
+            process_id: self.process_id,
+            service_flags: 0,
+        }
+    }
+
+    fn passes(&self, filter: &ServiceFilter) -> bool {
+        let group = self.info.load_order_group.as_ref().map(|group| key(group));
+        filter.service_type.matches(self.info.service_type)
+            && filter.state.matches(self.status.current_state)
+            && filter
+                .load_order_group
+                .as_ref()
+                .map_or(true, |filter_group| {
+                    key(filter_group) == group.unwrap_or_default()
+                })
+    }
+
+    fn setting(&self, kind: ServiceSettingKind) -> ServiceSetting {
+        self.info
+            .settings
+            .iter()
+            .find(|setting| setting.kind() == kind)
+            .cloned()
+            .unwrap_or_else(|| default_setting(kind))
+    }
+
+    fn change_setting(&mut self, setting: &ServiceSetting) {
+        let settings = &mut self.info.settings;
+        settings.retain(|existing| existing.kind() != setting.kind());
+        settings.push(setting.clone());
+    }
+
+    fn config(&self) -> Result<ServiceConfig> {
+        let (executable_path, launch_arguments) =
+            split_launch_command(self.info.service_type, &self.binary_path);
+
+        Ok(ServiceConfig {
+            service_type: self.info.service_type,
+            start_type: self.info.start_type,
+            error_control: self.info.error_control,
+            binary_path: self.binary_path.clone(),
+            executable_path,
+            launch_arguments,
+            load_order_group: self.info.load_order_group.clone(),
+            tag_id: 0,
+            dependencies: self.info.dependencies.clone(),
+            account_name: Some(
+                self.info
+                    .account_name
+                    .clone()
+                    .unwrap_or_else(|| OsString::from("LocalSystem")),
+            ),
+            display_name: self.info.display_name.clone(),
+        })
+    }
+}
+
+/// Backend for [`Service`] opened via [`FakeScm`].
+///
+/// [`Service`]: super::super::service::Service
+struct ServiceHandle {
+    scm: FakeScm,
+    key: String,
+    id: u64,
+    access: ServiceAccess,
+}
+
+impl ServiceHandle {
+    fn check_access(&self, required_access: ServiceAccess) -> io::Result<()> {
+        if self.access.contains(required_access) {
+            Ok(())
+        } else {
+            Err(system_error(ERROR_ACCESS_DENIED))
+        }
+    }
+}
+
+impl ServiceBackend for ServiceHandle {
+    fn start(&self, service_arguments: &[&OsStr]) -> Result<()> {
+        for argument in service_arguments {
+            WideCString::from_str(argument).chain_err(|| ErrorKind::InvalidStartArgument)?;
+        }
+        self.check_access(ServiceAccess::START)?;
+
+        let mut database = self.scm.lock();
+        let record = database.record(self)?;
+        if record.marked_for_delete {
+            Err(system_error(ERROR_SERVICE_MARKED_FOR_DELETE))?;
+        }
+        if record.info.start_type == ServiceStartType::Disabled {
+            Err(system_error(ERROR_SERVICE_DISABLED))?;
+        }
+        if record.status.current_state != ServiceState::Stopped {
+            Err(system_error(ERROR_SERVICE_ALREADY_RUNNING))?;
+        }
+
+        let mut arguments = vec![record.info.name.clone()];
+        arguments.extend(service_arguments.iter().map(|argument| argument.to_os_string()));
+        record.start_arguments = Some(arguments);
+        record.process_id = record.id as u32 + 1000;
+        // Like the system, report the service as starting until it reports its own status.
+        record.status = ServiceStatus {
+            service_type: record.info.service_type,
+            current_state: ServiceState::StartPending,
+            controls_accepted: ServiceControlAccept::empty(),
+            exit_code: ServiceExitCode::default(),
+            checkpoint: 0,
+            wait_hint: Duration::from_secs(2),
+        };
+        Ok(())
+    }
+
+    fn control(&self, control: ServiceControl) -> Result<ServiceStatus> {
+        let (required_access, required_accept) = match control {
+            ServiceControl::Stop => (ServiceAccess::STOP, ServiceControlAccept::STOP),
+            ServiceControl::Pause | ServiceControl::Continue => (
+                ServiceAccess::PAUSE_CONTINUE,
+                ServiceControlAccept::PAUSE_CONTINUE,
+            ),
+            ServiceControl::Interrogate => {
+                (ServiceAccess::INTERROGATE, ServiceControlAccept::empty())
+            }
+            ServiceControl::ParamChange => (
+                ServiceAccess::PAUSE_CONTINUE,
+                ServiceControlAccept::PARAM_CHANGE,
+            ),
+            ServiceControl::NetBindAdd
+            | ServiceControl::NetBindDisable
+            | ServiceControl::NetBindEnable
+            | ServiceControl::NetBindRemove => (
+                ServiceAccess::PAUSE_CONTINUE,
+                ServiceControlAccept::NETBIND_CHANGE,
+            ),
+            ServiceControl::UserDefined(_) => (
+                ServiceAccess::USER_DEFINED_CONTROL,
+                ServiceControlAccept::empty(),
+            ),
+            // Only the system can send the remaining controls.
+            _ => Err(system_error(ERROR_INVALID_SERVICE_CONTROL))?,
+        };
+        self.check_access(required_access)?;
+
+        let mut database = self.scm.lock();
+        database.record(self)?;
+        let dependents_running = database
+            .dependents(&self.key)
+            .iter()
+            .any(|dependent| dependent.status.current_state != ServiceState::Stopped);
+        let record = database.record(self)?;
+        let status = &mut record.status;
+        match status.current_state {
+            ServiceState::Stopped => Err(system_error(ERROR_SERVICE_NOT_ACTIVE))?,
+            ServiceState::StartPending | ServiceState::StopPending => {
+                Err(system_error(ERROR_SERVICE_CANNOT_ACCEPT_CTRL))?
+            }
+            _ => (),
+        }
+        if !status.controls_accepted.contains(required_accept) {
+            Err(system_error(ERROR_INVALID_SERVICE_CONTROL))?;
+        }
+        if control == ServiceControl::Stop && dependents_running {
+            Err(system_error(ERROR_DEPENDENT_SERVICES_RUNNING))?;
+        }
+
+        match control {
+            ServiceControl::Stop => {
+                status.current_state = ServiceState::Stopped;
+                status.controls_accepted = ServiceControlAccept::empty();
+                status.exit_code = ServiceExitCode::default();
+                record.process_id = 0;
+            }
+            ServiceControl::Pause => status.current_state = ServiceState::Paused,
+            ServiceControl::Continue => status.current_state = ServiceState::Running,
+            _ => (),
+        }
+        Ok(status.clone())
+    }
+
+    fn query_status(&self) -> Result<ServiceStatus> {
+        self.check_access(ServiceAccess::QUERY_STATUS)?;
+        let mut database = self.scm.lock();
+        Ok(database.record(self)?.status.clone())
+    }
+
+    fn query_config(&self) -> Result<ServiceConfig> {
+        self.check_access(ServiceAccess::QUERY_CONFIG)?;
+        let mut database = self.scm.lock();
+        database.record(self)?.config()
+    }
+
+    fn change_config(&self, change: &ServiceConfigChange) -> Result<()> {
+        if let Some(ref dependencies) = change.dependencies {
+            dependencies_multi_string(dependencies)?;
+        }
+        to_wide(change.account_name.as_ref()).chain_err(|| ErrorKind::InvalidAccountName)?;
+        to_wide(change.account_password.as_ref())
+            .chain_err(|| ErrorKind::InvalidAccountPassword)?;
+        to_wide(change.display_name.as_ref()).chain_err(|| ErrorKind::InvalidDisplayName)?;
+        self.check_access(ServiceAccess::SERVICE_CHANGE_CONFIG)?;
+
+        let mut database = self.scm.lock();
+        let record = database.record(self)?;
+        if record.marked_for_delete {
+            Err(system_error(ERROR_SERVICE_MARKED_FOR_DELETE))?;
+        }
+        let launch_command = change.launch_command(|| {
+            self.check_access(ServiceAccess::QUERY_CONFIG)?;
+            record.config()
+        })?;
+
+        if let Some(command) = launch_command {
+            record.binary_path = command.to_os_string();
+        }
+        let info = &mut record.info;
+        if let Some(service_type) = change.service_type {
+            info.service_type = service_type;
+            record.status.service_type = service_type;
+        }
+        if let Some(start_type) = change.start_type {
+            info.start_type = start_type;
+        }
+        if let Some(error_control) = change.error_control {
+            info.error_control = error_control;
+        }
+        if let Some(ref account_name) = change.account_name {
+            info.account_name = Some(account_name.clone());
+        }
+        if let Some(ref display_name) = change.display_name {
+            info.display_name = display_name.clone();
+        }
+        if let Some(ref dependencies) = change.dependencies {
+            info.dependencies = dependencies.clone();
+        }
+        Ok(())
+    }
+
+    fn query_setting(&self, kind: ServiceSettingKind) -> Result<ServiceSetting> {
+        self.check_access(ServiceAccess::QUERY_CONFIG)?;
+        let mut database = self.scm.lock();
+        Ok(database.record(self)?.setting(kind))
+    }
+
+    fn change_setting(&self, setting: &ServiceSetting) -> Result<()> {
+        validate_setting(setting)?;
+        self.check_access(ServiceAccess::SERVICE_CHANGE_CONFIG)?;
+        if let ServiceSetting::FailureActions(ref failure_actions) = *setting {
+            if failure_actions.restarts_service() {
+                self.check_access(ServiceAccess::START)?;
+            }
+        }
+        let mut database = self.scm.lock();
+        let record = database.record(self)?;
+        if record.marked_for_delete {
+            Err(system_error(ERROR_SERVICE_MARKED_FOR_DELETE))?;
+        }
+        record.change_setting(setting);
+        Ok(())
+    }
+
+    fn watch(
+        &self,
+        mask: ServiceNotifyMask,
+        events: mpsc::Sender<Result<ServiceEvent>>,
+    ) -> Result<WatchHandle> {
+        self.check_access(ServiceAccess::QUERY_STATUS)?;
+        let id = self.scm.lock().record(self)?.id;
+        let target = WatchTarget::Service {
+            id,
+            mask,
+            last_state: None,
+        };
+        Ok(self.scm.add_watch(target, events))
+    }
+
+    fn dependents(&self, state_filter: ServiceStateFilter) -> Result<Vec<ServiceEntry>> {
+        self.check_access(ServiceAccess::ENUMERATE_DEPENDENTS)?;
+        let mut database = self.scm.lock();
+        database.record(self)?;
+        Ok(database
+            .dependents(&self.key)
+            .into_iter()
+            .filter(|dependent| state_filter.matches(dependent.status.current_state))
+            .map(ServiceRecord::entry)
+            .collect())
+    }
+
+    fn open_service(
+        &self,
+        name: &OsStr,
+        request_access: ServiceAccess,
+    ) -> Result<Box<dyn ServiceBackend>> {
+        WideCString::from_str(name).chain_err(|| ErrorKind::InvalidServiceName)?;
+        Ok(Box::new(self.scm.open_handle(name, request_access)?))
+    }
+
+    fn delete(&self) -> io::Result<()> {
+        self.check_access(ServiceAccess::DELETE)?;
+        let mut database = self.scm.lock();
+        let record = database.record(self)?;
+        if record.marked_for_delete {
+            return Err(system_error(ERROR_SERVICE_MARKED_FOR_DELETE));
+        }
+        record.marked_for_delete = true;
+        Ok(())
+    }
+}
+
+impl Drop for ServiceHandle {
+    fn drop(&mut self) {
+        let mut database = self.scm.lock();
+        if let Ok(record) = database.record(self) {
+            record.open_handles -= 1;
+        }
+        database.collect_garbage();
+    }
+}
+
+/// Service names are case insensitive.
+fn key(name: &OsStr) -> String {
+    name.to_string_lossy().to_lowercase()
+}
+
+fn to_wide<T: AsRef<OsStr>>(s: Option<T>) -> ::std::result::Result<Option<WideCString>, NulError> {
+    s.map(WideCString::from_str).transpose()
+}
+
+/// Check that the setting can be passed to the system.
+fn validate_setting(setting: &ServiceSetting) -> Result<()> {
+    match *setting {
+        ServiceSetting::Description(ref description) => {
+            WideCString::from_str(description).chain_err(|| ErrorKind::InvalidDescription)?;
+        }
+        ServiceSetting::FailureActions(ref failure_actions) => {
+            failure_actions.to_raw()?;
+        }
+        ServiceSetting::RequiredPrivileges(ref privileges) => {
+            privileges_multi_string(privileges)?;
+        }
+        ServiceSetting::Triggers(ref triggers) => {
+            for trigger in triggers {
+                trigger.to_raw()?;
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+/// The value the system reports for a setting that was never changed.
+fn default_setting(kind: ServiceSettingKind) -> ServiceSetting {
+    match kind {
+        ServiceSettingKind::Description => ServiceSetting::Description(OsString::new()),
+        ServiceSettingKind::FailureActions => {
+            ServiceSetting::FailureActions(ServiceFailureActions {
+                reset_period: ServiceFailureResetPeriod::After(Duration::default()),
+                reboot_msg: None,
+                command: None,
+                actions: vec![],
+            })
+        }
+        ServiceSettingKind::DelayedAutoStart => ServiceSetting::DelayedAutoStart(false),
+        ServiceSettingKind::PreshutdownTimeout => {
+            ServiceSetting::PreshutdownTimeout(Duration::from_secs(180))
+        }
+        ServiceSettingKind::RequiredPrivileges => ServiceSetting::RequiredPrivileges(vec![]),
+        ServiceSettingKind::SidType => ServiceSetting::SidType(ServiceSidType::None),
+        ServiceSettingKind::FailureActionsOnNonCrashFailures => {
+            ServiceSetting::FailureActionsOnNonCrashFailures(false)
+        }
+        ServiceSettingKind::PreferredNode => ServiceSetting::PreferredNode(None),
+        ServiceSettingKind::Triggers => ServiceSetting::Triggers(vec![]),
+    }
+}
+
+fn system_error(code: u32) -> io::Error {
+    io::Error::from_raw_os_error(code as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use service::{
+        Service, ServiceAction, ServiceActionType, ServiceErrorControl, ServiceType,
+        ServiceTypeFilter,
+    };
+    use service_trigger::{ServiceTrigger, ServiceTriggerAction};
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use watch::ServiceWatch;
+
+    fn service_info(name: &str) -> ServiceInfo {
+        ServiceInfo {
+            name: OsString::from(name),
+            display_name: OsString::from("Test service"),
+            service_type: ServiceType::OwnProcess,
+            start_type: ServiceStartType::OnDemand,
+            error_control: ServiceErrorControl::Normal,
+            executable_path: PathBuf::from(r"C:\Program Files\Test\service.exe"),
+            launch_arguments: vec![OsString::from("--service")],
+            load_order_group: None,
+            dependencies: vec![],
+            account_name: None,
+            account_password: None,
+            settings: vec![],
+        }
+    }
+
+    fn raw_os_error(error: ::Error) -> Option<u32> {
+        match error.kind() {
+            ErrorKind::System(ref io_error) => io_error.raw_os_error().map(|code| code as u32),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_create_and_query() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(ServiceManagerAccess::CREATE_SERVICE);
+        let service = manager
+            .create_service(
+                service_info("Test"),
+                ServiceAccess::QUERY_STATUS | ServiceAccess::QUERY_CONFIG,
+            )
+            .unwrap();
+
+        let status = service.query_status().unwrap();
+        assert_eq!(status.current_state, ServiceState::Stopped);
+        assert_eq!(
+            status.exit_code,
+            ServiceExitCode::Win32(ERROR_SERVICE_NEVER_STARTED)
+        );
+
+        let config = service.query_config().unwrap();
+        assert_eq!(
+            config.executable_path,
+            PathBuf::from(r"C:\Program Files\Test\service.exe")
+        );
+        assert_eq!(config.launch_arguments, vec![OsString::from("--service")]);
+        assert_eq!(config.account_name, Some(OsString::from("LocalSystem")));
+
+        let list = scm
+            .connect(ServiceManagerAccess::ENUMERATE_SERVICE)
+            .list_services()
+            .unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(
+            list[0].to_detail().binary_path,
+            Some(OsString::from(r#""C:\Program Files\Test\service.exe" --service"#))
+        );
+        assert!(scm.contains("TEST"));
+    }
+
+    #[test]
+    fn test_query_config_unquoted_executable_path() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(ServiceManagerAccess::CREATE_SERVICE);
+        let service = manager
+            .create_service(service_info("test"), ServiceAccess::QUERY_CONFIG)
+            .unwrap();
+        scm.set_binary_path("test", r"C:\Program Files\Foo\svc.exe -x")
+            .unwrap();
+
+        let config = service.query_config().unwrap();
+        assert_eq!(
+            config.binary_path,
+            OsString::from(r"C:\Program Files\Foo\svc.exe -x")
+        );
+        assert_eq!(
+            config.executable_path,
+            PathBuf::from(r"C:\Program Files\Foo\svc.exe")
+        );
+        assert_eq!(config.launch_arguments, vec![OsString::from("-x")]);
+    }
+
+    #[test]
+    fn test_list_services_reports_binary_path_verbatim() {
+        let scm = FakeScm::new();
+        scm.connect(ServiceManagerAccess::CREATE_SERVICE)
+            .create_service(service_info("test"), ServiceAccess::empty())
+            .unwrap();
+        let binary_path = OsString::from(r#"C:\Program Files\Foo\svc.exe -x "quoted arg""#);
+        scm.set_binary_path("test", binary_path.clone()).unwrap();
+
+        let list = scm
+            .connect(ServiceManagerAccess::ENUMERATE_SERVICE)
+            .list_services()
+            .unwrap();
+        assert_eq!(list[0].to_detail().binary_path, Some(binary_path));
+    }
+
+    #[test]
+    fn test_file_system_driver_path_round_trip() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(ServiceManagerAccess::CREATE_SERVICE);
+        let driver_path = PathBuf::from(r"C:\Program Files\Foo\filter.sys");
+        let service = manager
+            .create_service(
+                ServiceInfo {
+                    service_type: ServiceType::FileSystemDriver,
+                    executable_path: driver_path.clone(),
+                    launch_arguments: vec![],
+                    ..service_info("filter")
+                },
+                ServiceAccess::QUERY_CONFIG | ServiceAccess::SERVICE_CHANGE_CONFIG,
+            )
+            .unwrap();
+
+        let config = service.query_config().unwrap();
+        assert_eq!(config.binary_path, driver_path.as_os_str());
+        assert_eq!(config.executable_path, driver_path);
+
+        service
+            .change_config(&ServiceConfigChange::new().launch_arguments(Vec::<OsString>::new()))
+            .unwrap();
+        assert_eq!(
+            service.query_config().unwrap().binary_path,
+            driver_path.as_os_str()
+        );
+    }
+
+    #[test]
+    fn test_create_with_dependencies() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(
+            ServiceManagerAccess::CREATE_SERVICE | ServiceManagerAccess::ENUMERATE_SERVICE,
+        );
+        let dependencies = vec![
+            ServiceDependency::Service(OsString::from("Tcpip")),
+            ServiceDependency::Group(OsString::from("NetworkProvider")),
+        ];
+        let service = manager
+            .create_service(
+                ServiceInfo {
+                    load_order_group: Some(OsString::from("Extended Base")),
+                    dependencies: dependencies.clone(),
+                    ..service_info("test")
+                },
+                ServiceAccess::QUERY_CONFIG,
+            )
+            .unwrap();
+
+        let config = service.query_config().unwrap();
+        assert_eq!(config.load_order_group, Some(OsString::from("Extended Base")));
+        assert_eq!(config.dependencies, dependencies);
+        assert_eq!(
+            manager.list_services().unwrap()[0].to_detail().dependencies,
+            Some(dependencies)
+        );
+
+        let error = manager
+            .create_service(
+                ServiceInfo {
+                    dependencies: vec![ServiceDependency::Service(OsString::new())],
+                    ..service_info("other")
+                },
+                ServiceAccess::empty(),
+            )
+            .err()
+            .unwrap();
+        match *error.kind() {
+            ErrorKind::InvalidDependency => (),
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_start_service() {
+        let scm = FakeScm::new();
+        let service = scm
+            .connect(ServiceManagerAccess::CREATE_SERVICE)
+            .create_service(
+                service_info("test"),
+                ServiceAccess::START | ServiceAccess::QUERY_STATUS,
+            )
+            .unwrap();
+        assert_eq!(scm.start_arguments("test"), None);
+
+        service.start(&["--verbose", "two words"]).unwrap();
+        assert_eq!(
+            service.query_status().unwrap().current_state,
+            ServiceState::StartPending
+        );
+        assert_eq!(
+            scm.start_arguments("test"),
+            Some(vec![
+                OsString::from("test"),
+                OsString::from("--verbose"),
+                OsString::from("two words"),
+            ])
+        );
+
+        let error = service.start::<&str>(&[]).unwrap_err();
+        assert_eq!(raw_os_error(error), Some(ERROR_SERVICE_ALREADY_RUNNING));
+    }
+
+    #[test]
+    fn test_start_disabled_service() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(ServiceManagerAccess::CREATE_SERVICE);
+        let service_info = ServiceInfo {
+            start_type: ServiceStartType::Disabled,
+            ..service_info("test")
+        };
+        manager
+            .create_service(service_info, ServiceAccess::empty())
+            .unwrap();
+
+        let service = manager
+            .open_service("test", ServiceAccess::QUERY_STATUS)
+            .unwrap();
+        let error = service.start::<&str>(&[]).unwrap_err();
+        assert_eq!(raw_os_error(error), Some(ERROR_ACCESS_DENIED));
+
+        let service = manager.open_service("test", ServiceAccess::START).unwrap();
+        let error = service.start::<&str>(&[]).unwrap_err();
+        assert_eq!(raw_os_error(error), Some(ERROR_SERVICE_DISABLED));
+    }
+
+    #[test]
+    fn test_pause_resume_and_user_control() {
+        let scm = FakeScm::new();
+        let service = scm
+            .connect(ServiceManagerAccess::CREATE_SERVICE)
+            .create_service(
+                service_info("test"),
+                ServiceAccess::PAUSE_CONTINUE
+                    | ServiceAccess::INTERROGATE
+                    | ServiceAccess::USER_DEFINED_CONTROL,
+            )
+            .unwrap();
+        assert_eq!(
+            raw_os_error(service.interrogate().unwrap_err()),
+            Some(ERROR_SERVICE_NOT_ACTIVE)
+        );
+
+        scm.set_state("test", ServiceState::Running).unwrap();
+        assert_eq!(service.pause().unwrap().current_state, ServiceState::Paused);
+        assert_eq!(
+            service.interrogate().unwrap().current_state,
+            ServiceState::Paused
+        );
+        assert_eq!(service.resume().unwrap().current_state, ServiceState::Running);
+        assert_eq!(
+            service.send_user_control(128).unwrap().current_state,
+            ServiceState::Running
+        );
+
+        match *service.send_user_control(127).unwrap_err().kind() {
+            ErrorKind::InvalidServiceControl(127) => (),
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_stop_with_dependents() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(ServiceManagerAccess::CREATE_SERVICE);
+        let services = vec![
+            ("base", Some("Core"), vec![]),
+            ("web", None, vec![ServiceDependency::Service(OsString::from("BASE"))]),
+            ("proxy", None, vec![ServiceDependency::Service(OsString::from("web"))]),
+            ("plugin", None, vec![ServiceDependency::Group(OsString::from("core"))]),
+            ("idle", None, vec![ServiceDependency::Service(OsString::from("base"))]),
+        ];
+        for (name, group, dependencies) in services {
+            let service_info = ServiceInfo {
+                load_order_group: group.map(OsString::from),
+                dependencies,
+                ..service_info(name)
+            };
+            manager
+                .create_service(service_info, ServiceAccess::empty())
+                .unwrap();
+            if name != "idle" {
+                scm.set_state(name, ServiceState::Running).unwrap();
+            }
+        }
+
+        let service = manager
+            .open_service(
+                "base",
+                ServiceAccess::ENUMERATE_DEPENDENTS
+                    | ServiceAccess::STOP
+                    | ServiceAccess::QUERY_STATUS,
+            )
+            .unwrap();
+        let names = |entries: Vec<&ServiceEntry>| -> Vec<OsString> {
+            entries.into_iter().map(|entry| entry.name.clone()).collect()
+        };
+        let dependents = service.dependents(ServiceStateFilter::All).unwrap();
+        assert_eq!(
+            names(dependents.iter().collect()),
+            vec!["idle", "plugin", "proxy", "web"]
+        );
+        assert_eq!(
+            raw_os_error(service.stop().unwrap_err()),
+            Some(ERROR_DEPENDENT_SERVICES_RUNNING)
+        );
+
+        let report = service
+            .stop_with_dependents(Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(report.status.current_state, ServiceState::Stopped);
+        assert_eq!(
+            names(report.dependents.iter().collect()),
+            vec!["plugin", "proxy", "web"]
+        );
+        assert!(report
+            .dependents
+            .iter()
+            .all(|dependent| dependent.status.current_state == ServiceState::Running));
+        assert_eq!(
+            names(report.restart_order().collect()),
+            vec!["web", "proxy", "plugin"]
+        );
+        for name in &["base", "web", "proxy", "plugin"] {
+            assert_eq!(
+                scm.status(name).unwrap().current_state,
+                ServiceState::Stopped
+            );
+        }
+    }
+
+    #[test]
+    fn test_create_existing_service() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(ServiceManagerAccess::CREATE_SERVICE);
+        manager
+            .create_service(service_info("test"), ServiceAccess::empty())
+            .unwrap();
+        let error = manager
+            .create_service(service_info("test"), ServiceAccess::empty())
+            .err()
+            .unwrap();
+        assert_eq!(raw_os_error(error), Some(ERROR_SERVICE_EXISTS));
+    }
+
+    #[test]
+    fn test_manager_access_check() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(ServiceManagerAccess::CONNECT);
+        let error = manager
+            .create_service(service_info("test"), ServiceAccess::empty())
+            .err()
+            .unwrap();
+        assert_eq!(raw_os_error(error), Some(ERROR_ACCESS_DENIED));
+        assert_eq!(
+            raw_os_error(manager.list_services().unwrap_err()),
+            Some(ERROR_ACCESS_DENIED)
+        );
+    }
+
+    #[test]
+    fn test_enumerate() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(ServiceManagerAccess::CREATE_SERVICE);
+        let services = vec![
+            ("a", ServiceType::OwnProcess, None),
+            ("b", ServiceType::Win32ShareProcess, Some("Net")),
+            ("c", ServiceType::OwnProcess, None),
+            ("d", ServiceType::KernelDriver, Some("SCSI Class")),
+            ("e", ServiceType::OwnProcess, Some("net")),
+            ("f", ServiceType::FileSystemDriver, None),
+            ("g", ServiceType::OwnProcess, None),
+        ];
+        for (name, service_type, group) in services {
+            let service_info = ServiceInfo {
+                service_type,
+                load_order_group: group.map(OsString::from),
+                ..service_info(name)
+            };
+            manager
+                .create_service(service_info, ServiceAccess::empty())
+                .unwrap();
+        }
+        scm.set_state("d", ServiceState::Running).unwrap();
+        scm.set_state("e", ServiceState::Running).unwrap();
+
+        let manager = scm.connect(ServiceManagerAccess::ENUMERATE_SERVICE);
+        let names = |filter: ServiceFilter| -> Vec<OsString> {
+            manager
+                .enumerate(filter)
+                .map(|entry| entry.unwrap().name)
+                .collect()
+        };
+        assert_eq!(
+            names(ServiceFilter::new()),
+            vec!["a", "b", "c", "d", "e", "f", "g"]
+        );
+        assert_eq!(
+            names(ServiceFilter::new().service_type(ServiceTypeFilter::WIN32)),
+            vec!["a", "b", "c", "e", "g"]
+        );
+        assert_eq!(
+            names(
+                ServiceFilter::new()
+                    .service_type(ServiceTypeFilter::DRIVER)
+                    .state(ServiceStateFilter::Active)
+            ),
+            vec!["d"]
+        );
+        assert_eq!(
+            names(ServiceFilter::new().state(ServiceStateFilter::Inactive)),
+            vec!["a", "b", "c", "f", "g"]
+        );
+        assert_eq!(
+            names(ServiceFilter::new().load_order_group("NET")),
+            vec!["b", "e"]
+        );
+        assert_eq!(
+            names(ServiceFilter::new().load_order_group("")),
+            vec!["a", "c", "f", "g"]
+        );
+
+        let backend = FakeServiceManager {
+            scm: scm.clone(),
+            access: ServiceManagerAccess::ENUMERATE_SERVICE,
+        };
+        let page = backend.enumerate_page(&ServiceFilter::new(), 0).unwrap();
+        assert_eq!(page.services.len(), PAGE_SIZE);
+        assert_eq!(page.resume_handle, Some(PAGE_SIZE as u32));
+        assert_eq!(page.services[3].status.status.current_state, ServiceState::Running);
+
+        // Configuration is only queried on request, and requires access to the services.
+        let mut with_config = manager
+            .enumerate(ServiceFilter::new().load_order_group("net"))
+            .with_config();
+        let (entry, config) = with_config.next().unwrap().unwrap();
+        assert_eq!(entry.name, "b");
+        assert_eq!(config.service_type, ServiceType::Win32ShareProcess);
+        assert_eq!(config.load_order_group, Some(OsString::from("Net")));
+        scm.set_allowed_access("e", ServiceAccess::QUERY_STATUS).unwrap();
+        assert_eq!(
+            raw_os_error(with_config.next().unwrap().unwrap_err()),
+            Some(ERROR_ACCESS_DENIED)
+        );
+        assert!(with_config.next().is_none());
+
+        let manager = scm.connect(ServiceManagerAccess::CONNECT);
+        let mut entries = manager.enumerate(ServiceFilter::new());
+        assert_eq!(
+            raw_os_error(entries.next().unwrap().unwrap_err()),
+            Some(ERROR_ACCESS_DENIED)
+        );
+        assert!(entries.next().is_none());
+    }
+
+    /// Deletes a service right after each page is enumerated, before the configuration of the
+    /// enumerated services is queried.
+    struct DeleteAfterEnumeration {
+        inner: FakeServiceManager,
+        name: &'static str,
+    }
+
+    impl ServiceManagerBackend for DeleteAfterEnumeration {
+        fn create_service(
+            &self,
+            service_info: &ServiceInfo,
+            service_access: ServiceAccess,
+        ) -> Result<Box<dyn ServiceBackend>> {
+            self.inner.create_service(service_info, service_access)
+        }
+
+        fn open_service(
+            &self,
+            name: &OsStr,
+            request_access: ServiceAccess,
+        ) -> Result<Box<dyn ServiceBackend>> {
+            self.inner.open_service(name, request_access)
+        }
+
+        fn enumerate_page(
+            &self,
+            filter: &ServiceFilter,
+            resume_handle: u32,
+        ) -> Result<ServicePage> {
+            let page = self.inner.enumerate_page(filter, resume_handle)?;
+            if self.inner.scm.contains(self.name) {
+                self.inner
+                    .scm
+                    .connect(ServiceManagerAccess::CONNECT)
+                    .open_service(self.name, ServiceAccess::DELETE)?
+                    .delete()?;
+            }
+            Ok(page)
+        }
+
+        fn watch_created_deleted(
+            &self,
+            events: mpsc::Sender<Result<ServiceEvent>>,
+        ) -> Result<WatchHandle> {
+            self.inner.watch_created_deleted(events)
+        }
+    }
+
+    #[test]
+    fn test_list_services_partial_failure() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(ServiceManagerAccess::CREATE_SERVICE);
+        for name in &["deleted", "denied", "marked", "normal"] {
+            manager
+                .create_service(service_info(name), ServiceAccess::empty())
+                .unwrap();
+        }
+        scm.set_allowed_access("denied", ServiceAccess::QUERY_STATUS)
+            .unwrap();
+        scm.set_state("marked", ServiceState::Running).unwrap();
+        manager
+            .open_service("marked", ServiceAccess::DELETE)
+            .unwrap()
+            .delete()
+            .unwrap();
+
+        let manager = ServiceManager::with_backend(DeleteAfterEnumeration {
+            inner: FakeServiceManager {
+                scm: scm.clone(),
+                access: ServiceManagerAccess::ENUMERATE_SERVICE,
+            },
+            name: "deleted",
+        });
+        let mut listing = manager.list_services().unwrap().into_iter();
+
+        let deleted = listing.next().unwrap();
+        assert_eq!(deleted.entry.name, "deleted");
+        assert_eq!(
+            raw_os_error(deleted.config.unwrap_err()),
+            Some(ERROR_SERVICE_DOES_NOT_EXIST)
+        );
+
+        let denied = listing.next().unwrap();
+        let detail = denied.to_detail();
+        assert_eq!(detail.name, "denied");
+        assert_eq!(detail.status.status.current_state, ServiceState::Stopped);
+        assert_eq!(detail.binary_path, None);
+        assert_eq!(detail.tag_id, None);
+        assert_eq!(
+            raw_os_error(denied.failure_actions.unwrap_err()),
+            Some(ERROR_ACCESS_DENIED)
+        );
+        assert_eq!(
+            raw_os_error(denied.config.unwrap_err()),
+            Some(ERROR_ACCESS_DENIED)
+        );
+
+        let marked = listing.next().unwrap();
+        assert_eq!(marked.entry.status.status.current_state, ServiceState::Running);
+        assert_eq!(
+            raw_os_error(marked.config.unwrap_err()),
+            Some(ERROR_SERVICE_MARKED_FOR_DELETE)
+        );
+
+        let normal = listing.next().unwrap();
+        let detail = normal.to_detail();
+        assert_eq!(detail.error_control, Some(ServiceErrorControl::Normal));
+        assert_eq!(detail.tag_id, Some(0));
+        assert_eq!(detail.start_name, Some(OsString::from("LocalSystem")));
+        assert!(detail.failure_actions.is_some());
+        assert_eq!(normal.config.unwrap().display_name, "Test service");
+        assert!(listing.next().is_none());
+    }
+
+    /// Opens services that deny querying their failure actions.
+    struct DenyFailureActions {
+        inner: FakeServiceManager,
+    }
+
+    impl ServiceManagerBackend for DenyFailureActions {
+        fn create_service(
+            &self,
+            service_info: &ServiceInfo,
+            service_access: ServiceAccess,
+        ) -> Result<Box<dyn ServiceBackend>> {
+            self.inner.create_service(service_info, service_access)
+        }
+
+        fn open_service(
+            &self,
+            name: &OsStr,
+            request_access: ServiceAccess,
+        ) -> Result<Box<dyn ServiceBackend>> {
+            let inner = self.inner.open_service(name, request_access)?;
+            Ok(Box::new(SettingOverride {
+                inner,
+                query: |inner, kind| match kind {
+                    ServiceSettingKind::FailureActions => {
+                        Err(system_error(ERROR_ACCESS_DENIED).into())
+                    }
+                    kind => inner.query_setting(kind),
+                },
+            }))
+        }
+
+        fn enumerate_page(
+            &self,
+            filter: &ServiceFilter,
+            resume_handle: u32,
+        ) -> Result<ServicePage> {
+            self.inner.enumerate_page(filter, resume_handle)
+        }
+
+        fn watch_created_deleted(
+            &self,
+            events: mpsc::Sender<Result<ServiceEvent>>,
+        ) -> Result<WatchHandle> {
+            self.inner.watch_created_deleted(events)
+        }
+    }
+
+    #[test]
+    fn test_list_services_failure_actions_denied() {
+        let scm = FakeScm::new();
+        scm.connect(ServiceManagerAccess::CREATE_SERVICE)
+            .create_service(service_info("test"), ServiceAccess::empty())
+            .unwrap();
+        let manager = ServiceManager::with_backend(DenyFailureActions {
+            inner: FakeServiceManager {
+                scm: scm.clone(),
+                access: ServiceManagerAccess::ENUMERATE_SERVICE,
+            },
+        });
+
+        let listing = manager.list_services().unwrap().pop().unwrap();
+        let detail = listing.to_detail();
+        assert_eq!(detail.start_type, Some(ServiceStartType::OnDemand));
+        assert_eq!(detail.failure_actions, None);
+        assert!(listing.config.is_ok());
+        assert_eq!(
+            raw_os_error(listing.failure_actions.unwrap_err()),
+            Some(ERROR_ACCESS_DENIED)
+        );
+    }
+
+    #[test]
+    fn test_watch_service() {
+        let scm = FakeScm::new();
+        let service = scm
+            .connect(ServiceManagerAccess::CREATE_SERVICE)
+            .create_service(
+                service_info("test"),
+                ServiceAccess::QUERY_STATUS | ServiceAccess::START | ServiceAccess::DELETE,
+            )
+            .unwrap();
+        let states = |watch: &ServiceWatch| -> Vec<ServiceState> {
+            watch
+                .try_iter()
+                .map(|event| match event.unwrap() {
+                    ServiceEvent::StateChanged(status) => status.status.current_state,
+                    event => panic!("unexpected event {:?}", event),
+                })
+                .collect()
+        };
+
+        let watch = service
+            .watch(ServiceNotifyMask::STOPPED | ServiceNotifyMask::RUNNING)
+            .unwrap();
+        let all_states = service.watch(ServiceNotifyMask::ANY_STATE).unwrap();
+        assert_eq!(states(&watch), vec![ServiceState::Stopped]);
+
+        service.start(&[] as &[&OsStr]).unwrap();
+        scm.set_state("test", ServiceState::Running).unwrap();
+        // Status updates that do not change the state are not reported.
+        scm.set_state("test", ServiceState::Running).unwrap();
+        scm.set_state("test", ServiceState::Stopped).unwrap();
+        assert_eq!(
+            states(&watch),
+            vec![ServiceState::Running, ServiceState::Stopped]
+        );
+        assert_eq!(
+            states(&all_states),
+            vec![
+                ServiceState::Stopped,
+                ServiceState::StartPending,
+                ServiceState::Running,
+                ServiceState::Stopped,
+            ]
+        );
+
+        let delete_pending = service
+            .watch(ServiceNotifyMask::DELETE_PENDING)
+            .unwrap();
+        drop(all_states);
+        service.delete().unwrap();
+        assert_eq!(
+            delete_pending.collect::<Vec<_>>().len(),
+            1,
+            "the watch ends after reporting the deletion"
+        );
+        assert!(watch.recv().is_none());
+        assert!(scm.lock().watches.is_empty());
+    }
+
+    #[test]
+    fn test_watch_created_deleted() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(ServiceManagerAccess::CREATE_SERVICE);
+        assert_eq!(
+            raw_os_error(manager.watch_created_deleted().err().unwrap()),
+            Some(ERROR_ACCESS_DENIED)
+        );
+        let watch = scm
+            .connect(ServiceManagerAccess::ENUMERATE_SERVICE)
+            .watch_created_deleted()
+            .unwrap();
+
+        let service = manager
+            .create_service(service_info("test"), ServiceAccess::DELETE)
+            .unwrap();
+        assert_eq!(
+            watch.try_recv().unwrap().unwrap(),
+            ServiceEvent::Created(OsString::from("test"))
+        );
+        service.delete().unwrap();
+        assert_eq!(
+            watch.try_recv().unwrap().unwrap(),
+            ServiceEvent::Deleted(OsString::from("test"))
+        );
+        assert!(watch.try_recv().is_err());
+
+        drop(watch);
+        assert!(scm.lock().watches.is_empty());
+    }
+
+    #[test]
+    fn test_change_config() {
+        let scm = FakeScm::new();
+        let service = scm
+            .connect(ServiceManagerAccess::CREATE_SERVICE)
+            .create_service(
+                service_info("test"),
+                ServiceAccess::QUERY_CONFIG | ServiceAccess::SERVICE_CHANGE_CONFIG,
+            )
+            .unwrap();
+
+        service
+            .change_config(
+                &ServiceConfigChange::new()
+                    .start_type(ServiceStartType::AutoStart)
+                    .display_name("Renamed service")
+                    .launch_arguments(vec!["--service", "--log level"])
+                    .dependencies(vec![ServiceDependency::Service(OsString::from("Tcpip"))]),
+            )
+            .unwrap();
+
+        let config = service.query_config().unwrap();
+        assert_eq!(config.start_type, ServiceStartType::AutoStart);
+        assert_eq!(config.error_control, ServiceErrorControl::Normal);
+        assert_eq!(config.display_name, OsString::from("Renamed service"));
+        assert_eq!(
+            config.executable_path,
+            PathBuf::from(r"C:\Program Files\Test\service.exe")
+        );
+        assert_eq!(
+            config.launch_arguments,
+            vec![OsString::from("--service"), OsString::from("--log level")]
+        );
+        assert_eq!(
+            config.dependencies,
+            vec![ServiceDependency::Service(OsString::from("Tcpip"))]
+        );
+
+        service
+            .change_config(&ServiceConfigChange::new().executable_path(r"C:\Agent\agent.exe"))
+            .unwrap();
+        let config = service.query_config().unwrap();
+        assert_eq!(config.executable_path, PathBuf::from(r"C:\Agent\agent.exe"));
+        assert_eq!(config.launch_arguments.len(), 2);
+    }
+
+    #[test]
+    fn test_change_launch_arguments_keeps_unquoted_executable_path() {
+        let scm = FakeScm::new();
+        let service = scm
+            .connect(ServiceManagerAccess::CREATE_SERVICE)
+            .create_service(
+                service_info("test"),
+                ServiceAccess::QUERY_CONFIG | ServiceAccess::SERVICE_CHANGE_CONFIG,
+            )
+            .unwrap();
+        scm.set_binary_path("test", r"C:\Program Files\Foo\svc.exe -x")
+            .unwrap();
+
+        service
+            .change_config(&ServiceConfigChange::new().launch_arguments(vec!["-y", "a b"]))
+            .unwrap();
+
+        let config = service.query_config().unwrap();
+        assert_eq!(
+            config.binary_path,
+            OsString::from(r#"C:\Program Files\Foo\svc.exe -y "a b""#)
+        );
+        assert_eq!(
+            config.executable_path,
+            PathBuf::from(r"C:\Program Files\Foo\svc.exe")
+        );
+        assert_eq!(
+            config.launch_arguments,
+            vec![OsString::from("-y"), OsString::from("a b")]
+        );
+    }
+
+    #[test]
+    fn test_change_service_type_and_launch_arguments() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(ServiceManagerAccess::CREATE_SERVICE);
+        let driver_path = PathBuf::from(r"C:\Program Files\Foo\filter.sys");
+        let service = manager
+            .create_service(
+                ServiceInfo {
+                    service_type: ServiceType::KernelDriver,
+                    executable_path: driver_path.clone(),
+                    launch_arguments: vec![],
+                    ..service_info("filter")
+                },
+                ServiceAccess::QUERY_CONFIG | ServiceAccess::SERVICE_CHANGE_CONFIG,
+            )
+            .unwrap();
+
+        // The path of the driver is quoted once the service runs in a process.
+        service
+            .change_config(
+                &ServiceConfigChange::new()
+                    .service_type(ServiceType::OwnProcess)
+                    .launch_arguments(vec!["-x"]),
+            )
+            .unwrap();
+        let config = service.query_config().unwrap();
+        assert_eq!(
+            config.binary_path,
+            OsString::from(r#""C:\Program Files\Foo\filter.sys" -x"#)
+        );
+        assert_eq!(config.executable_path, driver_path);
+
+        // And written without quotes again for a driver.
+        service
+            .change_config(
+                &ServiceConfigChange::new()
+                    .service_type(ServiceType::KernelDriver)
+                    .launch_arguments(Vec::<OsString>::new()),
+            )
+            .unwrap();
+        let config = service.query_config().unwrap();
+        assert_eq!(config.binary_path, driver_path.as_os_str());
+        assert_eq!(config.executable_path, driver_path);
+    }
+
+    #[test]
+    fn test_change_config_access_check() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(ServiceManagerAccess::CREATE_SERVICE);
+        manager
+            .create_service(service_info("test"), ServiceAccess::empty())
+            .unwrap();
+
+        let service = manager
+            .open_service("test", ServiceAccess::QUERY_CONFIG)
+            .unwrap();
+        let change = ServiceConfigChange::new().start_type(ServiceStartType::Disabled);
+        assert_eq!(
+            raw_os_error(service.change_config(&change).unwrap_err()),
+            Some(ERROR_ACCESS_DENIED)
+        );
+
+        // The rest of the command line has to be read back when only the arguments change.
+        let service = manager
+            .open_service("test", ServiceAccess::SERVICE_CHANGE_CONFIG)
+            .unwrap();
+        let change = ServiceConfigChange::new().launch_arguments(vec!["--debug"]);
+        assert_eq!(
+            raw_os_error(service.change_config(&change).unwrap_err()),
+            Some(ERROR_ACCESS_DENIED)
+        );
+        let change = change
+            .service_type(ServiceType::OwnProcess)
+            .executable_path(r"C:\Agent\agent.exe");
+        service.change_config(&change).unwrap();
+    }
+
+    #[test]
+    fn test_create_with_settings() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(ServiceManagerAccess::CREATE_SERVICE);
+        let service_info = ServiceInfo {
+            settings: vec![
+                ServiceSetting::Description(OsString::from("Test description")),
+                ServiceSetting::DelayedAutoStart(true),
+                ServiceSetting::SidType(ServiceSidType::Unrestricted),
+            ],
+            ..service_info("test")
+        };
+        let service = manager
+            .create_service(
+                service_info,
+                ServiceAccess::QUERY_CONFIG | ServiceAccess::SERVICE_CHANGE_CONFIG,
+            )
+            .unwrap();
+
+        assert_eq!(service.description().unwrap(), OsString::from("Test description"));
+        assert!(service.delayed_auto_start().unwrap());
+        assert_eq!(service.sid_type().unwrap(), ServiceSidType::Unrestricted);
+        assert_eq!(
+            service.preshutdown_timeout().unwrap(),
+            Duration::from_secs(180)
+        );
+        assert_eq!(service.preferred_node().unwrap(), None);
+
+        service.set_description("").unwrap();
+        service
+            .set_required_privileges(vec!["SeChangeNotifyPrivilege"])
+            .unwrap();
+        service.set_preferred_node(Some(1)).unwrap();
+        assert_eq!(service.description().unwrap(), OsString::new());
+        assert_eq!(
+            service.required_privileges().unwrap(),
+            vec![OsString::from("SeChangeNotifyPrivilege")]
+        );
+        assert_eq!(service.preferred_node().unwrap(), Some(1));
+    }
+
+    #[test]
+    fn test_create_with_invalid_setting() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(ServiceManagerAccess::CREATE_SERVICE);
+        let service_info = ServiceInfo {
+            settings: vec![
+                ServiceSetting::Description(OsString::from("Test description")),
+                ServiceSetting::RequiredPrivileges(vec![OsString::new()]),
+            ],
+            ..service_info("test")
+        };
+        match manager.create_service(service_info, ServiceAccess::empty()) {
+            Err(ref error) => match error.kind() {
+                ErrorKind::InvalidRequiredPrivilege => (),
+                kind => panic!("unexpected error: {}", kind),
+            },
+            Ok(_) => panic!("created a service with an invalid setting"),
+        }
+        assert!(!scm.contains("test"));
+    }
+
+    #[test]
+    fn test_setting_access_check() {
+        let scm = FakeScm::new();
+        let service = scm
+            .connect(ServiceManagerAccess::CREATE_SERVICE)
+            .create_service(service_info("test"), ServiceAccess::QUERY_STATUS)
+            .unwrap();
+        assert_eq!(
+            raw_os_error(service.description().unwrap_err()),
+            Some(ERROR_ACCESS_DENIED)
+        );
+        assert_eq!(
+            raw_os_error(service.set_delayed_auto_start(true).unwrap_err()),
+            Some(ERROR_ACCESS_DENIED)
+        );
+    }
+
+    /// Answers the setting queries with the given function instead of the service.
+    struct SettingOverride {
+        inner: Box<dyn ServiceBackend>,
+        query: fn(&dyn ServiceBackend, ServiceSettingKind) -> Result<ServiceSetting>,
+    }
+
+    impl ServiceBackend for SettingOverride {
+        fn start(&self, service_arguments: &[&OsStr]) -> Result<()> {
+            self.inner.start(service_arguments)
+        }
+
+        fn control(&self, control: ServiceControl) -> Result<ServiceStatus> {
+            self.inner.control(control)
+        }
+
+        fn query_status(&self) -> Result<ServiceStatus> {
+            self.inner.query_status()
+        }
+
+        fn query_config(&self) -> Result<ServiceConfig> {
+            self.inner.query_config()
+        }
+
+        fn change_config(&self, change: &ServiceConfigChange) -> Result<()> {
+            self.inner.change_config(change)
+        }
+
+        fn query_setting(&self, kind: ServiceSettingKind) -> Result<ServiceSetting> {
+            (self.query)(&*self.inner, kind)
+        }
+
+        fn change_setting(&self, setting: &ServiceSetting) -> Result<()> {
+            self.inner.change_setting(setting)
+        }
+
+        fn dependents(&self, state_filter: ServiceStateFilter) -> Result<Vec<ServiceEntry>> {
+            self.inner.dependents(state_filter)
+        }
+
+        fn watch(
+            &self,
+            mask: ServiceNotifyMask,
+            events: mpsc::Sender<Result<ServiceEvent>>,
+        ) -> Result<WatchHandle> {
+            self.inner.watch(mask, events)
+        }
+
+        fn open_service(
+            &self,
+            name: &OsStr,
+            request_access: ServiceAccess,
+        ) -> Result<Box<dyn ServiceBackend>> {
+            self.inner.open_service(name, request_access)
+        }
+
+        fn delete(&self) -> io::Result<()> {
+            self.inner.delete()
+        }
+    }
+
+    #[test]
+    fn test_unexpected_setting_kind() {
+        let scm = FakeScm::new();
+        let manager = FakeServiceManager {
+            scm: scm.clone(),
+            access: ServiceManagerAccess::CREATE_SERVICE,
+        };
+        let inner = manager
+            .create_service(&service_info("test"), ServiceAccess::QUERY_CONFIG)
+            .unwrap();
+        // Answer every setting query with the description of the service.
+        let service = Service::new(Box::new(SettingOverride {
+            inner,
+            query: |inner, _| inner.query_setting(ServiceSettingKind::Description),
+        }));
+
+        assert!(service.description().is_ok());
+        match *service.delayed_auto_start().unwrap_err().kind() {
+            ErrorKind::UnexpectedServiceSetting(expected, actual) => {
+                assert_eq!(expected, ServiceSettingKind::DelayedAutoStart);
+                assert_eq!(actual, ServiceSettingKind::Description);
+            }
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_failure_actions() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(ServiceManagerAccess::CREATE_SERVICE);
+        let service = manager
+            .create_service(
+                service_info("test"),
+                ServiceAccess::QUERY_CONFIG | ServiceAccess::SERVICE_CHANGE_CONFIG,
+            )
+            .unwrap();
+        assert!(service.failure_actions().unwrap().actions.is_empty());
+
+        let failure_actions = ServiceFailureActions {
+            reset_period: ServiceFailureResetPeriod::Never,
+            reboot_msg: None,
+            command: Some(OsString::from(r"C:\Program Files\Test\notify.exe")),
+            actions: vec![
+                ServiceAction {
+                    action_type: ServiceActionType::Restart,
+                    delay: Duration::from_secs(5),
+                },
+                ServiceAction {
+                    action_type: ServiceActionType::RunCommand,
+                    delay: Duration::from_secs(60),
+                },
+            ],
+        };
+        // Restart actions also need the permission to start the service.
+        assert_eq!(
+            raw_os_error(service.set_failure_actions(&failure_actions).unwrap_err()),
+            Some(ERROR_ACCESS_DENIED)
+        );
+
+        let service = manager
+            .open_service(
+                "test",
+                ServiceAccess::QUERY_CONFIG
+                    | ServiceAccess::SERVICE_CHANGE_CONFIG
+                    | ServiceAccess::START,
+            )
+            .unwrap();
+        service.set_failure_actions(&failure_actions).unwrap();
+        assert_eq!(service.failure_actions().unwrap(), failure_actions);
+
+        let details = scm
+            .connect(ServiceManagerAccess::ENUMERATE_SERVICE)
+            .list_services()
+            .unwrap();
+        assert_eq!(
+            details[0].failure_actions.as_ref().ok(),
+            Some(&failure_actions)
+        );
+    }
+
+    #[test]
+    fn test_triggers() {
+        let scm = FakeScm::new();
+        let service = scm
+            .connect(ServiceManagerAccess::CREATE_SERVICE)
+            .create_service(
+                service_info("test"),
+                ServiceAccess::QUERY_CONFIG | ServiceAccess::SERVICE_CHANGE_CONFIG,
+            )
+            .unwrap();
+        assert_eq!(service.triggers().unwrap(), vec![]);
+
+        let triggers = vec![
+            ServiceTrigger::first_ip_address_arrival(),
+            ServiceTrigger::last_ip_address_removal().action(ServiceTriggerAction::Stop),
+        ];
+        service.set_triggers(&triggers).unwrap();
+        assert_eq!(service.triggers().unwrap(), triggers);
+
+        let invalid = vec![ServiceTrigger::named_pipe("a\0b")];
+        match service.set_triggers(&invalid).unwrap_err().kind() {
+            ErrorKind::InvalidServiceTriggerData => (),
+            kind => panic!("unexpected error: {}", kind),
+        }
+        assert_eq!(service.triggers().unwrap(), triggers);
+
+        service.set_triggers(&[]).unwrap();
+        assert_eq!(service.triggers().unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_service_access_check() {
+        let scm = FakeScm::new();
+        scm.connect(ServiceManagerAccess::CREATE_SERVICE)
+            .create_service(service_info("test"), ServiceAccess::empty())
+            .unwrap();
+        scm.set_state("test", ServiceState::Running).unwrap();
+
+        let manager = scm.connect(ServiceManagerAccess::CONNECT);
+        let service = manager
+            .open_service("test", ServiceAccess::QUERY_STATUS)
+            .unwrap();
+        assert_eq!(
+            raw_os_error(service.stop().unwrap_err()),
+            Some(ERROR_ACCESS_DENIED)
+        );
+
+        scm.set_allowed_access("test", ServiceAccess::QUERY_STATUS)
+            .unwrap();
+        let error = manager
+            .open_service("test", ServiceAccess::STOP)
+            .err()
+            .unwrap();
+        assert_eq!(raw_os_error(error), Some(ERROR_ACCESS_DENIED));
+    }
+
+    #[test]
+    fn test_stop() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(ServiceManagerAccess::CREATE_SERVICE);
+        let service = manager
+            .create_service(service_info("test"), ServiceAccess::STOP)
+            .unwrap();
+        assert_eq!(
+            raw_os_error(service.stop().unwrap_err()),
+            Some(ERROR_SERVICE_NOT_ACTIVE)
+        );
+
+        scm.set_state("test", ServiceState::Running).unwrap();
+        let status = service.stop().unwrap();
+        assert_eq!(status.current_state, ServiceState::Stopped);
+        assert_eq!(
+            scm.status("test").unwrap().current_state,
+            ServiceState::Stopped
+        );
+    }
+
+    #[test]
+    fn test_delete_running_service() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(ServiceManagerAccess::CREATE_SERVICE);
+        let service = manager
+            .create_service(service_info("test"), ServiceAccess::DELETE)
+            .unwrap();
+        scm.set_state("test", ServiceState::Running).unwrap();
+        service.delete().unwrap();
+
+        // The service lingers until it is stopped.
+        assert!(scm.contains("test"));
+        let error = manager
+            .open_service("test", ServiceAccess::QUERY_STATUS)
+            .err()
+            .unwrap();
+        assert_eq!(raw_os_error(error), Some(ERROR_SERVICE_MARKED_FOR_DELETE));
+
+        scm.set_state("test", ServiceState::Stopped).unwrap();
+        assert!(!scm.contains("test"));
+    }
+
+    #[test]
+    fn test_delete_waits_for_open_handles() {
+        let scm = FakeScm::new();
+        let manager = scm.connect(ServiceManagerAccess::CREATE_SERVICE);
+        let service = manager
+            .create_service(service_info("test"), ServiceAccess::DELETE)
+            .unwrap();
+        let other = manager
+            .open_service("test", ServiceAccess::QUERY_STATUS)
+            .unwrap();
+        service.delete().unwrap();
+        assert!(scm.contains("test"));
+
+        drop(other);
+        assert!(!scm.contains("test"));
+    }
+}
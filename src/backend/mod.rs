@@ -0,0 +1,152 @@
+//! Pluggable backends for the service control manager.
+//!
+//! [`ServiceManager`] and [`Service`] do not talk to the system directly, instead they delegate
+//! every operation to a [`ServiceManagerBackend`] and a [`ServiceBackend`] respectively. The
+//! default backend forwards the calls to the Win32 service control manager and is only available
+//! on Windows, while [`FakeScm`] keeps an in-memory database of services which is handy for
+//! testing the code that manages services on any platform.
+//!
+//! [`ServiceManager`]: super::service_manager::ServiceManager
+//! [`Service`]: super::service::Service
+
+use std::ffi::OsStr;
+use std::fmt;
+use std::io;
+use std::sync::mpsc;
+
+use service::{
+    ServiceAccess, ServiceConfig, ServiceConfigChange, ServiceControl, ServiceEntry,
+    ServiceEntryExt, ServiceInfo, ServiceSetting, ServiceSettingKind, ServiceStateFilter,
+    ServiceStatus,
+};
+use service_manager::ServiceFilter;
+use watch::{ServiceEvent, ServiceNotifyMask};
+use Result;
+
+mod fake;
+#[cfg(windows)]
+pub(crate) mod win32;
+
+pub use self::fake::FakeScm;
+
+/// Operations on the service control manager database.
+///
+/// An instance of the backend represents an open connection to the database, with the access
+/// permissions that were requested when connecting. Backends can be shared between threads.
+pub trait ServiceManagerBackend: Send + Sync {
+    /// Create a service and return the backend for the newly created service.
+    fn create_service(
+        &self,
+        service_info: &ServiceInfo,
+        service_access: ServiceAccess,
+    ) -> Result<Box<dyn ServiceBackend>>;
+
+    /// Open an existing service.
+    fn open_service(
+        &self,
+        name: &OsStr,
+        request_access: ServiceAccess,
+    ) -> Result<Box<dyn ServiceBackend>>;
+
+    /// Enumerate the next page of services that pass the filter.
+    ///
+    /// The first page is requested with a `resume_handle` of zero, the following pages with the
+    /// resume handle returned along with the previous page.
+    fn enumerate_page(&self, filter: &ServiceFilter, resume_handle: u32) -> Result<ServicePage>;
+
+    /// Send an event to `events` whenever a service is created or deleted, until the returned
+    /// handle is dropped.
+    fn watch_created_deleted(
+        &self,
+        events: mpsc::Sender<Result<ServiceEvent>>,
+    ) -> Result<WatchHandle>;
+}
+
+/// A page of services returned by [`ServiceManagerBackend::enumerate_page`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServicePage {
+    /// The services on this page.
+    pub services: Vec<ServiceEntryExt>,
+
+    /// The handle to request the next page with, `None` if this is the last page.
+    pub resume_handle: Option<u32>,
+}
+
+/// Operations on an open service.
+///
+/// An instance of the backend represents an open service handle, with the access permissions
+/// that were requested when opening or creating the service. Backends can be shared between
+/// threads.
+pub trait ServiceBackend: Send + Sync {
+    /// Start the service, passing the arguments to its `service_main`.
+    fn start(&self, service_arguments: &[&OsStr]) -> Result<()>;
+
+    /// Send a control code to the service and return the status reported back.
+    fn control(&self, control: ServiceControl) -> Result<ServiceStatus>;
+
+    /// Query the current service status.
+    fn query_status(&self) -> Result<ServiceStatus>;
+
+    /// Query the service configuration.
+    fn query_config(&self) -> Result<ServiceConfig>;
+
+    /// Apply the set fields of the change to the service configuration.
+    fn change_config(&self, change: &ServiceConfigChange) -> Result<()>;
+
+    /// Query an optional service setting. The returned setting must be of the requested kind.
+    fn query_setting(&self, kind: ServiceSettingKind) -> Result<ServiceSetting>;
+
+    /// Change an optional service setting.
+    fn change_setting(&self, setting: &ServiceSetting) -> Result<()>;
+
+    /// List the services that depend on this service, directly or indirectly, in the order they
+    /// have to be stopped.
+    fn dependents(&self, state_filter: ServiceStateFilter) -> Result<Vec<ServiceEntry>>;
+
+    /// Send an event to `events` whenever the service enters a state selected by the mask, until
+    /// the returned handle is dropped. The first event reports the current state if it is
+    /// selected by the mask. The watch ends when the service is marked for deletion.
+    fn watch(
+        &self,
+        mask: ServiceNotifyMask,
+        events: mpsc::Sender<Result<ServiceEvent>>,
+    ) -> Result<WatchHandle>;
+
+    /// Open another service in the database this service was opened from.
+    fn open_service(
+        &self,
+        name: &OsStr,
+        request_access: ServiceAccess,
+    ) -> Result<Box<dyn ServiceBackend>>;
+
+    /// Mark the service for deletion.
+    fn delete(&self) -> io::Result<()>;
+}
+
+/// Keeps a watch registered by a backend alive. Dropping the handle stops the watch.
+pub struct WatchHandle {
+    stop: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl WatchHandle {
+    /// Create a handle that calls `stop` when it is dropped.
+    pub fn new<F: FnOnce() + Send + 'static>(stop: F) -> Self {
+        WatchHandle {
+            stop: Some(Box::new(stop)),
+        }
+    }
+}
+
+impl fmt::Debug for WatchHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WatchHandle").finish()
+    }
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            stop();
+        }
+    }
+}
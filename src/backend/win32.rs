@@ -0,0 +1,1141 @@
+use std::ffi::{OsStr, OsString};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+use std::{io, mem, ptr, slice, thread};
+
+use widestring::{NulError, WideCStr, WideCString, WideString};
+use winapi::shared::minwindef::{BOOL, DWORD, FALSE, HLOCAL, LPVOID, PBYTE, TRUE, USHORT};
+use winapi::shared::ntdef::{BOOLEAN, HANDLE, PVOID};
+use winapi::shared::winerror;
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::synchapi::{CreateEventW, SetEvent, WaitForSingleObjectEx};
+use winapi::um::winbase::{LocalFree, INFINITE, WAIT_IO_COMPLETION};
+use winapi::um::{winnt, winsvc};
+
+use backend::{ServiceBackend, ServiceManagerBackend, ServicePage, WatchHandle};
+use guid::Guid;
+use sc_handle::ScHandle;
+use service::{
+    duration_millis, RawFailureActions, RawServiceAction, ServiceAccess, ServiceConfig,
+    ServiceConfigChange, ServiceControl, ServiceDependency, ServiceEntry, ServiceEntryExt,
+    ServiceErrorControl, ServiceFailureActions, ServiceInfo, ServiceSetting, ServiceSettingKind,
+    ServiceSidType, ServiceStartType, ServiceStateFilter, ServiceStatus, ServiceStatusExt,
+    ServiceType,
+};
+use service_manager::{
+    dependencies_multi_string, launch_command, privileges_multi_string, split_launch_command,
+    ServiceFilter, ServiceManagerAccess,
+};
+use service_trigger::{RawServiceTrigger, RawTriggerDataItem, ServiceTrigger};
+use watch::{ServiceEvent, ServiceNotifyMask};
+use {ErrorKind, Result, ResultExt};
+
+/// Backend that connects to the service control manager of the system.
+pub(crate) struct Win32ServiceManager {
+    manager_handle: Arc<ScHandle>,
+    /// The machine and database the connection was made to, used to open more connections.
+    machine_name: Option<WideCString>,
+    database_name: Option<WideCString>,
+}
+
+impl Win32ServiceManager {
+    /// Connect to the service control manager on the given machine, or the local machine when
+    /// `machine` is `None`.
+    pub fn connect<M: AsRef<OsStr>, D: AsRef<OsStr>>(
+        machine: Option<M>,
+        database: Option<D>,
+        request_access: ServiceManagerAccess,
+    ) -> Result<Self> {
+        let machine_name = to_wide(machine).chain_err(|| ErrorKind::InvalidMachineName)?;
+        let database_name = to_wide(database).chain_err(|| ErrorKind::InvalidDatabaseName)?;
+        let handle = unsafe {
+            winsvc::OpenSCManagerW(
+                machine_name.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+                database_name.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+                request_access.bits(),
+            )
+        };
+
+        if handle.is_null() {
+            Err(io::Error::last_os_error().into())
+        } else {
+            Ok(Win32ServiceManager {
+                manager_handle: Arc::new(unsafe { ScHandle::new(handle) }),
+                machine_name,
+                database_name,
+            })
+        }
+    }
+}
+
+impl ServiceManagerBackend for Win32ServiceManager {
+    fn create_service(
+        &self,
+        service_info: &ServiceInfo,
+        service_access: ServiceAccess,
+    ) -> Result<Box<dyn ServiceBackend>> {
+        let service_name = WideCString::from_str(&service_info.name)
+            .chain_err(|| ErrorKind::InvalidServiceName)?;
+        let display_name = WideCString::from_str(&service_info.display_name)
+            .chain_err(|| ErrorKind::InvalidDisplayName)?;
+        let account_name =
+            to_wide(service_info.account_name.as_ref()).chain_err(|| ErrorKind::InvalidAccountName)?;
+        let account_password = to_wide(service_info.account_password.as_ref())
+            .chain_err(|| ErrorKind::InvalidAccountPassword)?;
+        let launch_command = launch_command(
+            service_info.service_type,
+            &service_info.executable_path,
+            &service_info.launch_arguments,
+        )?;
+        let load_order_group = to_wide(service_info.load_order_group.as_ref())
+            .chain_err(|| ErrorKind::InvalidLoadOrderGroup)?;
+        let dependencies = dependencies_multi_string(&service_info.dependencies)?;
+
+        // Applying the settings, or rolling back the creation, needs more access than requested.
+        let create_access = if service_info.settings.is_empty() {
+            service_access
+        } else {
+            service_access | ServiceAccess::SERVICE_CHANGE_CONFIG | ServiceAccess::DELETE
+        };
+        let service_handle = unsafe {
+            winsvc::CreateServiceW(
+                self.manager_handle.raw_handle(),
+                service_name.as_ptr(),
+                display_name.as_ptr(),
+                create_access.bits(),
+                service_info.service_type.to_raw(),
+                service_info.start_type.to_raw(),
+                service_info.error_control.to_raw(),
+                launch_command.as_ptr(),
+                load_order_group.map_or(ptr::null(), |s| s.as_ptr()),
+                ptr::null_mut(), // tag id within the load ordering group
+                dependencies.as_ptr(),
+                account_name.map_or(ptr::null(), |s| s.as_ptr()),
+                account_password.map_or(ptr::null(), |s| s.as_ptr()),
+            )
+        };
+
+        if service_handle.is_null() {
+            return Err(io::Error::last_os_error().into());
+        }
+        let service = Win32Service::new(
+            unsafe { ScHandle::new(service_handle) },
+            self.manager_handle.clone(),
+            service_name,
+        );
+        if create_access == service_access {
+            return Ok(Box::new(service));
+        }
+
+        for setting in &service_info.settings {
+            if let Err(error) = service.change_setting(setting) {
+                // Deletion is best effort, the error of the setting is more relevant.
+                let _ = service.delete();
+                return Err(error);
+            }
+        }
+        drop(service);
+        open_service(&self.manager_handle, &service_info.name, service_access)
+    }
+
+    fn open_service(
+        &self,
+        name: &OsStr,
+        request_access: ServiceAccess,
+    ) -> Result<Box<dyn ServiceBackend>> {
+        open_service(&self.manager_handle, name, request_access)
+    }
+
+    fn enumerate_page(&self, filter: &ServiceFilter, resume_handle: u32) -> Result<ServicePage> {
+        let load_order_group = to_wide(filter.load_order_group.as_ref())
+            .chain_err(|| ErrorKind::InvalidLoadOrderGroup)?;
+
+        let mut buffer_size = ENUM_PAGE_BUFFER_SIZE;
+        loop {
+            let mut buffer = AlignedBuffer::new(buffer_size);
+            let mut bytes_needed = 0;
+            let mut services_returned = 0;
+            let mut next_resume_handle = resume_handle;
+            let success = unsafe {
+                winsvc::EnumServicesStatusExW(
+                    self.manager_handle.raw_handle(),
+                    winsvc::SC_ENUM_PROCESS_INFO,
+                    filter.service_type.bits(),
+                    filter.state.to_raw(),
+                    buffer.as_mut_ptr(),
+                    buffer_size as DWORD,
+                    &mut bytes_needed,
+                    &mut services_returned,
+                    &mut next_resume_handle,
+                    load_order_group
+                        .as_ref()
+                        .map_or(ptr::null(), |group| group.as_ptr()),
+                )
+            };
+
+            let more_data = success == 0;
+            if more_data {
+                let error = io::Error::last_os_error();
+                if error.raw_os_error() != Some(winerror::ERROR_MORE_DATA as i32) {
+                    return Err(error.into());
+                }
+                if services_returned == 0 {
+                    // Not even a single service fits, retry with the size the system asked for.
+                    buffer_size = bytes_needed as usize;
+                    continue;
+                }
+            }
+
+            let raw_services =
+                EnumListServiceResult::from_raw(buffer.as_mut_ptr::<u8>(), services_returned);
+            let services = raw_services
+                .map(|raw_service| unsafe { ServiceEntryExt::from_raw(&raw_service) })
+                .collect::<Result<Vec<_>>>()?;
+            return Ok(ServicePage {
+                services,
+                resume_handle: if more_data {
+                    Some(next_resume_handle)
+                } else {
+                    None
+                },
+            });
+        }
+    }
+
+    fn watch_created_deleted(
+        &self,
+        events: mpsc::Sender<Result<ServiceEvent>>,
+    ) -> Result<WatchHandle> {
+        // The watch needs a connection of its own, because closing the connection is the only
+        // way to cancel the notifications.
+        let manager_handle = unsafe {
+            winsvc::OpenSCManagerW(
+                self.machine_name.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+                self.database_name.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+                winsvc::SC_MANAGER_ENUMERATE_SERVICE,
+            )
+        };
+        if manager_handle.is_null() {
+            return Err(io::Error::last_os_error().into());
+        }
+        let manager_handle = unsafe { ScHandle::new(manager_handle) };
+        spawn_watch(manager_handle, WatchKind::CreatedDeleted, events)
+    }
+}
+
+/// Backend that operates on a service handle obtained from the system.
+pub(crate) struct Win32Service {
+    service_handle: ScHandle,
+    /// The connection the service was opened from, used to open its dependents.
+    manager_handle: Arc<ScHandle>,
+    /// The service name, used to open more handles to the service.
+    name: WideCString,
+}
+
+impl Win32Service {
+    pub fn new(service_handle: ScHandle, manager_handle: Arc<ScHandle>, name: WideCString) -> Self {
+        Win32Service {
+            service_handle,
+            manager_handle,
+            name,
+        }
+    }
+}
+
+impl Win32Service {
+    /// Private helper to change an optional setting with `ChangeServiceConfig2W`.
+    fn change_config2<T>(&self, kind: ServiceSettingKind, raw_info: &mut T) -> Result<()> {
+        let success = unsafe {
+            winsvc::ChangeServiceConfig2W(
+                self.service_handle.raw_handle(),
+                kind.to_raw(),
+                raw_info as *mut T as LPVOID,
+            )
+        };
+        if success == 1 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error().into())
+        }
+    }
+}
+
+/// Read an optional service setting with `QueryServiceConfig2W`.
+fn query_config2(
+    service_handle: winsvc::SC_HANDLE,
+    kind: ServiceSettingKind,
+) -> io::Result<AlignedBuffer> {
+    let mut bytes_needed = 0;
+    let success = unsafe {
+        winsvc::QueryServiceConfig2W(
+            service_handle,
+            kind.to_raw(),
+            ptr::null_mut(),
+            0,
+            &mut bytes_needed,
+        )
+    };
+    if success == 0 {
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() != Some(winerror::ERROR_INSUFFICIENT_BUFFER as i32) {
+            return Err(error);
+        }
+    }
+
+    let mut buffer = AlignedBuffer::new(bytes_needed as usize);
+    let success = unsafe {
+        winsvc::QueryServiceConfig2W(
+            service_handle,
+            kind.to_raw(),
+            buffer.as_mut_ptr(),
+            bytes_needed,
+            &mut bytes_needed,
+        )
+    };
+    if success == 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(buffer)
+    }
+}
+
+/// Read the failure actions of the service.
+fn query_failure_actions(service_handle: winsvc::SC_HANDLE) -> Result<ServiceFailureActions> {
+    let mut buffer = query_config2(service_handle, ServiceSettingKind::FailureActions)?;
+    unsafe { ServiceFailureActions::from_raw_win32(&*buffer.as_mut_ptr()) }
+}
+
+fn open_service(
+    manager_handle: &Arc<ScHandle>,
+    name: &OsStr,
+    request_access: ServiceAccess,
+) -> Result<Box<dyn ServiceBackend>> {
+    let service_name = WideCString::from_str(name).chain_err(|| ErrorKind::InvalidServiceName)?;
+    let service_handle = unsafe {
+        winsvc::OpenServiceW(
+            manager_handle.raw_handle(),
+            service_name.as_ptr(),
+            request_access.bits(),
+        )
+    };
+
+    if service_handle.is_null() {
+        Err(io::Error::last_os_error().into())
+    } else {
+        Ok(Box::new(Win32Service::new(
+            unsafe { ScHandle::new(service_handle) },
+            manager_handle.clone(),
+            service_name,
+        )))
+    }
+}
+
+impl ServiceBackend for Win32Service {
+    fn start(&self, service_arguments: &[&OsStr]) -> Result<()> {
+        let wide_service_arguments = service_arguments
+            .iter()
+            .map(|s| WideCString::from_str(s).chain_err(|| ErrorKind::InvalidStartArgument))
+            .collect::<Result<Vec<WideCString>>>()?;
+        let mut raw_service_arguments: Vec<*const u16> = wide_service_arguments
+            .iter()
+            .map(|s| s.as_ptr())
+            .collect();
+
+        let success = unsafe {
+            winsvc::StartServiceW(
+                self.service_handle.raw_handle(),
+                raw_service_arguments.len() as u32,
+                raw_service_arguments.as_mut_ptr(),
+            )
+        };
+
+        if success == 1 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error().into())
+        }
+    }
+
+    fn control(&self, control: ServiceControl) -> Result<ServiceStatus> {
+        let mut raw_status = unsafe { mem::zeroed::<winsvc::SERVICE_STATUS>() };
+        let success = unsafe {
+            winsvc::ControlService(
+                self.service_handle.raw_handle(),
+                control.to_raw(),
+                &mut raw_status,
+            )
+        };
+
+        if success == 1 {
+            ServiceStatus::from_raw(raw_status)
+        } else {
+            Err(io::Error::last_os_error().into())
+        }
+    }
+
+    fn query_status(&self) -> Result<ServiceStatus> {
+        let mut raw_status = unsafe { mem::zeroed::<winsvc::SERVICE_STATUS>() };
+        let success = unsafe {
+            winsvc::QueryServiceStatus(self.service_handle.raw_handle(), &mut raw_status)
+        };
+        if success == 1 {
+            ServiceStatus::from_raw(raw_status)
+        } else {
+            Err(io::Error::last_os_error().into())
+        }
+    }
+
+    fn query_config(&self) -> Result<ServiceConfig> {
+        let mut bytes_needed = 0;
+        let success = unsafe {
+            winsvc::QueryServiceConfigW(
+                self.service_handle.raw_handle(),
+                ptr::null_mut(),
+                0,
+                &mut bytes_needed,
+            )
+        };
+        if success == 0 {
+            let error = io::Error::last_os_error();
+            if error.raw_os_error() != Some(winerror::ERROR_INSUFFICIENT_BUFFER as i32) {
+                return Err(error.into());
+            }
+        }
+
+        let mut buffer = AlignedBuffer::new(bytes_needed as usize);
+        let raw_config = buffer.as_mut_ptr::<winsvc::QUERY_SERVICE_CONFIGW>();
+        let success = unsafe {
+            winsvc::QueryServiceConfigW(
+                self.service_handle.raw_handle(),
+                raw_config,
+                bytes_needed,
+                &mut bytes_needed,
+            )
+        };
+        if success == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        unsafe { ServiceConfig::from_raw(&*raw_config) }
+    }
+
+    fn change_config(&self, change: &ServiceConfigChange) -> Result<()> {
+        let launch_command = change.launch_command(|| self.query_config())?;
+        let dependencies = change
+            .dependencies
+            .as_ref()
+            .map(|dependencies| dependencies_multi_string(dependencies))
+            .transpose()?;
+        let account_name =
+            to_wide(change.account_name.as_ref()).chain_err(|| ErrorKind::InvalidAccountName)?;
+        let account_password = to_wide(change.account_password.as_ref())
+            .chain_err(|| ErrorKind::InvalidAccountPassword)?;
+        let display_name =
+            to_wide(change.display_name.as_ref()).chain_err(|| ErrorKind::InvalidDisplayName)?;
+
+        let success = unsafe {
+            winsvc::ChangeServiceConfigW(
+                self.service_handle.raw_handle(),
+                change
+                    .service_type
+                    .map_or(winsvc::SERVICE_NO_CHANGE, |service_type| service_type.to_raw()),
+                change
+                    .start_type
+                    .map_or(winsvc::SERVICE_NO_CHANGE, |start_type| start_type.to_raw()),
+                change
+                    .error_control
+                    .map_or(winsvc::SERVICE_NO_CHANGE, |error_control| error_control.to_raw()),
+                launch_command.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+                ptr::null(),     // load ordering group
+                ptr::null_mut(), // tag id within the load ordering group
+                dependencies.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+                account_name.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+                account_password.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+                display_name.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+            )
+        };
+
+        if success == 1 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error().into())
+        }
+    }
+
+    fn query_setting(&self, kind: ServiceSettingKind) -> Result<ServiceSetting> {
+        if kind == ServiceSettingKind::FailureActions {
+            let failure_actions = query_failure_actions(self.service_handle.raw_handle())?;
+            return Ok(ServiceSetting::FailureActions(failure_actions));
+        }
+        let mut buffer = match query_config2(self.service_handle.raw_handle(), kind) {
+            Ok(buffer) => buffer,
+            Err(ref error)
+                if kind == ServiceSettingKind::PreferredNode
+                    && error.raw_os_error() == Some(winerror::ERROR_NOT_FOUND as i32) =>
+            {
+                // The service has no preferred node.
+                return Ok(ServiceSetting::PreferredNode(None));
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        let setting = unsafe {
+            match kind {
+                ServiceSettingKind::Description => {
+                    let raw_info = &*buffer.as_mut_ptr::<winsvc::SERVICE_DESCRIPTIONW>();
+                    ServiceSetting::Description(
+                        optional_string(raw_info.lpDescription).unwrap_or_default(),
+                    )
+                }
+                ServiceSettingKind::FailureActions => unreachable!(),
+                ServiceSettingKind::DelayedAutoStart => {
+                    let raw_info = &*buffer.as_mut_ptr::<SERVICE_DELAYED_AUTO_START_INFO>();
+                    ServiceSetting::DelayedAutoStart(raw_info.fDelayedAutostart != 0)
+                }
+                ServiceSettingKind::PreshutdownTimeout => {
+                    let raw_info = &*buffer.as_mut_ptr::<SERVICE_PRESHUTDOWN_INFO>();
+                    ServiceSetting::PreshutdownTimeout(Duration::from_millis(u64::from(
+                        raw_info.dwPreshutdownTimeout,
+                    )))
+                }
+                ServiceSettingKind::RequiredPrivileges => {
+                    let raw_info = &*buffer.as_mut_ptr::<SERVICE_REQUIRED_PRIVILEGES_INFOW>();
+                    ServiceSetting::RequiredPrivileges(multi_string(
+                        raw_info.pmszRequiredPrivileges,
+                    ))
+                }
+                ServiceSettingKind::SidType => {
+                    let raw_info = &*buffer.as_mut_ptr::<SERVICE_SID_INFO>();
+                    ServiceSetting::SidType(ServiceSidType::from_raw(raw_info.dwServiceSidType)?)
+                }
+                ServiceSettingKind::FailureActionsOnNonCrashFailures => {
+                    let raw_info = &*buffer.as_mut_ptr::<winsvc::SERVICE_FAILURE_ACTIONS_FLAG>();
+                    ServiceSetting::FailureActionsOnNonCrashFailures(
+                        raw_info.fFailureActionsOnNonCrashFailures != 0,
+                    )
+                }
+                ServiceSettingKind::Triggers => {
+                    let raw_info = &*buffer.as_mut_ptr::<SERVICE_TRIGGER_INFO>();
+                    ServiceSetting::Triggers(triggers_from_raw(raw_info)?)
+                }
+                ServiceSettingKind::PreferredNode => {
+                    let raw_info = &*buffer.as_mut_ptr::<SERVICE_PREFERRED_NODE_INFO>();
+                    ServiceSetting::PreferredNode(if raw_info.fDelete != 0 {
+                        None
+                    } else {
+                        Some(raw_info.usPreferredNode)
+                    })
+                }
+            }
+        };
+        Ok(setting)
+    }
+
+    fn change_setting(&self, setting: &ServiceSetting) -> Result<()> {
+        let kind = setting.kind();
+        match *setting {
+            ServiceSetting::Description(ref description) => {
+                let description = WideCString::from_str(description)
+                    .chain_err(|| ErrorKind::InvalidDescription)?;
+                let mut raw_info = winsvc::SERVICE_DESCRIPTIONW {
+                    lpDescription: description.as_ptr() as *mut u16,
+                };
+                self.change_config2(kind, &mut raw_info)
+            }
+            ServiceSetting::FailureActions(ref failure_actions) => {
+                let mut raw = failure_actions.to_raw()?;
+                let mut raw_info = winsvc::SERVICE_FAILURE_ACTIONSW {
+                    dwResetPeriod: raw.reset_period,
+                    lpRebootMsg: raw.reboot_msg.as_ptr() as *mut u16,
+                    lpCommand: raw.command.as_ptr() as *mut u16,
+                    cActions: raw.actions.len() as DWORD,
+                    // Never null, so that an empty list removes the actions.
+                    lpsaActions: raw.actions.as_mut_ptr() as *mut winsvc::SC_ACTION,
+                };
+                self.change_config2(kind, &mut raw_info)
+            }
+            ServiceSetting::DelayedAutoStart(delayed) => {
+                let mut raw_info = SERVICE_DELAYED_AUTO_START_INFO {
+                    fDelayedAutostart: delayed as BOOL,
+                };
+                self.change_config2(kind, &mut raw_info)
+            }
+            ServiceSetting::PreshutdownTimeout(timeout) => {
+                let mut raw_info = SERVICE_PRESHUTDOWN_INFO {
+                    dwPreshutdownTimeout: duration_millis(timeout),
+                };
+                self.change_config2(kind, &mut raw_info)
+            }
+            ServiceSetting::RequiredPrivileges(ref privileges) => {
+                let mut privileges = privileges_multi_string(privileges)?;
+                let mut raw_info = SERVICE_REQUIRED_PRIVILEGES_INFOW {
+                    pmszRequiredPrivileges: privileges.as_mut_ptr(),
+                };
+                self.change_config2(kind, &mut raw_info)
+            }
+            ServiceSetting::SidType(sid_type) => {
+                let mut raw_info = SERVICE_SID_INFO {
+                    dwServiceSidType: sid_type.to_raw(),
+                };
+                self.change_config2(kind, &mut raw_info)
+            }
+            ServiceSetting::FailureActionsOnNonCrashFailures(enabled) => {
+                let mut raw_info = winsvc::SERVICE_FAILURE_ACTIONS_FLAG {
+                    fFailureActionsOnNonCrashFailures: enabled as BOOL,
+                };
+                self.change_config2(kind, &mut raw_info)
+            }
+            ServiceSetting::Triggers(ref triggers) => {
+                let mut raw_triggers = triggers
+                    .iter()
+                    .map(ServiceTrigger::to_raw)
+                    .collect::<Result<Vec<_>>>()?;
+                // The system reads the data through these pointers, which stay valid while
+                // `raw_triggers` is alive.
+                let mut raw_data_items: Vec<Vec<_>> = raw_triggers
+                    .iter_mut()
+                    .map(|raw_trigger| {
+                        raw_trigger
+                            .data
+                            .iter_mut()
+                            .map(|item| SERVICE_TRIGGER_SPECIFIC_DATA_ITEM {
+                                dwDataType: item.data_type,
+                                cbData: item.data.len() as DWORD,
+                                pData: item.data.as_mut_ptr(),
+                            })
+                            .collect()
+                    })
+                    .collect();
+                let mut raw_trigger_array: Vec<SERVICE_TRIGGER> = raw_triggers
+                    .iter_mut()
+                    .zip(raw_data_items.iter_mut())
+                    .map(|(raw_trigger, data_items)| SERVICE_TRIGGER {
+                        dwTriggerType: raw_trigger.trigger_type,
+                        dwAction: raw_trigger.action,
+                        pTriggerSubtype: &mut raw_trigger.subtype,
+                        cDataItems: data_items.len() as DWORD,
+                        pDataItems: data_items.as_mut_ptr(),
+                    })
+                    .collect();
+                let mut raw_info = SERVICE_TRIGGER_INFO {
+                    cTriggers: raw_trigger_array.len() as DWORD,
+                    pTriggers: raw_trigger_array.as_mut_ptr(),
+                    pReserved: ptr::null_mut(),
+                };
+                self.change_config2(kind, &mut raw_info)
+            }
+            ServiceSetting::PreferredNode(node) => {
+                let mut raw_info = SERVICE_PREFERRED_NODE_INFO {
+                    usPreferredNode: node.unwrap_or(0),
+                    fDelete: node.is_none() as BOOLEAN,
+                };
+                self.change_config2(kind, &mut raw_info)
+            }
+        }
+    }
+
+    fn watch(
+        &self,
+        mask: ServiceNotifyMask,
+        events: mpsc::Sender<Result<ServiceEvent>>,
+    ) -> Result<WatchHandle> {
+        // Like the connection in `watch_created_deleted`, the watch needs a handle of its own.
+        let service_handle = unsafe {
+            winsvc::OpenServiceW(
+                self.manager_handle.raw_handle(),
+                self.name.as_ptr(),
+                winsvc::SERVICE_QUERY_STATUS,
+            )
+        };
+        if service_handle.is_null() {
+            return Err(io::Error::last_os_error().into());
+        }
+        let service_handle = unsafe { ScHandle::new(service_handle) };
+        spawn_watch(service_handle, WatchKind::Service(mask), events)
+    }
+
+    fn dependents(&self, state_filter: ServiceStateFilter) -> Result<Vec<ServiceEntry>> {
+        let mut bytes_needed = 0;
+        let mut services_returned = 0;
+        let success = unsafe {
+            winsvc::EnumDependentServicesW(
+                self.service_handle.raw_handle(),
+                state_filter.to_raw(),
+                ptr::null_mut(),
+                0,
+                &mut bytes_needed,
+                &mut services_returned,
+            )
+        };
+        if success == 1 {
+            // There are no dependents to fit in the empty buffer.
+            return Ok(vec![]);
+        }
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() != Some(winerror::ERROR_MORE_DATA as i32) {
+            return Err(error.into());
+        }
+
+        let mut buffer = AlignedBuffer::new(bytes_needed as usize);
+        let raw_services = buffer.as_mut_ptr::<winsvc::ENUM_SERVICE_STATUSW>();
+        let success = unsafe {
+            winsvc::EnumDependentServicesW(
+                self.service_handle.raw_handle(),
+                state_filter.to_raw(),
+                raw_services,
+                bytes_needed,
+                &mut bytes_needed,
+                &mut services_returned,
+            )
+        };
+        if success == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        let raw_services =
+            unsafe { slice::from_raw_parts(raw_services, services_returned as usize) };
+        raw_services
+            .iter()
+            .map(|raw_service| unsafe { ServiceEntry::from_raw(raw_service) })
+            .collect()
+    }
+
+    fn open_service(
+        &self,
+        name: &OsStr,
+        request_access: ServiceAccess,
+    ) -> Result<Box<dyn ServiceBackend>> {
+        open_service(&self.manager_handle, name, request_access)
+    }
+
+    fn delete(&self) -> io::Result<()> {
+        let success = unsafe { winsvc::DeleteService(self.service_handle.raw_handle()) };
+        if success == 1 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+impl ServiceConfig {
+    /// Read the configuration returned by `QueryServiceConfigW`.
+    ///
+    /// The string pointers of `raw_config` must point to valid nul terminated strings.
+    unsafe fn from_raw(raw_config: &winsvc::QUERY_SERVICE_CONFIGW) -> Result<Self> {
+        let service_type = ServiceType::from_raw(raw_config.dwServiceType)?;
+        let binary_path = optional_string(raw_config.lpBinaryPathName).unwrap_or_default();
+        let (executable_path, launch_arguments) = split_launch_command(service_type, &binary_path);
+
+        Ok(ServiceConfig {
+            service_type,
+            start_type: ServiceStartType::from_raw(raw_config.dwStartType)?,
+            error_control: ServiceErrorControl::from_raw(raw_config.dwErrorControl)?,
+            binary_path,
+            executable_path,
+            launch_arguments,
+            load_order_group: optional_string(raw_config.lpLoadOrderGroup),
+            tag_id: raw_config.dwTagId,
+            dependencies: multi_string(raw_config.lpDependencies)
+                .into_iter()
+                .map(ServiceDependency::from_system_identifier)
+                .collect(),
+            account_name: optional_string(raw_config.lpServiceStartName),
+            display_name: optional_string(raw_config.lpDisplayName).unwrap_or_default(),
+        })
+    }
+}
+
+impl ServiceFailureActions {
+    /// Read the failure actions returned by `QueryServiceConfig2W`.
+    ///
+    /// The pointers of `raw_info` must be null or point to valid data.
+    unsafe fn from_raw_win32(raw_info: &winsvc::SERVICE_FAILURE_ACTIONSW) -> Result<Self> {
+        let wide_string = |ptr: *const u16| {
+            if ptr.is_null() {
+                WideCString::default()
+            } else {
+                WideCString::from_ptr_str(ptr)
+            }
+        };
+        let actions = raw_slice(raw_info.lpsaActions, raw_info.cActions);
+        ServiceFailureActions::from_raw(&RawFailureActions {
+            reset_period: raw_info.dwResetPeriod,
+            reboot_msg: wide_string(raw_info.lpRebootMsg),
+            command: wide_string(raw_info.lpCommand),
+            actions: actions
+                .iter()
+                .map(|action| RawServiceAction {
+                    action_type: action.Type,
+                    delay: action.Delay,
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Read the triggers returned by `QueryServiceConfig2W`.
+///
+/// The pointers of `raw_info` must be null or point to valid data.
+unsafe fn triggers_from_raw(raw_info: &SERVICE_TRIGGER_INFO) -> Result<Vec<ServiceTrigger>> {
+    let raw_triggers = raw_slice(raw_info.pTriggers, raw_info.cTriggers);
+    raw_triggers
+        .iter()
+        .map(|raw_trigger| {
+            let raw_data_items = raw_slice(raw_trigger.pDataItems, raw_trigger.cDataItems);
+            ServiceTrigger::from_raw(&RawServiceTrigger {
+                trigger_type: raw_trigger.dwTriggerType,
+                action: raw_trigger.dwAction,
+                subtype: if raw_trigger.pTriggerSubtype.is_null() {
+                    Guid::default()
+                } else {
+                    *raw_trigger.pTriggerSubtype
+                },
+                data: raw_data_items
+                    .iter()
+                    .map(|item| RawTriggerDataItem {
+                        data_type: item.dwDataType,
+                        data: raw_slice(item.pData, item.cbData).to_vec(),
+                    })
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// What a watch registers for.
+#[derive(Clone, Copy)]
+enum WatchKind {
+    Service(ServiceNotifyMask),
+    CreatedDeleted,
+}
+
+/// Event object that tells a watch thread to stop.
+struct StopEvent(HANDLE);
+
+// Event handles are not tied to the thread that created them.
+unsafe impl Send for StopEvent {}
+unsafe impl Sync for StopEvent {}
+
+impl Drop for StopEvent {
+    fn drop(&mut self) {
+        unsafe { CloseHandle(self.0) };
+    }
+}
+
+/// Watch the handle on a thread of its own, which closes the handle when the watch stops.
+///
+/// The system delivers the notifications as asynchronous procedure calls to the thread that
+/// registered for them, so the thread spends its life in an alertable wait for either the next
+/// notification or the request to stop.
+fn spawn_watch(
+    handle: ScHandle,
+    kind: WatchKind,
+    events: mpsc::Sender<Result<ServiceEvent>>,
+) -> Result<WatchHandle> {
+    let stop_event = unsafe { CreateEventW(ptr::null_mut(), TRUE, FALSE, ptr::null()) };
+    if stop_event.is_null() {
+        return Err(io::Error::last_os_error().into());
+    }
+    let stop_event = Arc::new(StopEvent(stop_event));
+
+    let thread_stop_event = stop_event.clone();
+    let thread = thread::Builder::new()
+        .name(String::from("service watch"))
+        .spawn(move || watch_thread(handle, kind, &thread_stop_event, &events))?;
+    Ok(WatchHandle::new(move || {
+        unsafe { SetEvent(stop_event.0) };
+        let _ = thread.join();
+    }))
+}
+
+fn watch_thread(
+    handle: ScHandle,
+    kind: WatchKind,
+    stop_event: &StopEvent,
+    events: &mpsc::Sender<Result<ServiceEvent>>,
+) {
+    // The system writes the notification into this buffer, it must stay in place until the
+    // handle is closed.
+    let mut notify: Box<winsvc::SERVICE_NOTIFYW> = Box::new(unsafe { mem::zeroed() });
+    let mut last_state = None;
+    loop {
+        let register_mask = match kind {
+            WatchKind::Service(_) => {
+                // Register for every state but the current one, so that each transition is
+                // reported exactly once. The mask of the caller is applied when reporting.
+                let current_state =
+                    last_state.map_or(ServiceNotifyMask::empty(), ServiceNotifyMask::from_state);
+                let register_mask = (ServiceNotifyMask::ANY_STATE - current_state)
+                    | ServiceNotifyMask::DELETE_PENDING;
+                register_mask.bits()
+            }
+            WatchKind::CreatedDeleted => {
+                winsvc::SERVICE_NOTIFY_CREATED | winsvc::SERVICE_NOTIFY_DELETED
+            }
+        };
+
+        *notify = unsafe { mem::zeroed() };
+        notify.dwVersion = winsvc::SERVICE_NOTIFY_STATUS_CHANGE;
+        notify.pfnNotifyCallback = Some(notify_callback);
+        let mut error = unsafe {
+            winsvc::NotifyServiceStatusChangeW(handle.raw_handle(), register_mask, &mut *notify)
+        };
+        if error == winerror::ERROR_SUCCESS {
+            if !wait_for_notification(stop_event) {
+                break;
+            }
+            error = notify.dwNotificationStatus;
+        }
+
+        let delete_pending = error == winerror::ERROR_SERVICE_MARKED_FOR_DELETE
+            || (error == winerror::ERROR_SUCCESS
+                && notify.dwNotificationTriggered & winsvc::SERVICE_NOTIFY_DELETE_PENDING != 0);
+        if delete_pending {
+            match kind {
+                WatchKind::Service(mask) if mask.contains(ServiceNotifyMask::DELETE_PENDING) => {
+                    let _ = events.send(Ok(ServiceEvent::DeletePending));
+                }
+                _ => (),
+            }
+            break;
+        }
+        if error != winerror::ERROR_SUCCESS {
+            let _ = events.send(Err(io::Error::from_raw_os_error(error as i32).into()));
+            break;
+        }
+
+        let sent = match kind {
+            WatchKind::Service(mask) => match ServiceStatusExt::from_raw(notify.ServiceStatus) {
+                Ok(status) => {
+                    let state = status.status.current_state;
+                    last_state = Some(state);
+                    !mask.contains(ServiceNotifyMask::from_state(state))
+                        || events.send(Ok(ServiceEvent::StateChanged(status))).is_ok()
+                }
+                Err(error) => {
+                    let _ = events.send(Err(error));
+                    false
+                }
+            },
+            WatchKind::CreatedDeleted => {
+                let names = unsafe { multi_string(notify.pszServiceNames) };
+                if !notify.pszServiceNames.is_null() {
+                    unsafe { LocalFree(notify.pszServiceNames as HLOCAL) };
+                }
+                names
+                    .into_iter()
+                    .all(|name| events.send(Ok(created_deleted_event(name))).is_ok())
+            }
+        };
+        if !sent {
+            break;
+        }
+    }
+
+    // Closing the handle cancels the pending notification, only then the buffer can be freed.
+    drop(handle);
+    drop(notify);
+}
+
+/// Called by the system in the watch thread when a notification arrives. The notification is
+/// read from the buffer after the alertable wait returns.
+unsafe extern "system" fn notify_callback(_notify: PVOID) {}
+
+/// Wait until the notification callback ran. Returns `false` if the watch was stopped instead.
+fn wait_for_notification(stop_event: &StopEvent) -> bool {
+    unsafe { WaitForSingleObjectEx(stop_event.0, INFINITE, TRUE) == WAIT_IO_COMPLETION }
+}
+
+/// The system tells created services apart from deleted ones by prefixing their names with a
+/// slash.
+fn created_deleted_event(name: OsString) -> ServiceEvent {
+    let wide_name = WideString::from_str(&name);
+    match wide_name.as_slice().split_first() {
+        Some((&first, created_name)) if first == u16::from(b'/') => {
+            ServiceEvent::Created(WideString::from_vec(created_name).to_os_string())
+        }
+        _ => ServiceEvent::Deleted(name),
+    }
+}
+
+/// Private helper to view a counted array that may be null when empty.
+unsafe fn raw_slice<'a, T>(ptr: *const T, len: DWORD) -> &'a [T] {
+    if ptr.is_null() {
+        &[]
+    } else {
+        slice::from_raw_parts(ptr, len as usize)
+    }
+}
+
+impl ServiceEntryExt {
+    unsafe fn from_raw(raw_service: &winsvc::ENUM_SERVICE_STATUS_PROCESSW) -> Result<Self> {
+        Ok(ServiceEntryExt {
+            name: optional_string(raw_service.lpServiceName).unwrap_or_default(),
+            display_name: optional_string(raw_service.lpDisplayName).unwrap_or_default(),
+            status: ServiceStatusExt::from_raw(raw_service.ServiceStatusProcess)?,
+        })
+    }
+}
+
+impl ServiceEntry {
+    unsafe fn from_raw(raw_service: &winsvc::ENUM_SERVICE_STATUSW) -> Result<Self> {
+        Ok(ServiceEntry {
+            name: optional_string(raw_service.lpServiceName).unwrap_or_default(),
+            display_name: optional_string(raw_service.lpDisplayName).unwrap_or_default(),
+            status: ServiceStatus::from_raw(raw_service.ServiceStatus)?,
+        })
+    }
+}
+
+fn to_wide<T: AsRef<OsStr>>(s: Option<T>) -> ::std::result::Result<Option<WideCString>, NulError> {
+    if let Some(s) = s {
+        Ok(Some(WideCString::from_str(s)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Read a nul terminated wide string, treating null pointers and empty strings as `None`.
+unsafe fn optional_string(ptr: *const u16) -> Option<OsString> {
+    if ptr.is_null() {
+        None
+    } else {
+        let string = WideCStr::from_ptr_str(ptr).to_os_string();
+        if string.is_empty() {
+            None
+        } else {
+            Some(string)
+        }
+    }
+}
+
+/// Read a sequence of nul terminated wide strings, terminated by an empty string.
+unsafe fn multi_string(mut ptr: *const u16) -> Vec<OsString> {
+    let mut strings = Vec::new();
+    if ptr.is_null() {
+        return strings;
+    }
+    loop {
+        let string = WideCStr::from_ptr_str(ptr);
+        if string.is_empty() {
+            break;
+        }
+        ptr = ptr.add(string.len() + 1);
+        strings.push(string.to_os_string());
+    }
+    strings
+}
+
+// Structures passed to `ChangeServiceConfig2W` and `QueryServiceConfig2W`.
+// Not defined by `winapi`.
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct SERVICE_DELAYED_AUTO_START_INFO {
+    fDelayedAutostart: BOOL,
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct SERVICE_SID_INFO {
+    dwServiceSidType: DWORD,
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct SERVICE_REQUIRED_PRIVILEGES_INFOW {
+    pmszRequiredPrivileges: winnt::LPWSTR,
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct SERVICE_PRESHUTDOWN_INFO {
+    dwPreshutdownTimeout: DWORD,
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct SERVICE_PREFERRED_NODE_INFO {
+    usPreferredNode: USHORT,
+    fDelete: BOOLEAN,
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct SERVICE_TRIGGER_SPECIFIC_DATA_ITEM {
+    dwDataType: DWORD,
+    cbData: DWORD,
+    pData: PBYTE,
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct SERVICE_TRIGGER {
+    dwTriggerType: DWORD,
+    dwAction: DWORD,
+    pTriggerSubtype: *mut Guid,
+    cDataItems: DWORD,
+    pDataItems: *mut SERVICE_TRIGGER_SPECIFIC_DATA_ITEM,
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct SERVICE_TRIGGER_INFO {
+    cTriggers: DWORD,
+    pTriggers: *mut SERVICE_TRIGGER,
+    pReserved: PBYTE,
+}
+
+/// The initial buffer size for a page of enumerated services.
+const ENUM_PAGE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// A buffer suitably aligned to hold Win32 structures followed by their string data.
+struct AlignedBuffer(Vec<u64>);
+
+impl AlignedBuffer {
+    fn new(size_in_bytes: usize) -> Self {
+        AlignedBuffer(vec![0u64; size_in_bytes / mem::size_of::<u64>() + 1])
+    }
+
+    fn as_mut_ptr<T>(&mut self) -> *mut T {
+        self.0.as_mut_ptr() as *mut T
+    }
+}
+
+/// Iterator over the entries written by `EnumServicesStatusExW`.
+struct EnumListServiceResult {
+    entry_point: *const u8,
+    index: usize,
+    size: usize,
+}
+
+impl EnumListServiceResult {
+    fn from_raw(entry_point: *const u8, size: u32) -> EnumListServiceResult {
+        EnumListServiceResult {
+            entry_point,
+            index: 0,
+            size: size as usize,
+        }
+    }
+}
+
+impl Iterator for EnumListServiceResult {
+    type Item = winsvc::ENUM_SERVICE_STATUS_PROCESSW;
+
+    fn next(&mut self) -> Option<winsvc::ENUM_SERVICE_STATUS_PROCESSW> {
+        if self.index < self.size {
+            let result = unsafe {
+                self.entry_point
+                    .add(self.index * mem::size_of::<winsvc::ENUM_SERVICE_STATUS_PROCESSW>())
+                    as *const winsvc::ENUM_SERVICE_STATUS_PROCESSW
+            };
+
+            self.index += 1;
+
+            Some(unsafe { ptr::read_unaligned(result) })
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,461 @@
+//! Device events delivered to services.
+//!
+//! A service subscribes to the events of a device interface class or of an open device handle
+//! through [`ServiceStatusHandle::subscribe_device_interface`] and
+//! [`ServiceStatusHandle::subscribe_handle`]. The system then sends
+//! [`ServiceControl::DeviceEvent`] controls to the service, carrying a [`DeviceEvent`] decoded
+//! from the `DEV_BROADCAST_DEVICEINTERFACE` or `DEV_BROADCAST_HANDLE` structure that came with
+//! the event.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! extern crate windows_service;
+//!
+//! use windows_service::device_event::{DeviceBroadcast, DeviceEvent};
+//! use windows_service::service::ServiceControl;
+//! # #[cfg(windows)]
+//! use windows_service::service_control_handler::ServiceControlHandlerResult;
+//!
+//! # #[cfg(windows)]
+//! fn handle_control(control: ServiceControl) -> ServiceControlHandlerResult {
+//!     match control {
+//!         ServiceControl::DeviceEvent(DeviceEvent::Arrival(DeviceBroadcast::DeviceInterface(
+//!             device,
+//!         ))) => {
+//!             println!("Device arrived: {}", device.name.to_string_lossy());
+//!             ServiceControlHandlerResult::NoError
+//!         }
+//!         ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+//!         _ => ServiceControlHandlerResult::NotImplemented,
+//!     }
+//! }
+//! # fn main() {}
+//! ```
+//!
+//! [`ServiceStatusHandle::subscribe_device_interface`]:
+//!     super::service_control_handler::ServiceStatusHandle::subscribe_device_interface
+//! [`ServiceStatusHandle::subscribe_handle`]:
+//!     super::service_control_handler::ServiceStatusHandle::subscribe_handle
+//! [`ServiceControl::DeviceEvent`]: super::service::ServiceControl::DeviceEvent
+
+use std::ffi::OsString;
+use std::mem;
+use std::os::raw::c_void;
+use std::slice;
+
+use widestring::WideString;
+
+use guid::Guid;
+use schema;
+use sys::dbt;
+use {ErrorKind, Result};
+
+/// Size of the `DEV_BROADCAST_HDR` structure that starts every broadcast.
+const HEADER_SIZE: usize = 12;
+
+/// Offset of `dbcc_name` in `DEV_BROADCAST_DEVICEINTERFACE_W`.
+const DEVICE_INTERFACE_NAME_OFFSET: usize = HEADER_SIZE + 16;
+
+/// Offset of `dbch_handle` in `DEV_BROADCAST_HANDLE`, which is aligned for a pointer.
+fn handle_offset() -> usize {
+    let align = mem::align_of::<usize>();
+    (HEADER_SIZE + align - 1) / align * align
+}
+
+/// Offset of `dbch_eventguid` in `DEV_BROADCAST_HANDLE`, following the device handle and the
+/// notification handle.
+fn event_guid_offset() -> usize {
+    handle_offset() + 2 * mem::size_of::<usize>()
+}
+
+/// Offset of `dbch_data` in `DEV_BROADCAST_HANDLE`, following the event GUID and the name offset.
+fn handle_data_offset() -> usize {
+    event_guid_offset() + 16 + 4
+}
+
+/// An event of a device the service subscribed to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DeviceEvent {
+    /// A device was inserted and is now available.
+    Arrival(DeviceBroadcast),
+
+    /// The system asks for permission to remove a device. Any application can deny the request
+    /// by returning an error from the control handler.
+    QueryRemove(DeviceBroadcast),
+
+    /// The request to remove a device was canceled.
+    QueryRemoveFailed(DeviceBroadcast),
+
+    /// A device is about to be removed. It cannot be denied.
+    RemovePending(DeviceBroadcast),
+
+    /// A device was removed.
+    RemoveComplete(DeviceBroadcast),
+
+    /// The driver of a device the service holds a handle to raised a custom event.
+    CustomEvent(CustomDeviceEvent),
+}
+
+impl DeviceEvent {
+    /// Decode a device event from the event type and the bytes of the broadcast structure that
+    /// the system passes to the control handler.
+    pub fn from_raw(event_type: u32, broadcast: &[u8]) -> Result<Self> {
+        let event = match event_type {
+            dbt::DBT_DEVICEARRIVAL => DeviceEvent::Arrival(DeviceBroadcast::from_raw(broadcast)?),
+            dbt::DBT_DEVICEQUERYREMOVE => {
+                DeviceEvent::QueryRemove(DeviceBroadcast::from_raw(broadcast)?)
+            }
+            dbt::DBT_DEVICEQUERYREMOVEFAILED => {
+                DeviceEvent::QueryRemoveFailed(DeviceBroadcast::from_raw(broadcast)?)
+            }
+            dbt::DBT_DEVICEREMOVEPENDING => {
+                DeviceEvent::RemovePending(DeviceBroadcast::from_raw(broadcast)?)
+            }
+            dbt::DBT_DEVICEREMOVECOMPLETE => {
+                DeviceEvent::RemoveComplete(DeviceBroadcast::from_raw(broadcast)?)
+            }
+            dbt::DBT_CUSTOMEVENT => DeviceEvent::CustomEvent(CustomDeviceEvent::from_raw(broadcast)?),
+            other => Err(ErrorKind::InvalidDeviceEventType(other))?,
+        };
+        Ok(event)
+    }
+
+    /// Decode a device event from the event type and data that the system passes to the control
+    /// handler.
+    ///
+    /// # Safety
+    ///
+    /// `event_data` must point to the broadcast structure the system passed with the event.
+    pub unsafe fn from_raw_event(event_type: u32, event_data: *const c_void) -> Result<Self> {
+        let size = *(event_data as *const u32) as usize;
+        let broadcast = slice::from_raw_parts(event_data as *const u8, size);
+        DeviceEvent::from_raw(event_type, broadcast)
+    }
+}
+
+/// The device a [`DeviceEvent`] is about.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DeviceBroadcast {
+    /// A device of a device interface class the service subscribed to.
+    DeviceInterface(DeviceInterfaceBroadcast),
+
+    /// A device the service subscribed to through an open handle.
+    Handle(DeviceHandleBroadcast),
+}
+
+impl DeviceBroadcast {
+    /// Decode the bytes of a `DEV_BROADCAST_DEVICEINTERFACE_W` or `DEV_BROADCAST_HANDLE`
+    /// structure.
+    pub fn from_raw(broadcast: &[u8]) -> Result<Self> {
+        let broadcast = header(broadcast)?;
+        let broadcast = match read_u32(broadcast, 4)? {
+            dbt::DBT_DEVTYP_DEVICEINTERFACE => {
+                DeviceBroadcast::DeviceInterface(DeviceInterfaceBroadcast {
+                    class_guid: read_guid(broadcast, HEADER_SIZE)?,
+                    name: read_wide_string(broadcast, DEVICE_INTERFACE_NAME_OFFSET)?,
+                })
+            }
+            dbt::DBT_DEVTYP_HANDLE => DeviceBroadcast::Handle(DeviceHandleBroadcast {
+                handle: read_usize(broadcast, handle_offset())?,
+            }),
+            other => Err(ErrorKind::InvalidDeviceBroadcastType(other))?,
+        };
+        Ok(broadcast)
+    }
+}
+
+/// A device of a device interface class, from the `DEV_BROADCAST_DEVICEINTERFACE` structure.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceInterfaceBroadcast {
+    /// The GUID of the device interface class.
+    pub class_guid: Guid,
+
+    /// The symbolic link name of the device, which can be used to open it.
+    #[serde(with = "schema::os_string")]
+    pub name: OsString,
+}
+
+/// A device the service holds a handle to, from the `DEV_BROADCAST_HANDLE` structure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceHandleBroadcast {
+    /// The handle passed to [`ServiceStatusHandle::subscribe_handle`], as an integer.
+    ///
+    /// [`ServiceStatusHandle::subscribe_handle`]:
+    ///     super::service_control_handler::ServiceStatusHandle::subscribe_handle
+    pub handle: usize,
+}
+
+/// A custom event raised by the driver of a device, from the `DEV_BROADCAST_HANDLE` structure.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomDeviceEvent {
+    /// The handle passed to [`ServiceStatusHandle::subscribe_handle`], as an integer.
+    ///
+    /// [`ServiceStatusHandle::subscribe_handle`]:
+    ///     super::service_control_handler::ServiceStatusHandle::subscribe_handle
+    pub handle: usize,
+
+    /// The GUID identifying the event.
+    pub event_guid: Guid,
+
+    /// The optional string the driver attached to the event.
+    #[serde(with = "schema::os_string::option")]
+    pub name: Option<OsString>,
+
+    /// The data the driver attached to the event. The format depends on the event.
+    pub data: Vec<u8>,
+}
+
+impl CustomDeviceEvent {
+    /// Decode the bytes of a `DEV_BROADCAST_HANDLE` structure reporting a custom event.
+    pub fn from_raw(broadcast: &[u8]) -> Result<Self> {
+        let broadcast = header(broadcast)?;
+        let device_type = read_u32(broadcast, 4)?;
+        if device_type != dbt::DBT_DEVTYP_HANDLE {
+            Err(ErrorKind::InvalidDeviceBroadcastType(device_type))?;
+        }
+        let data_offset = handle_data_offset();
+        let data = broadcast
+            .get(data_offset..)
+            .ok_or(ErrorKind::InvalidDeviceBroadcast)?;
+        let name_offset = read_u32(broadcast, data_offset - 4)? as i32;
+        let (data, name) = if name_offset < 0 {
+            (data, None)
+        } else {
+            let name_offset = name_offset as usize;
+            let data = data
+                .get(..name_offset)
+                .ok_or(ErrorKind::InvalidDeviceBroadcast)?;
+            let name = read_wide_string(broadcast, data_offset + name_offset)?;
+            (data, Some(name))
+        };
+        Ok(CustomDeviceEvent {
+            handle: read_usize(broadcast, handle_offset())?,
+            event_guid: read_guid(broadcast, event_guid_offset())?,
+            name,
+            data: data.to_vec(),
+        })
+    }
+}
+
+/// Returns the bytes of the broadcast structure, limited to the size given in its header.
+fn header(broadcast: &[u8]) -> Result<&[u8]> {
+    let size = read_u32(broadcast, 0)? as usize;
+    if size < HEADER_SIZE {
+        Err(ErrorKind::InvalidDeviceBroadcast)?;
+    }
+    Ok(broadcast
+        .get(..size)
+        .ok_or(ErrorKind::InvalidDeviceBroadcast)?)
+}
+
+fn read_bytes(bytes: &[u8], offset: usize, len: usize) -> Result<&[u8]> {
+    Ok(bytes
+        .get(offset..offset + len)
+        .ok_or(ErrorKind::InvalidDeviceBroadcast)?)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16> {
+    let bytes = read_bytes(bytes, offset, 2)?;
+    Ok(u16::from_ne_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    let bytes = read_bytes(bytes, offset, 4)?;
+    Ok(u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_usize(bytes: &[u8], offset: usize) -> Result<usize> {
+    let bytes = read_bytes(bytes, offset, mem::size_of::<usize>())?;
+    let mut value = [0; mem::size_of::<usize>()];
+    value.copy_from_slice(bytes);
+    Ok(usize::from_ne_bytes(value))
+}
+
+fn read_guid(bytes: &[u8], offset: usize) -> Result<Guid> {
+    let mut data4 = [0; 8];
+    data4.copy_from_slice(read_bytes(bytes, offset + 8, 8)?);
+    Ok(Guid::from_values(
+        read_u32(bytes, offset)?,
+        read_u16(bytes, offset + 4)?,
+        read_u16(bytes, offset + 6)?,
+        data4,
+    ))
+}
+
+/// Read a nul terminated wide string. A string that is not terminated before the end of the
+/// structure ends with the structure.
+fn read_wide_string(bytes: &[u8], offset: usize) -> Result<OsString> {
+    let bytes = bytes
+        .get(offset..)
+        .ok_or(ErrorKind::InvalidDeviceBroadcast)?;
+    let code_units = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_ne_bytes([pair[0], pair[1]]))
+        .take_while(|&code_unit| code_unit != 0)
+        .collect::<Vec<u16>>();
+    Ok(WideString::from_vec(code_units).to_os_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USB_DEVICE: Guid = Guid::from_values(
+        0xa5dcbf10,
+        0x6530,
+        0x11d2,
+        [0x90, 0x1f, 0x00, 0xc0, 0x4f, 0xb9, 0x51, 0xed],
+    );
+
+    fn push_header(buffer: &mut Vec<u8>, device_type: u32) {
+        buffer.extend_from_slice(&0u32.to_ne_bytes());
+        buffer.extend_from_slice(&device_type.to_ne_bytes());
+        buffer.extend_from_slice(&0u32.to_ne_bytes());
+    }
+
+    fn push_guid(buffer: &mut Vec<u8>, guid: Guid) {
+        buffer.extend_from_slice(&guid.data1.to_ne_bytes());
+        buffer.extend_from_slice(&guid.data2.to_ne_bytes());
+        buffer.extend_from_slice(&guid.data3.to_ne_bytes());
+        buffer.extend_from_slice(&guid.data4);
+    }
+
+    fn push_wide_string(buffer: &mut Vec<u8>, value: &str) {
+        for code_unit in value.encode_utf16().chain(Some(0)) {
+            buffer.extend_from_slice(&code_unit.to_ne_bytes());
+        }
+    }
+
+    fn set_size(mut buffer: Vec<u8>) -> Vec<u8> {
+        let size = buffer.len() as u32;
+        buffer[..4].copy_from_slice(&size.to_ne_bytes());
+        buffer
+    }
+
+    fn device_interface_broadcast(name: &str) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        push_header(&mut buffer, dbt::DBT_DEVTYP_DEVICEINTERFACE);
+        push_guid(&mut buffer, USB_DEVICE);
+        push_wide_string(&mut buffer, name);
+        set_size(buffer)
+    }
+
+    fn handle_broadcast(handle: usize, name_offset: i32, data: &[u8]) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        push_header(&mut buffer, dbt::DBT_DEVTYP_HANDLE);
+        buffer.resize(handle_offset(), 0);
+        buffer.extend_from_slice(&handle.to_ne_bytes());
+        buffer.extend_from_slice(&0usize.to_ne_bytes());
+        push_guid(&mut buffer, USB_DEVICE);
+        buffer.extend_from_slice(&name_offset.to_ne_bytes());
+        buffer.extend_from_slice(data);
+        set_size(buffer)
+    }
+
+    #[test]
+    fn test_device_interface_arrival() {
+        let name = r"\\?\USB#VID_1234&PID_5678#0001#{a5dcbf10-6530-11d2-901f-00c04fb951ed}";
+        let broadcast = device_interface_broadcast(name);
+        assert_eq!(
+            DeviceEvent::from_raw(dbt::DBT_DEVICEARRIVAL, &broadcast).unwrap(),
+            DeviceEvent::Arrival(DeviceBroadcast::DeviceInterface(DeviceInterfaceBroadcast {
+                class_guid: USB_DEVICE,
+                name: OsString::from(name),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_handle_remove_complete() {
+        let broadcast = handle_broadcast(0x1234, -1, &[0]);
+        assert_eq!(
+            DeviceEvent::from_raw(dbt::DBT_DEVICEREMOVECOMPLETE, &broadcast).unwrap(),
+            DeviceEvent::RemoveComplete(DeviceBroadcast::Handle(DeviceHandleBroadcast {
+                handle: 0x1234,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_custom_event() {
+        let mut data = vec![1, 2, 3];
+        push_wide_string(&mut data, "volume");
+        let broadcast = handle_broadcast(0x1234, 3, &data);
+        assert_eq!(
+            DeviceEvent::from_raw(dbt::DBT_CUSTOMEVENT, &broadcast).unwrap(),
+            DeviceEvent::CustomEvent(CustomDeviceEvent {
+                handle: 0x1234,
+                event_guid: USB_DEVICE,
+                name: Some(OsString::from("volume")),
+                data: vec![1, 2, 3],
+            })
+        );
+
+        let broadcast = handle_broadcast(0x1234, -1, &[4, 5]);
+        let event = CustomDeviceEvent::from_raw(&broadcast).unwrap();
+        assert_eq!(event.name, None);
+        assert_eq!(event.data, vec![4, 5]);
+    }
+
+    #[test]
+    fn test_from_raw_event() {
+        let broadcast = device_interface_broadcast("device");
+        let event = unsafe {
+            DeviceEvent::from_raw_event(
+                dbt::DBT_DEVICEQUERYREMOVE,
+                broadcast.as_ptr() as *const c_void,
+            )
+        };
+        match event.unwrap() {
+            DeviceEvent::QueryRemove(DeviceBroadcast::DeviceInterface(device)) => {
+                assert_eq!(device.name, OsString::from("device"))
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_invalid_broadcast() {
+        let broadcast = device_interface_broadcast("device");
+        assert!(DeviceEvent::from_raw(0x8005, &broadcast).is_err());
+        assert!(DeviceBroadcast::from_raw(&broadcast[..8]).is_err());
+        assert!(DeviceBroadcast::from_raw(&set_size(vec![0; 20])).is_err());
+        assert!(CustomDeviceEvent::from_raw(&broadcast).is_err());
+
+        let mut truncated = broadcast.clone();
+        truncated[..4].copy_from_slice(&(broadcast.len() as u32 + 2).to_ne_bytes());
+        assert!(DeviceBroadcast::from_raw(&truncated).is_err());
+
+        let broadcast = handle_broadcast(0x1234, 8, &[1, 2]);
+        assert!(CustomDeviceEvent::from_raw(&broadcast).is_err());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_broadcast_layout() {
+        use winapi::um::dbt::{DEV_BROADCAST_DEVICEINTERFACE_W, DEV_BROADCAST_HANDLE};
+
+        let interface: DEV_BROADCAST_DEVICEINTERFACE_W = unsafe { mem::zeroed() };
+        let base = &interface as *const _ as usize;
+        assert_eq!(
+            &interface.dbcc_name as *const _ as usize - base,
+            DEVICE_INTERFACE_NAME_OFFSET
+        );
+
+        let handle: DEV_BROADCAST_HANDLE = unsafe { mem::zeroed() };
+        let base = &handle as *const _ as usize;
+        assert_eq!(&handle.dbch_handle as *const _ as usize - base, handle_offset());
+        assert_eq!(
+            &handle.dbch_eventguid as *const _ as usize - base,
+            event_guid_offset()
+        );
+        assert_eq!(
+            &handle.dbch_data as *const _ as usize - base,
+            handle_data_offset()
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! Globally unique identifiers.
+//!
+//! The system identifies device interface classes, event providers and trigger subtypes by GUID.
+//! [`Guid`] mirrors the layout of the Win32 `GUID` structure, so it can be used on any platform.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(windows)]
+use winapi::shared::guiddef::GUID;
+
+use {Error, ErrorKind};
+
+/// A globally unique identifier.
+///
+/// GUIDs are formatted as 32 hexadecimal digits in groups separated by hyphens, and can be parsed
+/// with or without surrounding braces.
+///
+/// # Example
+///
+/// ```rust
+/// use windows_service::guid::Guid;
+///
+/// # fn main() -> windows_service::Result<()> {
+/// let usb_device: Guid = "{A5DCBF10-6530-11D2-901F-00C04FB951ED}".parse()?;
+/// assert_eq!(usb_device.to_string(), "a5dcbf10-6530-11d2-901f-00c04fb951ed");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+#[repr(C)]
+pub struct Guid {
+    pub data1: u32,
+    pub data2: u16,
+    pub data3: u16,
+    pub data4: [u8; 8],
+}
+
+impl Guid {
+    /// Create a GUID from its fields.
+    pub const fn from_values(data1: u32, data2: u16, data3: u16, data4: [u8; 8]) -> Self {
+        Guid {
+            data1,
+            data2,
+            data3,
+            data4,
+        }
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-",
+            self.data1, self.data2, self.data3, self.data4[0], self.data4[1]
+        )?;
+        for byte in &self.data4[2..] {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Guid {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let invalid = || Error::from(ErrorKind::InvalidGuid(s.to_owned()));
+        let digits = if s.starts_with('{') && s.ends_with('}') {
+            &s[1..s.len() - 1]
+        } else {
+            s
+        };
+
+        let groups: Vec<&str> = digits.split('-').collect();
+        let lengths: Vec<usize> = groups.iter().map(|group| group.len()).collect();
+        if lengths != [8, 4, 4, 4, 12]
+            || !digits.chars().all(|c| c == '-' || c.is_ascii_hexdigit())
+        {
+            return Err(invalid());
+        }
+
+        let tail = format!("{}{}", groups[3], groups[4]);
+        let mut data4 = [0u8; 8];
+        for (i, byte) in data4.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&tail[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+        }
+        Ok(Guid {
+            data1: u32::from_str_radix(groups[0], 16).map_err(|_| invalid())?,
+            data2: u16::from_str_radix(groups[1], 16).map_err(|_| invalid())?,
+            data3: u16::from_str_radix(groups[2], 16).map_err(|_| invalid())?,
+            data4,
+        })
+    }
+}
+
+#[cfg(windows)]
+impl From<GUID> for Guid {
+    fn from(guid: GUID) -> Self {
+        Guid::from_values(guid.Data1, guid.Data2, guid.Data3, guid.Data4)
+    }
+}
+
+#[cfg(windows)]
+impl From<Guid> for GUID {
+    fn from(guid: Guid) -> Self {
+        GUID {
+            Data1: guid.data1,
+            Data2: guid.data2,
+            Data3: guid.data3,
+            Data4: guid.data4,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USB_DEVICE: Guid = Guid::from_values(
+        0xa5dcbf10,
+        0x6530,
+        0x11d2,
+        [0x90, 0x1f, 0x00, 0xc0, 0x4f, 0xb9, 0x51, 0xed],
+    );
+
+    #[test]
+    fn test_guid_parse_and_format() {
+        assert_eq!(
+            "a5dcbf10-6530-11d2-901f-00c04fb951ed".parse::<Guid>().unwrap(),
+            USB_DEVICE
+        );
+        assert_eq!(
+            "{A5DCBF10-6530-11D2-901F-00C04FB951ED}".parse::<Guid>().unwrap(),
+            USB_DEVICE
+        );
+        assert_eq!(
+            USB_DEVICE.to_string(),
+            "a5dcbf10-6530-11d2-901f-00c04fb951ed"
+        );
+
+        for invalid in &[
+            "",
+            "a5dcbf10-6530-11d2-901f",
+            "a5dcbf10-6530-11d2-901f-00c04fb951eg",
+            "a5dcbf1-06530-11d2-901f-00c04fb951ed",
+            "{a5dcbf10-6530-11d2-901f-00c04fb951ed",
+            "+5dcbf10-6530-11d2-901f-00c04fb951ed",
+        ] {
+            assert!(invalid.parse::<Guid>().is_err(), "{}", invalid);
+        }
+    }
+}
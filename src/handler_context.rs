@@ -0,0 +1,373 @@
+//! Ownership of the service control handlers registered with the system.
+//!
+//! The system calls the handler of a service through [`service_control_handler`], passing back
+//! the context pointer it was registered with. The context is a [`HandlerContext`] that is kept
+//! alive for as long as the system may call the handler:
+//!
+//! * The `ServiceStatusHandle` returned from the registration shares the context, and drops the
+//!   handler once the service reports the `Stopped` state. Any control the system still delivers
+//!   afterwards is answered without calling into the dropped handler.
+//! * The context itself is retained until the service control dispatcher returns, which happens
+//!   after all services of the process have stopped, so that the pointer the system holds never
+//!   dangles.
+
+use std::fmt;
+use std::os::raw::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use service::ServiceControl;
+use sys::winerror::{ERROR_CALL_NOT_IMPLEMENTED, ERROR_SERVICE_CANNOT_ACCEPT_CTRL, NO_ERROR};
+use sys::winsvc;
+
+/// Abstraction over the return value of service control handler.
+/// The meaning of each of variants in this enum depends on the type of received event.
+///
+/// See the "Return value" section of corresponding MSDN article for more info:
+///
+/// <https://msdn.microsoft.com/en-us/library/windows/desktop/ms683241(v=vs.85).aspx>
+#[derive(Debug)]
+pub enum ServiceControlHandlerResult {
+    /// Either used to aknowledge the call or grant the permission in advanced events.
+    NoError,
+    /// The received event is not implemented.
+    NotImplemented,
+    /// This variant is used to deny permission and return the reason error code in advanced
+    /// events.
+    Other(u32),
+}
+
+impl ServiceControlHandlerResult {
+    pub fn to_raw(&self) -> u32 {
+        match *self {
+            ServiceControlHandlerResult::NoError => NO_ERROR,
+            ServiceControlHandlerResult::NotImplemented => ERROR_CALL_NOT_IMPLEMENTED,
+            ServiceControlHandlerResult::Other(code) => code,
+        }
+    }
+}
+
+/// Alias for control event handler closure, receiving the decoded control along with the raw
+/// event type and event data.
+pub(crate) type HandlerFn =
+    dyn Fn(ServiceControl, u32, *mut c_void) -> ServiceControlHandlerResult + Send;
+
+/// The registered handler of a service.
+pub(crate) struct HandlerContext {
+    /// The handler, or `None` once the service has stopped.
+    handler: Mutex<Option<Box<HandlerFn>>>,
+
+    /// Set when the service reports the `Stopped` state.
+    stopped: AtomicBool,
+}
+
+/// Contexts handed to the system, retained until the service control dispatcher returns.
+static RETAINED_CONTEXTS: Mutex<Vec<Arc<HandlerContext>>> = Mutex::new(Vec::new());
+
+impl HandlerContext {
+    pub(crate) fn new(handler: Box<HandlerFn>) -> Arc<Self> {
+        Arc::new(HandlerContext {
+            handler: Mutex::new(Some(handler)),
+            stopped: AtomicBool::new(false),
+        })
+    }
+
+    /// Retain the context until [`HandlerContext::release_retained`] is called, and return the
+    /// pointer to pass to the system along with [`service_control_handler`].
+    pub(crate) fn retain(context: &Arc<HandlerContext>) -> *mut c_void {
+        lock(&RETAINED_CONTEXTS).push(context.clone());
+        Arc::as_ptr(context) as *mut c_void
+    }
+
+    /// Release the retained contexts once the system no longer calls the handlers.
+    pub(crate) fn release_retained() {
+        let contexts = ::std::mem::take(&mut *lock(&RETAINED_CONTEXTS));
+        drop(contexts);
+    }
+
+    /// Drop the handler because the service stopped.
+    ///
+    /// If the handler is running, for example because it reported the `Stopped` state itself, it
+    /// is dropped as soon as it returns.
+    pub(crate) fn release(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.drop_handler_if_idle();
+    }
+
+    /// Pass a control to the handler and return the raw result for the system.
+    pub(crate) fn dispatch(&self, control: u32, event_type: u32, event_data: *mut c_void) -> u32 {
+        let result = {
+            let handler = lock(&self.handler);
+            match *handler {
+                Some(ref handler_fn) if !self.stopped.load(Ordering::SeqCst) => {
+                    // Pass the controls that cannot be decoded on as they are, so that the handler
+                    // answers them, for example to grant a query it does not know about.
+                    let service_control =
+                        unsafe { ServiceControl::from_raw_event(control, event_type, event_data) }
+                            .unwrap_or(ServiceControl::Unknown {
+                                control,
+                                event_type,
+                            });
+                    (handler_fn)(service_control, event_type, event_data).to_raw()
+                }
+                _ => Self::stopped_result(control),
+            }
+        };
+        if self.stopped.load(Ordering::SeqCst) {
+            self.drop_handler_if_idle();
+        }
+        result
+    }
+
+    fn drop_handler_if_idle(&self) {
+        if let Ok(mut handler) = self.handler.try_lock() {
+            *handler = None;
+        }
+    }
+
+    /// The result of a control received after the service stopped.
+    fn stopped_result(control: u32) -> u32 {
+        if control == winsvc::SERVICE_CONTROL_INTERROGATE {
+            NO_ERROR
+        } else {
+            ERROR_SERVICE_CANNOT_ACCEPT_CTRL
+        }
+    }
+}
+
+impl fmt::Debug for HandlerContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HandlerContext")
+            .field("stopped", &self.stopped.load(Ordering::SeqCst))
+            .finish()
+    }
+}
+
+/// A handler that panicked while holding the lock can still be dropped.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Static service control handler, receiving a [`HandlerContext`] as its context.
+pub(crate) extern "system" fn service_control_handler(
+    control: u32,
+    event_type: u32,
+    event_data: *mut c_void,
+    context: *mut c_void,
+) -> u32 {
+    // The context is retained for as long as the system calls the handler.
+    let context = unsafe { &*(context as *const HandlerContext) };
+    context.dispatch(control, event_type, event_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+    use std::sync::mpsc;
+    use sys::dbt;
+
+    /// A handler context as registered with the system, along with the pointer the system calls
+    /// the handler with.
+    struct Harness {
+        context: Arc<HandlerContext>,
+        pointer: *mut c_void,
+    }
+
+    impl Harness {
+        fn new<F>(handler: F) -> Self
+        where
+            F: Fn(ServiceControl) -> ServiceControlHandlerResult + Send + 'static,
+        {
+            let context = HandlerContext::new(Box::new(move |control, _, _| handler(control)));
+            let pointer = Arc::as_ptr(&context) as *mut c_void;
+            Harness { context, pointer }
+        }
+
+        /// Deliver the controls through the trampoline, like the system does, and return the
+        /// raw results.
+        fn replay(&self, controls: &[u32]) -> Vec<u32> {
+            controls
+                .iter()
+                .map(|&control| service_control_handler(control, 0, ptr::null_mut(), self.pointer))
+                .collect()
+        }
+    }
+
+    /// A handler that forwards the controls and reports the service as stopped on `Stop`.
+    fn stopping_handler(
+        context: Arc<Mutex<Option<Arc<HandlerContext>>>>,
+        controls: mpsc::Sender<ServiceControl>,
+    ) -> impl Fn(ServiceControl) -> ServiceControlHandlerResult + Send {
+        let controls = Mutex::new(controls);
+        move |control| {
+            let stop = control == ServiceControl::Stop;
+            lock(&controls).send(control).unwrap();
+            if stop {
+                lock(&context).as_ref().unwrap().release();
+            }
+            ServiceControlHandlerResult::NoError
+        }
+    }
+
+    #[test]
+    fn test_dispatch_to_handler() {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let harness = Harness::new(move |control| {
+            lock(&sender).send(control.clone()).unwrap();
+            match control {
+                ServiceControl::Interrogate | ServiceControl::Stop => {
+                    ServiceControlHandlerResult::NoError
+                }
+                _ => ServiceControlHandlerResult::NotImplemented,
+            }
+        });
+        let results = harness.replay(&[
+            winsvc::SERVICE_CONTROL_INTERROGATE,
+            winsvc::SERVICE_CONTROL_PAUSE,
+            0x7F,
+            winsvc::SERVICE_CONTROL_SESSIONCHANGE,
+            winsvc::SERVICE_CONTROL_STOP,
+        ]);
+        assert_eq!(
+            results,
+            vec![
+                NO_ERROR,
+                ERROR_CALL_NOT_IMPLEMENTED,
+                ERROR_CALL_NOT_IMPLEMENTED,
+                ERROR_CALL_NOT_IMPLEMENTED,
+                NO_ERROR,
+            ]
+        );
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![
+                ServiceControl::Interrogate,
+                ServiceControl::Pause,
+                ServiceControl::Unknown {
+                    control: 0x7F,
+                    event_type: 0,
+                },
+                ServiceControl::Unknown {
+                    control: winsvc::SERVICE_CONTROL_SESSIONCHANGE,
+                    event_type: 0,
+                },
+                ServiceControl::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_undecodable_query_reaches_handler() {
+        let harness = Harness::new(|control| match control {
+            ServiceControl::Unknown { .. } => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        });
+        // A device query without its broadcast structure cannot be decoded, and the handler
+        // grants it instead of the query being vetoed as not implemented.
+        let result = service_control_handler(
+            winsvc::SERVICE_CONTROL_DEVICEEVENT,
+            dbt::DBT_DEVICEQUERYREMOVE,
+            ptr::null_mut(),
+            harness.pointer,
+        );
+        assert_eq!(result, NO_ERROR);
+    }
+
+    #[test]
+    fn test_controls_after_stop_keep_handler() {
+        // The handler stays registered until the service reports the stopped state, so a
+        // second `Stop` or `Interrogate` still reaches it.
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let harness = Harness::new(move |control| {
+            lock(&sender).send(control).unwrap();
+            ServiceControlHandlerResult::NoError
+        });
+        let results = harness.replay(&[
+            winsvc::SERVICE_CONTROL_STOP,
+            winsvc::SERVICE_CONTROL_INTERROGATE,
+            winsvc::SERVICE_CONTROL_STOP,
+        ]);
+        assert_eq!(results, vec![NO_ERROR; 3]);
+        assert_eq!(receiver.try_iter().count(), 3);
+    }
+
+    #[test]
+    fn test_controls_after_stopped_status() {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let harness = Harness::new(move |control| {
+            lock(&sender).send(control).unwrap();
+            ServiceControlHandlerResult::NoError
+        });
+        assert_eq!(
+            harness.replay(&[winsvc::SERVICE_CONTROL_INTERROGATE]),
+            vec![NO_ERROR]
+        );
+        harness.context.release();
+        assert!(lock(&harness.context.handler).is_none());
+        assert_eq!(
+            harness.replay(&[
+                winsvc::SERVICE_CONTROL_INTERROGATE,
+                winsvc::SERVICE_CONTROL_STOP,
+                winsvc::SERVICE_CONTROL_SHUTDOWN,
+            ]),
+            vec![
+                NO_ERROR,
+                ERROR_SERVICE_CANNOT_ACCEPT_CTRL,
+                ERROR_SERVICE_CANNOT_ACCEPT_CTRL,
+            ]
+        );
+        assert_eq!(receiver.recv().unwrap(), ServiceControl::Interrogate);
+        // The handler was dropped along with its sender.
+        assert!(receiver.recv().is_err());
+    }
+
+    #[test]
+    fn test_stopped_status_reported_from_handler() {
+        let shared_context = Arc::new(Mutex::new(None));
+        let (sender, receiver) = mpsc::channel();
+        let harness = Harness::new(stopping_handler(shared_context.clone(), sender));
+        *lock(&shared_context) = Some(harness.context.clone());
+
+        let results = harness.replay(&[
+            winsvc::SERVICE_CONTROL_INTERROGATE,
+            winsvc::SERVICE_CONTROL_STOP,
+            winsvc::SERVICE_CONTROL_STOP,
+            winsvc::SERVICE_CONTROL_INTERROGATE,
+        ]);
+        assert_eq!(
+            results,
+            vec![NO_ERROR, NO_ERROR, ERROR_SERVICE_CANNOT_ACCEPT_CTRL, NO_ERROR]
+        );
+        // The handler was dropped when it returned from the `Stop` control.
+        assert!(lock(&harness.context.handler).is_none());
+        assert_eq!(
+            receiver.iter().collect::<Vec<_>>(),
+            vec![ServiceControl::Interrogate, ServiceControl::Stop]
+        );
+        lock(&shared_context).take();
+    }
+
+    #[test]
+    fn test_retained_context_outlives_handle() {
+        let harness = Harness::new(|_| ServiceControlHandlerResult::NoError);
+        let pointer = HandlerContext::retain(&harness.context);
+        assert_eq!(pointer, harness.pointer);
+        let weak = Arc::downgrade(&harness.context);
+        drop(harness);
+        assert_eq!(
+            service_control_handler(
+                winsvc::SERVICE_CONTROL_INTERROGATE,
+                0,
+                ptr::null_mut(),
+                pointer
+            ),
+            NO_ERROR
+        );
+        HandlerContext::release_retained();
+        assert!(weak.upgrade().is_none());
+    }
+}
@@ -8,6 +8,10 @@
 
 //! A crate that provides facilities for management and implementation of windows services.
 //!
+//! The data model in [`service`], the [`shell_escape`] helpers and the in-memory
+//! [`FakeScm`] are available on every platform, while the parts that talk to the system are only
+//! compiled on Windows.
+//!
 //! # Implementing windows service
 //!
 //! This section describes the steps of implementing a program that runs as a windows service, for
@@ -30,8 +34,10 @@
 //! extern crate windows_service;
 //!
 //! use std::ffi::OsString;
+//! # #[cfg(windows)]
 //! use windows_service::service_dispatcher;
 //!
+//! # #[cfg(windows)]
 //! define_windows_service!(ffi_service_main, my_service_main);
 //!
 //! fn my_service_main(arguments: Vec<OsString>) {
@@ -39,12 +45,15 @@
 //!     // `service_dispatcher::start` from `main`.
 //! }
 //!
+//! # #[cfg(windows)]
 //! fn main() -> Result<(), windows_service::Error> {
 //!     // Register generated `ffi_service_main` with the system and start the service, blocking
 //!     // this thread until the service is stopped.
 //!     service_dispatcher::start("myservice", ffi_service_main)?;
 //!     Ok(())
 //! }
+//! # #[cfg(not(windows))]
+//! # fn main() {}
 //! ```
 //!
 //! ## Handling service events
@@ -56,15 +65,19 @@
 //! extern crate windows_service;
 //!
 //! use std::ffi::OsString;
+//! # #[cfg(windows)]
 //! use windows_service::service::ServiceControl;
+//! # #[cfg(windows)]
 //! use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
 //!
+//! # #[cfg(windows)]
 //! fn my_service_main(arguments: Vec<OsString>) {
 //!     if let Err(_e) = run_service(arguments) {
 //!         // Handle errors in some way.
 //!     }
 //! }
 //!
+//! # #[cfg(windows)]
 //! fn run_service(arguments: Vec<OsString>) -> Result<(), windows_service::Error> {
 //!     let event_handler = move |control_event| -> ServiceControlHandlerResult {
 //!         match control_event {
@@ -108,23 +121,30 @@
 //!
 //! Note that it's safe to clone [`ServiceStatusHandle`] and use it from any thread.
 //!
+//! [`StatusReporter`] wraps the [`ServiceStatusHandle`] to keep track of the current state,
+//! increment the checkpoint as the service makes progress and refuse invalid state transitions.
+//!
 //! ```rust,no_run
 //! extern crate windows_service;
 //!
 //! use std::ffi::OsString;
 //! use std::time::Duration;
+//! # #[cfg(windows)]
 //! use windows_service::service::{
 //!     ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus,
 //!     ServiceType,
 //! };
+//! # #[cfg(windows)]
 //! use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
 //!
+//! # #[cfg(windows)]
 //! fn my_service_main(arguments: Vec<OsString>) {
 //!     if let Err(_e) = run_service(arguments) {
 //!         // Handle error in some way.
 //!     }
 //! }
 //!
+//! # #[cfg(windows)]
 //! fn run_service(arguments: Vec<OsString>) -> windows_service::Result<()> {
 //!     let event_handler = move |control_event| -> ServiceControlHandlerResult {
 //!         match control_event {
@@ -165,26 +185,40 @@
 //! Please refer to the "Service State Transitions" article on MSDN for more info:\
 //! <https://msdn.microsoft.com/en-us/library/windows/desktop/ee126211(v=vs.85).aspx>
 //!
+//! ## Asynchronous services
+//!
+//! With the `async` feature, [`service_control_handler::register_stream`] delivers the service
+//! events as a stream instead of calling a closure, and [`ServiceStatusHandle::report_pending`]
+//! keeps reporting the progress of a lengthy transition while a future runs. See the
+//! `asynchronous` module for the futures that work with any async runtime.
+//!
+//! [`FakeScm`]: backend::FakeScm
 //! [`ServiceStatusHandle`]: service_control_handler::ServiceStatusHandle
+//! [`StatusReporter`]: status_reporter::StatusReporter
+//! [`service_control_handler::register_stream`]: service_control_handler::register_stream
+//! [`ServiceStatusHandle::report_pending`]:
+//!     service_control_handler::ServiceStatusHandle::report_pending
 //! [`ServiceStatus::wait_hint`]: service::ServiceStatus::wait_hint
 //! [`ServiceStatus::checkpoint`]: service::ServiceStatus::checkpoint
 //! [`StartPending`]: service::ServiceState::StartPending
 //! [`Running`]: service::ServiceState::Running
 
-#![cfg(windows)]
 // Because of how deeply error-chain recurse with this many error types.
-#![recursion_limit = "128"]
+#![recursion_limit = "256"]
 
 #[macro_use]
 extern crate bitflags;
 #[macro_use]
 extern crate error_chain;
 extern crate widestring;
+#[cfg(windows)]
 extern crate winapi;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde;
 extern crate serde_json;
+#[cfg(feature = "async")]
+extern crate futures_core;
 
 pub use error_chain::ChainedError;
 
@@ -198,6 +232,26 @@ error_chain! {
         InvalidAccountPassword {
             description("Invalid account password")
         }
+        /// Invalid service dependency.
+        InvalidDependency {
+            description("Invalid service dependency")
+        }
+        /// Invalid service description.
+        InvalidDescription {
+            description("Invalid service description")
+        }
+        /// Invalid reboot message of the failure actions.
+        InvalidRebootMessage {
+            description("Invalid reboot message")
+        }
+        /// Invalid command line of the failure actions.
+        InvalidFailureCommand {
+            description("Invalid failure command")
+        }
+        /// Invalid data item of a service trigger.
+        InvalidServiceTriggerData {
+            description("Invalid service trigger data")
+        }
         /// Invalid display name.
         InvalidDisplayName {
             description("Invalid display name")
@@ -210,10 +264,22 @@ error_chain! {
         InvalidExecutablePath {
             description("Invalid executable path")
         }
+        /// Invalid load ordering group.
+        InvalidLoadOrderGroup {
+            description("Invalid load ordering group")
+        }
         /// Invalid launch arguments.
         InvalidLaunchArgument {
             description("Invalid launch argument")
         }
+        /// Invalid service start argument.
+        InvalidStartArgument {
+            description("Invalid start argument")
+        }
+        /// Invalid required privilege.
+        InvalidRequiredPrivilege {
+            description("Invalid required privilege")
+        }
         /// Invalid machine name.
         InvalidMachineName {
             description("Invalid machine name")
@@ -238,22 +304,155 @@ error_chain! {
             description("Invalid service state")
             display("Invalid service state value: {}", raw_value)
         }
+        /// Invalid raw representation of [`ServiceSidType`].
+        InvalidServiceSidType(raw_value: u32) {
+            description("Invalid service SID type")
+            display("Invalid service SID type value: {}", raw_value)
+        }
+        /// Invalid raw representation of [`ServiceActionType`].
+        InvalidServiceActionType(raw_value: u32) {
+            description("Invalid service action type")
+            display("Invalid service action type value: {}", raw_value)
+        }
+        /// Invalid raw representation of [`ServiceTriggerType`].
+        InvalidServiceTriggerType(raw_value: u32) {
+            description("Invalid service trigger type")
+            display("Invalid service trigger type value: {}", raw_value)
+        }
+        /// Invalid raw representation of [`ServiceTriggerAction`].
+        InvalidServiceTriggerAction(raw_value: u32) {
+            description("Invalid service trigger action")
+            display("Invalid service trigger action value: {}", raw_value)
+        }
+        /// Invalid raw representation of [`ServiceTriggerDataItem`].
+        InvalidServiceTriggerDataType(raw_value: u32) {
+            description("Invalid service trigger data type")
+            display("Invalid service trigger data type value: {}", raw_value)
+        }
+        /// Invalid string representation of [`Guid`].
+        InvalidGuid(value: String) {
+            description("Invalid GUID")
+            display("Invalid GUID: {}", value)
+        }
         /// Invalid raw representation of [`ServiceControl`].
         InvalidServiceControl(raw_value: u32) {
             description("Invalid service control")
             display("Invalid service control value: {}", raw_value)
         }
+        /// The service control carries event data that was not provided.
+        MissingServiceControlEventData(raw_value: u32) {
+            description("Missing service control event data")
+            display("Missing event data of service control: {}", raw_value)
+        }
+        /// Invalid raw representation of [`SessionChangeReason`].
+        InvalidSessionChangeReason(raw_value: u32) {
+            description("Invalid session change reason")
+            display("Invalid session change reason value: {}", raw_value)
+        }
+        /// Invalid raw representation of [`PowerEventParam`].
+        InvalidPowerEventType(raw_value: u32) {
+            description("Invalid power event type")
+            display("Invalid power event type value: {}", raw_value)
+        }
+        /// Invalid raw representation of [`DeviceEvent`].
+        InvalidDeviceEventType(raw_value: u32) {
+            description("Invalid device event type")
+            display("Invalid device event type value: {}", raw_value)
+        }
+        /// Invalid device type of a [`DeviceBroadcast`].
+        InvalidDeviceBroadcastType(raw_value: u32) {
+            description("Invalid device broadcast type")
+            display("Invalid device broadcast type value: {}", raw_value)
+        }
+        /// The device broadcast structure is truncated or malformed.
+        InvalidDeviceBroadcast {
+            description("Invalid device broadcast")
+        }
+        /// Invalid raw representation of [`HardwareProfileChangeParam`].
+        InvalidHardwareProfileChangeType(raw_value: u32) {
+            description("Invalid hardware profile change type")
+            display("Invalid hardware profile change type value: {}", raw_value)
+        }
+        /// The time of a [`TimeChangeParam`] does not fit in `SystemTime`.
+        InvalidFileTime(raw_value: i64) {
+            description("Invalid file time")
+            display("Invalid file time value: {}", raw_value)
+        }
+
+        /// The service stopped instead of reaching the awaited state.
+        ServiceFailed(exit_code: ::service::ServiceExitCode) {
+            description("Service stopped unexpectedly")
+            display("Service stopped unexpectedly with exit code {:?}", exit_code)
+        }
+        /// The service did not advance its checkpoint within the wait hint it reported.
+        ServiceHung(state: ::service::ServiceState, checkpoint: u32) {
+            description("Service stopped making progress")
+            display(
+                "Service stopped making progress in {:?} state at checkpoint {}",
+                state,
+                checkpoint
+            )
+        }
+        /// The service settled in a state other than the awaited one.
+        UnexpectedServiceState(state: ::service::ServiceState) {
+            description("Service is in an unexpected state")
+            display("Service is in an unexpected state: {:?}", state)
+        }
+        /// The service was still making progress when the wait timed out.
+        Timeout(state: ::service::ServiceState) {
+            description("Timed out waiting for service state")
+            display("Timed out waiting for service state, last state: {:?}", state)
+        }
+        /// The service backend returned a setting of another kind than the one queried.
+        UnexpectedServiceSetting(
+            expected: ::service::ServiceSettingKind,
+            actual: ::service::ServiceSettingKind
+        ) {
+            description("Service backend returned an unexpected setting")
+            display("Service backend returned {:?} when queried for {:?}", actual, expected)
+        }
+        /// The service cannot move from its current state to the reported one.
+        InvalidStateTransition(from: ::service::ServiceState, to: ::service::ServiceState) {
+            description("Invalid service state transition")
+            display("Invalid service state transition from {:?} to {:?}", from, to)
+        }
+        /// Progress can only be reported while the service is in a pending state.
+        ServiceNotPending(state: ::service::ServiceState) {
+            description("Service is not in a pending state")
+            display("Service is not in a pending state: {:?}", state)
+        }
     }
     foreign_links {
         System(::std::io::Error) #[doc = "System call error"];
     }
 }
 
+#[cfg(feature = "async")]
+pub mod asynchronous;
+pub mod backend;
+pub mod device_event;
+pub mod guid;
+// Only the Windows-only control handler registration uses the contexts outside of tests.
+#[cfg_attr(not(windows), allow(dead_code))]
+mod handler_context;
+#[cfg(windows)]
 mod sc_handle;
+pub mod schema;
 pub mod service;
+#[cfg(windows)]
 pub mod service_control_handler;
+// Only the Windows-only service control dispatcher uses the entry points outside of tests.
+#[cfg_attr(not(windows), allow(dead_code))]
+mod service_entry;
 pub mod service_manager;
+pub mod service_runner;
+pub mod service_trigger;
+#[cfg(windows)]
 #[macro_use]
 pub mod service_dispatcher;
 
-mod shell_escape;
+pub mod shell_escape;
+pub mod status_reporter;
+mod sys;
+mod wait;
+pub mod watch;
@@ -3,6 +3,11 @@ use winapi::um::winsvc;
 /// A handle holder that wraps a low level [`winsvc::SC_HANDLE`].
 pub(crate) struct ScHandle(winsvc::SC_HANDLE);
 
+// Service control manager handles are not tied to the thread that opened them, and can be used
+// from several threads at once.
+unsafe impl Send for ScHandle {}
+unsafe impl Sync for ScHandle {}
+
 impl ScHandle {
     pub(crate) unsafe fn new(handle: winsvc::SC_HANDLE) -> Self {
         ScHandle(handle)
@@ -0,0 +1,736 @@
+//! Serialization schema of the service types.
+//!
+//! All types in [`service`] implement `Serialize` and `Deserialize` following a schema that does
+//! not depend on the platform the data was produced on, so that for example the details of the
+//! services listed by [`ServiceManager::list_services`] on a Windows host can be read back on any
+//! other platform.
+//!
+//! The schema is versioned with [`SCHEMA_VERSION`]. Wrap the data in [`Versioned`] to record the
+//! version alongside it. The version is bumped whenever a change is made that an older reader
+//! would not be able to parse.
+//!
+//! Some values can be written in more than one way, and the reader tells them apart by the type
+//! of the value found in the data: OS strings that are not valid Unicode, and the flags,
+//! durations and dependencies written by version 1. Reading therefore requires a self-describing
+//! data format, such as JSON, CBOR or MessagePack. Formats that rely on the reader to know the
+//! type of each value, such as bincode or postcard, can write the data but fail to read it back.
+//!
+//! # Version 1
+//!
+//! The format written by [`ServiceManager::list_services`] before the schema was versioned, as a
+//! bare array of [`ServiceDetail`] values without a [`Versioned`] wrapper. Data in this format is
+//! still accepted when reading.
+//!
+//! * Structs are objects with `camelCase` field names.
+//! * Enums without data, such as [`ServiceState`], are strings holding the variant name, for
+//!   example `"Running"`.
+//! * [`ServiceExitCode`] is an object with a single key naming the variant, for example
+//!   `{"Win32": 0}` or `{"ServiceSpecific": 1}`.
+//! * Flags, such as [`ServiceControlAccept`], are objects holding the raw Win32 value, for example
+//!   `{"bits": 5}`.
+//! * Durations, such as [`ServiceStatus::wait_hint`], are objects holding the seconds and the
+//!   nanoseconds, for example `{"secs": 1, "nanos": 500000000}`.
+//! * Names and paths are strings, with the characters that are not valid Unicode replaced.
+//! * [`ServiceDetail::dependencies`] is a single string.
+//!
+//! # Version 2
+//!
+//! * Flags are integers holding the raw Win32 value. Unknown bits are dropped when reading.
+//! * Durations are integers holding milliseconds.
+//! * OS strings and paths are strings. Values that are not valid Unicode are written as an array
+//!   of UTF-16 code units instead.
+//! * [`ServiceDependency`] is the string the system uses to identify the dependency: the service
+//!   name, or the load ordering group name prefixed with `+`.
+//! * [`ServiceDetail::dependencies`] is a list of dependencies instead of a single string. A
+//!   single string written by version 1 is still accepted when reading.
+//! * [`ServiceInfo`] has the optional `loadOrderGroup` and `dependencies` fields.
+//! * [`ServiceConfig`] has the `binaryPath` field, the command line the `executablePath` and
+//!   `launchArguments` fields are parsed from. It is empty when missing.
+//! * [`ServiceControl`] has the `UserDefined` variant, written like [`ServiceExitCode`].
+//! * [`ServiceControl`] has the `Unknown` variant, written as
+//!   `{"Unknown": {"control": 64, "event_type": 0}}`.
+//! * [`ServiceControl`] has the variants for system events. The variants that carry event data
+//!   are written like [`ServiceExitCode`], with the times of `TimeChange` written as
+//!   `{"secs_since_epoch": 1500000000, "nanos_since_epoch": 0}`.
+//! * [`DeviceEvent`] is written like [`ServiceExitCode`], with the data of custom events as an
+//!   array of bytes.
+//! * [`ServiceDetail`] has the optional `failureActions` field.
+//! * [`ServiceInfo`] has the optional `settings` field, a list of [`ServiceSetting`] values
+//!   written like [`ServiceExitCode`], for example `{"DelayedAutoStart": true}`.
+//! * [`ServiceTrigger`] data items are written like [`ServiceExitCode`], with binary data as an
+//!   array of bytes.
+//! * [`Guid`] is a string of hyphen separated hexadecimal digits, for example
+//!   `"a5dcbf10-6530-11d2-901f-00c04fb951ed"`.
+//!
+//! # Example
+//!
+//! ```rust
+//! extern crate serde_json;
+//! extern crate windows_service;
+//!
+//! use std::time::Duration;
+//! use windows_service::schema::Versioned;
+//! use windows_service::service::{
+//!     ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType,
+//! };
+//!
+//! # fn main() {
+//! let status = ServiceStatus {
+//!     service_type: ServiceType::OwnProcess,
+//!     current_state: ServiceState::Running,
+//!     controls_accepted: ServiceControlAccept::STOP,
+//!     exit_code: ServiceExitCode::Win32(0),
+//!     checkpoint: 0,
+//!     wait_hint: Duration::default(),
+//! };
+//!
+//! let json = serde_json::to_string(&Versioned::new(status.clone())).unwrap();
+//! let snapshot: Versioned<ServiceStatus> = serde_json::from_str(&json).unwrap();
+//! assert_eq!(snapshot.data, status);
+//! # }
+//! ```
+//!
+//! [`service`]: super::service
+//! [`ServiceManager::list_services`]: super::service_manager::ServiceManager::list_services
+//! [`ServiceState`]: super::service::ServiceState
+//! [`ServiceExitCode`]: super::service::ServiceExitCode
+//! [`ServiceControlAccept`]: super::service::ServiceControlAccept
+//! [`ServiceStatus::wait_hint`]: super::service::ServiceStatus::wait_hint
+//! [`ServiceDependency`]: super::service::ServiceDependency
+//! [`ServiceDetail`]: super::service::ServiceDetail
+//! [`ServiceDetail::dependencies`]: super::service::ServiceDetail::dependencies
+//! [`ServiceInfo`]: super::service::ServiceInfo
+//! [`ServiceConfig`]: super::service::ServiceConfig
+//! [`ServiceControl`]: super::service::ServiceControl
+//! [`ServiceSetting`]: super::service::ServiceSetting
+//! [`ServiceTrigger`]: super::service_trigger::ServiceTrigger
+//! [`Guid`]: super::guid::Guid
+//! [`DeviceEvent`]: super::device_event::DeviceEvent
+
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+use std::ffi::OsString;
+
+use guid::Guid;
+use service::{ServiceAccess, ServiceControlAccept, ServiceDependency};
+use service_manager::ServiceManagerAccess;
+
+/// The version of the serialization schema implemented by this crate.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Data tagged with the version of the schema it was serialized with.
+///
+/// Deserialization fails if the data was written with a newer schema than [`SCHEMA_VERSION`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Versioned<T> {
+    /// The schema version the data was serialized with.
+    #[serde(deserialize_with = "deserialize_schema_version")]
+    pub schema_version: u32,
+
+    /// The versioned data.
+    pub data: T,
+}
+
+impl<T> Versioned<T> {
+    /// Tag the data with the current [`SCHEMA_VERSION`].
+    pub fn new(data: T) -> Self {
+        Versioned {
+            schema_version: SCHEMA_VERSION,
+            data,
+        }
+    }
+}
+
+fn deserialize_schema_version<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> ::std::result::Result<u32, D::Error> {
+    let version = u32::deserialize(deserializer)?;
+    if version == 0 || version > SCHEMA_VERSION {
+        Err(de::Error::custom(format_args!(
+            "unsupported schema version {}, expected at most {}",
+            version, SCHEMA_VERSION
+        )))
+    } else {
+        Ok(version)
+    }
+}
+
+/// The raw value of flags, or the object holding it that version 1 of the schema wrote.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawFlags {
+    Bits(u32),
+    Legacy { bits: u32 },
+}
+
+impl RawFlags {
+    fn bits(self) -> u32 {
+        match self {
+            RawFlags::Bits(bits) | RawFlags::Legacy { bits } => bits,
+        }
+    }
+}
+
+/// Implement serialization of bitflags types as their raw value.
+macro_rules! impl_serde_for_bitflags {
+    ($($flags:ident),*) => {
+        $(
+            impl Serialize for $flags {
+                fn serialize<S: Serializer>(
+                    &self,
+                    serializer: S,
+                ) -> ::std::result::Result<S::Ok, S::Error> {
+                    serializer.serialize_u32(self.bits())
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $flags {
+                fn deserialize<D: Deserializer<'de>>(
+                    deserializer: D,
+                ) -> ::std::result::Result<Self, D::Error> {
+                    let raw = ::schema::RawFlags::deserialize(deserializer)?;
+                    Ok($flags::from_bits_truncate(raw.bits()))
+                }
+            }
+        )*
+    };
+}
+
+impl_serde_for_bitflags!(ServiceAccess, ServiceControlAccept, ServiceManagerAccess);
+
+impl Serialize for ServiceDependency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> {
+        os_string::serialize(&self.to_system_identifier(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ServiceDependency {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> ::std::result::Result<Self, D::Error> {
+        os_string::deserialize::<OsString, D>(deserializer)
+            .map(ServiceDependency::from_system_identifier)
+    }
+}
+
+impl Serialize for Guid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Guid {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> ::std::result::Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        string.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Read a list of dependencies, accepting the single string written by version 1 of the schema.
+pub(crate) fn legacy_dependencies<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> ::std::result::Result<Option<Vec<ServiceDependency>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Dependencies {
+        List(Vec<ServiceDependency>),
+        Single(ServiceDependency),
+    }
+
+    Ok(match Option::<Dependencies>::deserialize(deserializer)? {
+        Some(Dependencies::List(dependencies)) => Some(dependencies),
+        Some(Dependencies::Single(ServiceDependency::Service(ref name))) if name.is_empty() => {
+            Some(vec![])
+        }
+        Some(Dependencies::Single(dependency)) => Some(vec![dependency]),
+        None => None,
+    })
+}
+
+/// Durations represented as integer milliseconds, accepting the object holding the seconds and
+/// nanoseconds that version 1 of the schema wrote. Durations longer than `u64::MAX` milliseconds
+/// are written as `u64::MAX`.
+pub(crate) mod duration_millis {
+    use std::time::Duration;
+
+    use serde::de::{self, Deserialize, Deserializer};
+    use serde::ser::Serializer;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawDuration {
+        Millis(u64),
+        Legacy { secs: u64, nanos: u32 },
+    }
+
+    pub fn serialize<S: Serializer>(
+        duration: &Duration,
+        serializer: S,
+    ) -> ::std::result::Result<S::Ok, S::Error> {
+        let millis = duration
+            .as_secs()
+            .saturating_mul(1000)
+            .saturating_add(u64::from(duration.subsec_millis()));
+        serializer.serialize_u64(millis)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> ::std::result::Result<Duration, D::Error> {
+        match RawDuration::deserialize(deserializer)? {
+            RawDuration::Millis(millis) => Ok(Duration::from_millis(millis)),
+            RawDuration::Legacy { secs, nanos } => Duration::from_secs(secs)
+                .checked_add(Duration::from_nanos(u64::from(nanos)))
+                .ok_or_else(|| de::Error::custom("duration overflow")),
+        }
+    }
+}
+
+/// OS strings and paths represented as strings, falling back to UTF-16 code units for values
+/// that are not valid Unicode.
+///
+/// Reading relies on `deserialize_any` to tell the two representations apart.
+pub(crate) mod os_string {
+    use std::ffi::{OsStr, OsString};
+    use std::fmt;
+
+    use serde::de::{self, Deserializer, SeqAccess, Visitor};
+    use serde::ser::Serializer;
+    use widestring::WideString;
+
+    pub fn serialize<T: AsRef<OsStr>, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> ::std::result::Result<S::Ok, S::Error> {
+        let value = value.as_ref();
+        match value.to_str() {
+            Some(string) => serializer.serialize_str(string),
+            None => serializer.collect_seq(WideString::from_str(value).as_slice()),
+        }
+    }
+
+    pub fn deserialize<'de, T: From<OsString>, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> ::std::result::Result<T, D::Error> {
+        deserializer.deserialize_any(OsStringVisitor).map(T::from)
+    }
+
+    struct OsStringVisitor;
+
+    impl<'de> Visitor<'de> for OsStringVisitor {
+        type Value = OsString;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a string or an array of UTF-16 code units")
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> ::std::result::Result<OsString, E> {
+            Ok(OsString::from(value))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(
+            self,
+            mut seq: A,
+        ) -> ::std::result::Result<OsString, A::Error> {
+            let mut code_units = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(code_unit) = seq.next_element::<u16>()? {
+                code_units.push(code_unit);
+            }
+            Ok(WideString::from_vec(code_units).to_os_string())
+        }
+    }
+
+    /// Wrapper to serialize a single OS string nested in other types.
+    #[derive(Serialize, Deserialize)]
+    struct Item(
+        #[serde(serialize_with = "serialize", deserialize_with = "deserialize")] OsString,
+    );
+
+    /// Optional OS strings and paths.
+    pub mod option {
+        use std::ffi::{OsStr, OsString};
+
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        use super::Item;
+
+        pub fn serialize<T: AsRef<OsStr>, S: Serializer>(
+            value: &Option<T>,
+            serializer: S,
+        ) -> ::std::result::Result<S::Ok, S::Error> {
+            value
+                .as_ref()
+                .map(|value| Item(value.as_ref().to_os_string()))
+                .serialize(serializer)
+        }
+
+        pub fn deserialize<'de, T: From<OsString>, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> ::std::result::Result<Option<T>, D::Error> {
+            let value = Option::<Item>::deserialize(deserializer)?;
+            Ok(value.map(|item| T::from(item.0)))
+        }
+    }
+
+    /// Lists of OS strings and paths.
+    pub mod vec {
+        use std::ffi::{OsStr, OsString};
+
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        use super::Item;
+
+        pub fn serialize<T: AsRef<OsStr>, S: Serializer>(
+            values: &[T],
+            serializer: S,
+        ) -> ::std::result::Result<S::Ok, S::Error> {
+            serializer.collect_seq(
+                values
+                    .iter()
+                    .map(|value| Item(value.as_ref().to_os_string())),
+            )
+        }
+
+        pub fn deserialize<'de, T: From<OsString>, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> ::std::result::Result<Vec<T>, D::Error> {
+            let values = Vec::<Item>::deserialize(deserializer)?;
+            Ok(values.into_iter().map(|item| T::from(item.0)).collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+    use service::{
+        ServiceAction, ServiceActionType, ServiceConfig, ServiceControl, ServiceDetail,
+        ServiceErrorControl, ServiceExitCode, ServiceFailureActions, ServiceFailureResetPeriod,
+        ServiceInfo, ServiceSetting, ServiceSidType, ServiceStartType, ServiceState,
+        ServiceStatus, ServiceStatusExt, ServiceType, SessionChangeParam, SessionChangeReason,
+        TimeChangeParam,
+    };
+    use service_trigger::{ServiceTrigger, ServiceTriggerAction};
+    use std::path::PathBuf;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn round_trip<T>(value: &T) -> T
+    where
+        T: Serialize + for<'de> Deserialize<'de>,
+    {
+        let json = serde_json::to_string(value).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    fn service_status() -> ServiceStatus {
+        ServiceStatus {
+            service_type: ServiceType::OwnProcess,
+            current_state: ServiceState::StartPending,
+            controls_accepted: ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+            exit_code: ServiceExitCode::ServiceSpecific(42),
+            checkpoint: 3,
+            wait_hint: Duration::from_millis(1500),
+        }
+    }
+
+    #[test]
+    fn test_service_status_format() {
+        assert_eq!(
+            serde_json::to_string(&Versioned::new(service_status())).unwrap(),
+            r#"{"schemaVersion":2,"data":{"serviceType":"OwnProcess","currentState":"StartPending","controlsAccepted":5,"exitCode":{"ServiceSpecific":42},"checkpoint":3,"waitHint":1500}}"#
+        );
+    }
+
+    #[test]
+    fn test_service_status_round_trip() {
+        let status = service_status();
+        assert_eq!(round_trip(&status), status);
+    }
+
+    #[test]
+    fn test_service_status_long_wait_hint() {
+        let status = ServiceStatus {
+            wait_hint: Duration::from_secs(u64::MAX),
+            ..service_status()
+        };
+        let json = serde_json::to_string(&status).unwrap();
+        assert!(json.ends_with(r#""waitHint":18446744073709551615}"#));
+    }
+
+    #[test]
+    fn test_service_info_round_trip() {
+        let service_info = ServiceInfo {
+            name: OsString::from("my_service"),
+            display_name: OsString::from("My service"),
+            service_type: ServiceType::OwnProcess,
+            start_type: ServiceStartType::AutoStart,
+            error_control: ServiceErrorControl::Severe,
+            executable_path: PathBuf::from(r"C:\Program Files\My service\service.exe"),
+            launch_arguments: vec![OsString::from("--verbose"), OsString::from("")],
+            load_order_group: None,
+            dependencies: vec![ServiceDependency::Service(OsString::from("Tcpip"))],
+            account_name: Some(OsString::from(r"NT AUTHORITY\NetworkService")),
+            account_password: None,
+            settings: vec![
+                ServiceSetting::Description(OsString::from("My service description")),
+                ServiceSetting::PreshutdownTimeout(Duration::from_secs(30)),
+                ServiceSetting::SidType(ServiceSidType::Restricted),
+                ServiceSetting::PreferredNode(None),
+            ],
+        };
+        assert_eq!(round_trip(&service_info), service_info);
+    }
+
+    #[test]
+    fn test_service_config_round_trip() {
+        let config = ServiceConfig {
+            service_type: ServiceType::KernelDriver,
+            start_type: ServiceStartType::BootStart,
+            error_control: ServiceErrorControl::Critical,
+            binary_path: OsString::from(r"\SystemRoot\System32\drivers\disk.sys"),
+            executable_path: PathBuf::from(r"\SystemRoot\System32\drivers\disk.sys"),
+            launch_arguments: vec![],
+            load_order_group: Some(OsString::from("SCSI Class")),
+            tag_id: 7,
+            dependencies: vec![ServiceDependency::Group(OsString::from("SCSI miniport"))],
+            account_name: None,
+            display_name: OsString::from("Disk Driver"),
+        };
+        assert_eq!(round_trip(&config), config);
+    }
+
+    #[test]
+    fn test_service_detail_round_trip() {
+        let details = Versioned::new(vec![ServiceDetail {
+            status: ServiceStatusExt {
+                status: service_status(),
+                process_id: 1234,
+                service_flags: 0,
+            },
+            name: OsString::from("my_service"),
+            display_name: OsString::from("My service"),
+            binary_path: Some(OsString::from(r"C:\service.exe")),
+            start_type: Some(ServiceStartType::OnDemand),
+            error_control: Some(ServiceErrorControl::Normal),
+            load_order_group: None,
+            tag_id: Some(0),
+            dependencies: Some(vec![
+                ServiceDependency::Service(OsString::from("Tcpip")),
+                ServiceDependency::Group(OsString::from("NetworkProvider")),
+            ]),
+            start_name: Some(OsString::from("LocalSystem")),
+            failure_actions: Some(ServiceFailureActions {
+                reset_period: ServiceFailureResetPeriod::After(Duration::from_secs(86400)),
+                reboot_msg: None,
+                command: Some(OsString::from(r"C:\notify.exe --failed")),
+                actions: vec![ServiceAction {
+                    action_type: ServiceActionType::Restart,
+                    delay: Duration::from_secs(5),
+                }],
+            }),
+        }]);
+        assert_eq!(round_trip(&details), details);
+    }
+
+    #[test]
+    fn test_service_trigger_round_trip() {
+        let usb_device: Guid = "a5dcbf10-6530-11d2-901f-00c04fb951ed".parse().unwrap();
+        let trigger = ServiceTrigger::device_interface_arrival(usb_device)
+            .string(r"USB\VID_1234&PID_5678")
+            .multi_string(vec!["a", "b"])
+            .binary(vec![0, 255])
+            .level(4)
+            .keyword_any(u64::MAX)
+            .action(ServiceTriggerAction::Stop);
+        let json = serde_json::to_string(&trigger).unwrap();
+        assert!(json.contains(r#""subtype":"a5dcbf10-6530-11d2-901f-00c04fb951ed""#));
+        assert_eq!(serde_json::from_str::<ServiceTrigger>(&json).unwrap(), trigger);
+
+        let setting = ServiceSetting::Triggers(vec![trigger]);
+        assert_eq!(round_trip(&setting), setting);
+        assert!(serde_json::from_str::<Guid>(r#""not a guid""#).is_err());
+    }
+
+    #[test]
+    fn test_service_control_format() {
+        let controls = vec![
+            ServiceControl::SessionChange(SessionChangeParam {
+                reason: SessionChangeReason::SessionLogon,
+                session_id: 2,
+            }),
+            ServiceControl::TimeChange(TimeChangeParam {
+                old_time: UNIX_EPOCH + Duration::from_secs(1500000000),
+                new_time: UNIX_EPOCH + Duration::from_secs(1500000060),
+            }),
+        ];
+        let json = serde_json::to_string(&controls).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"SessionChange":{"reason":"SessionLogon","sessionId":2}},{"TimeChange":{"oldTime":{"secs_since_epoch":1500000000,"nanos_since_epoch":0},"newTime":{"secs_since_epoch":1500000060,"nanos_since_epoch":0}}}]"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Vec<ServiceControl>>(&json).unwrap(),
+            controls
+        );
+    }
+
+    #[test]
+    fn test_service_dependency_format() {
+        let dependencies = vec![
+            ServiceDependency::Service(OsString::from("Tcpip")),
+            ServiceDependency::Group(OsString::from("NetworkProvider")),
+        ];
+        let json = serde_json::to_string(&dependencies).unwrap();
+        assert_eq!(json, r#"["Tcpip","+NetworkProvider"]"#);
+        assert_eq!(
+            serde_json::from_str::<Vec<ServiceDependency>>(&json).unwrap(),
+            dependencies
+        );
+    }
+
+    /// A service listed by `list_services` before the schema was versioned.
+    const VERSION_1_DETAIL: &str = r#"{"status":{"status":{"serviceType":"Win32ShareProcess",
+        "currentState":"Running","controlsAccepted":{"bits":5},"exitCode":{"Win32":0},
+        "checkpoint":0,"waitHint":{"secs":1,"nanos":500000000}},"processId":1204,
+        "serviceFlags":0},"name":"Dhcp","displayName":"DHCP Client",
+        "binaryPath":"C:\\Windows\\system32\\svchost.exe -k LocalServiceNetworkRestricted",
+        "startType":"AutoStart","errorControl":"Normal","loadOrderGroup":"TDI","tagId":0,
+        "dependencies":"NSI","startName":"NT Authority\\LocalService"}"#;
+
+    #[test]
+    fn test_version_1_service_detail() {
+        let details: Vec<ServiceDetail> =
+            serde_json::from_str(&format!("[{}]", VERSION_1_DETAIL)).unwrap();
+        let detail = &details[0];
+        assert_eq!(
+            detail.status.status,
+            ServiceStatus {
+                service_type: ServiceType::Win32ShareProcess,
+                current_state: ServiceState::Running,
+                controls_accepted: ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+                exit_code: ServiceExitCode::Win32(0),
+                checkpoint: 0,
+                wait_hint: Duration::from_millis(1500),
+            }
+        );
+        assert_eq!(detail.status.process_id, 1204);
+        assert_eq!(detail.name, OsString::from("Dhcp"));
+        assert_eq!(
+            detail.binary_path,
+            Some(OsString::from(
+                r"C:\Windows\system32\svchost.exe -k LocalServiceNetworkRestricted"
+            ))
+        );
+        assert_eq!(detail.start_type, Some(ServiceStartType::AutoStart));
+        assert_eq!(
+            detail.dependencies,
+            Some(vec![ServiceDependency::Service(OsString::from("NSI"))])
+        );
+        assert_eq!(detail.failure_actions, None);
+    }
+
+    #[test]
+    fn test_service_detail_legacy_dependencies() {
+        let detail = |dependencies: &str| {
+            let json = VERSION_1_DETAIL.replace(r#""NSI""#, dependencies);
+            serde_json::from_str::<ServiceDetail>(&json)
+                .unwrap()
+                .dependencies
+        };
+
+        assert_eq!(
+            detail(r#""+TDI""#),
+            Some(vec![ServiceDependency::Group(OsString::from("TDI"))])
+        );
+        assert_eq!(detail(r#""""#), Some(vec![]));
+        assert_eq!(detail("null"), None);
+    }
+
+    #[test]
+    fn test_flags_drop_unknown_bits() {
+        let flags: ServiceControlAccept = serde_json::from_str("32769").unwrap();
+        assert_eq!(flags, ServiceControlAccept::STOP);
+        let flags: ServiceControlAccept = serde_json::from_str(r#"{"bits":32769}"#).unwrap();
+        assert_eq!(flags, ServiceControlAccept::STOP);
+    }
+
+    #[test]
+    fn test_version_1_duration_overflow() {
+        let json = VERSION_1_DETAIL.replace(
+            r#"{"secs":1,"nanos":500000000}"#,
+            r#"{"secs":18446744073709551615,"nanos":1000000000}"#,
+        );
+        assert!(serde_json::from_str::<ServiceDetail>(&json).is_err());
+    }
+
+    #[test]
+    fn test_reject_newer_schema_version() {
+        let json = format!(r#"{{"schemaVersion":{},"data":null}}"#, SCHEMA_VERSION + 1);
+        assert!(serde_json::from_str::<Versioned<()>>(&json).is_err());
+    }
+
+    #[test]
+    fn test_os_string_from_code_units() {
+        let config: ServiceConfig = serde_json::from_str(
+            r#"{"serviceType":"OwnProcess","startType":"OnDemand","errorControl":"Normal",
+                "executablePath":[99,58,92,97,46,101,120,101],"launchArguments":["-v"],"loadOrderGroup":null,"tagId":0,
+                "dependencies":[],"accountName":"LocalSystem","displayName":"A"}"#,
+        ).unwrap();
+        assert_eq!(config.executable_path, PathBuf::from(r"c:\a.exe"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_os_string_unpaired_surrogate_round_trip() {
+        use std::os::windows::ffi::OsStringExt;
+
+        let name = OsString::from_wide(&[0x61, 0xD800, 0x62]);
+        let config = ServiceConfig {
+            service_type: ServiceType::OwnProcess,
+            start_type: ServiceStartType::OnDemand,
+            error_control: ServiceErrorControl::Normal,
+            binary_path: name.clone(),
+            executable_path: PathBuf::from(name.clone()),
+            launch_arguments: vec![name.clone()],
+            load_order_group: Some(name.clone()),
+            tag_id: 0,
+            dependencies: vec![ServiceDependency::Group(name.clone())],
+            account_name: Some(name.clone()),
+            display_name: name,
+        };
+        assert_eq!(round_trip(&config), config);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_service_detail_unpaired_surrogate_round_trip() {
+        use std::os::windows::ffi::OsStringExt;
+
+        let name = OsString::from_wide(&[0x61, 0xD800, 0x62]);
+        let detail = ServiceDetail {
+            status: ServiceStatusExt {
+                status: service_status(),
+                process_id: 0,
+                service_flags: 0,
+            },
+            name: name.clone(),
+            display_name: name.clone(),
+            binary_path: Some(name.clone()),
+            start_type: None,
+            error_control: None,
+            load_order_group: Some(name.clone()),
+            tag_id: None,
+            dependencies: None,
+            start_name: Some(name),
+            failure_actions: None,
+        };
+        assert_eq!(round_trip(&detail), detail);
+        assert_eq!(detail.name_lossy(), "a\u{FFFD}b");
+        assert_eq!(detail.display_name_lossy(), "a\u{FFFD}b");
+    }
+}
@@ -1,18 +1,73 @@
-use std::ffi::OsString;
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::mem;
+use std::os::raw::c_void;
 use std::path::PathBuf;
-use std::time::Duration;
-use std::{io, mem};
+use std::slice;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(windows)]
+use winapi::um::winsvc::{SERVICE_STATUS, SERVICE_STATUS_PROCESS};
+
+use widestring::{WideCString, WideString};
+
+#[cfg(feature = "async")]
+use asynchronous::ServiceStateFuture;
+use backend::ServiceBackend;
+use device_event::DeviceEvent;
+use guid::Guid;
+use schema;
+use service_manager::{is_driver, launch_command, replace_launch_arguments};
+use service_trigger::ServiceTrigger;
+#[cfg(windows)]
+use sys::winerror::ERROR_SERVICE_SPECIFIC_ERROR;
+use sys::winerror::NO_ERROR;
+use sys::{dbt, winbase, winnt, winsvc, winuser};
+use wait;
+use watch::{ServiceNotifyMask, ServiceWatch};
+use {ErrorKind, Result, ResultExt};
+
+/// A service or a load ordering group that must start before the service that depends on it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ServiceDependency {
+    /// Depend on the service with the given name.
+    Service(OsString),
+
+    /// Depend on at least one service of the given load ordering group.
+    Group(OsString),
+}
 
-use winapi::shared::winerror::{ERROR_SERVICE_SPECIFIC_ERROR, NO_ERROR};
-use winapi::um::{winnt, winsvc};
+impl ServiceDependency {
+    /// Parse the string the system uses to identify the dependency, where group names are
+    /// prefixed with `+`.
+    pub fn from_system_identifier<S: AsRef<OsStr>>(identifier: S) -> Self {
+        let identifier = WideString::from_str(&identifier);
+        match identifier.as_slice().split_first() {
+            Some((&first, group_name)) if first == winsvc::SC_GROUP_IDENTIFIER => {
+                ServiceDependency::Group(WideString::from_vec(group_name).to_os_string())
+            }
+            _ => ServiceDependency::Service(identifier.to_os_string()),
+        }
+    }
 
-use sc_handle::ScHandle;
-use {ErrorKind, Result};
-use winapi::um::winsvc::SERVICE_NO_CHANGE;
-use std::ptr;
+    /// Return the string the system uses to identify the dependency.
+    pub fn to_system_identifier(&self) -> OsString {
+        match *self {
+            ServiceDependency::Service(ref name) => name.clone(),
+            ServiceDependency::Group(ref name) => {
+                let mut identifier = WideString::new();
+                identifier.push_slice([winsvc::SC_GROUP_IDENTIFIER]);
+                identifier.push_str(name);
+                identifier.to_os_string()
+            }
+        }
+    }
+}
 
 /// Enum describing the types of Windows services.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[repr(u32)]
 pub enum ServiceType {
     /// Service that runs in its own process.
@@ -57,11 +112,13 @@ impl ServiceType {
 
 bitflags! {
     /// Flags describing the access permissions when working with services
-    #[derive(Serialize)]
     pub struct ServiceAccess: u32 {
         /// Can query the service status
         const QUERY_STATUS = winsvc::SERVICE_QUERY_STATUS;
 
+        /// Can query the service configuration
+        const QUERY_CONFIG = winsvc::SERVICE_QUERY_CONFIG;
+
         /// Can start the service
         const START = winsvc::SERVICE_START;
 
@@ -74,6 +131,12 @@ bitflags! {
         /// Can ask the service to report its status
         const INTERROGATE = winsvc::SERVICE_INTERROGATE;
 
+        /// Can list the services that depend on the service
+        const ENUMERATE_DEPENDENTS = winsvc::SERVICE_ENUMERATE_DEPENDENTS;
+
+        /// Can send user-defined control codes to the service
+        const USER_DEFINED_CONTROL = winsvc::SERVICE_USER_DEFINED_CONTROL;
+
         /// Can change config of the service
         const SERVICE_CHANGE_CONFIG = winsvc::SERVICE_CHANGE_CONFIG;
 
@@ -83,7 +146,7 @@ bitflags! {
 }
 
 /// Enum describing the start options for windows services.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[repr(u32)]
 pub enum ServiceStartType {
     /// Autostart on system startup
@@ -117,7 +180,7 @@ impl ServiceStartType {
 /// Error handling strategy for service failures.
 ///
 /// See <https://msdn.microsoft.com/en-us/library/windows/desktop/ms682450(v=vs.85).aspx>
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[repr(u32)]
 pub enum ServiceErrorControl {
     Critical = winnt::SERVICE_ERROR_CRITICAL,
@@ -144,13 +207,15 @@ impl ServiceErrorControl {
 }
 
 /// A struct that describes the service.
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ServiceInfo {
     /// Service name
+    #[serde(with = "schema::os_string")]
     pub name: OsString,
 
     /// User-friendly service name
+    #[serde(with = "schema::os_string")]
     pub display_name: OsString,
 
     /// The service type
@@ -163,67 +228,393 @@ pub struct ServiceInfo {
     pub error_control: ServiceErrorControl,
 
     /// Path to the service binary
+    #[serde(with = "schema::os_string")]
     pub executable_path: PathBuf,
 
     /// Launch arguments passed to `main` when system starts the service.
     /// This is not the same as arguments passed to `service_main`.
+    #[serde(with = "schema::os_string::vec")]
     pub launch_arguments: Vec<OsString>,
 
+    /// The load ordering group this service belongs to.
+    /// Use `None` if the service does not belong to a group.
+    #[serde(default, with = "schema::os_string::option")]
+    pub load_order_group: Option<OsString>,
+
+    /// Services or load ordering groups that must start before this service.
+    #[serde(default)]
+    pub dependencies: Vec<ServiceDependency>,
+
     /// Account to use for running the service.
     /// for example: NT Authority\System.
     /// use `None` to run as LocalSystem.
+    #[serde(with = "schema::os_string::option")]
     pub account_name: Option<OsString>,
 
     /// Account password.
     /// For system accounts this should normally be `None`.
+    #[serde(with = "schema::os_string::option")]
     pub account_password: Option<OsString>,
+
+    /// Optional settings applied right after the service is created. The service is deleted
+    /// again if any of them cannot be applied.
+    #[serde(default)]
+    pub settings: Vec<ServiceSetting>,
 }
 
 /// Enum describing the service control operations.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
-#[repr(u32)]
+///
+/// The controls sent by the system about system events carry the decoded event data, see
+/// [`ServiceControl::from_raw_event`]. Controls that cannot be decoded reach the event handler as
+/// [`ServiceControl::Unknown`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ServiceControl {
-    Continue = winsvc::SERVICE_CONTROL_CONTINUE,
-    Interrogate = winsvc::SERVICE_CONTROL_INTERROGATE,
-    NetBindAdd = winsvc::SERVICE_CONTROL_NETBINDADD,
-    NetBindDisable = winsvc::SERVICE_CONTROL_NETBINDDISABLE,
-    NetBindEnable = winsvc::SERVICE_CONTROL_NETBINDENABLE,
-    NetBindRemove = winsvc::SERVICE_CONTROL_NETBINDREMOVE,
-    ParamChange = winsvc::SERVICE_CONTROL_PARAMCHANGE,
-    Pause = winsvc::SERVICE_CONTROL_PAUSE,
-    Preshutdown = winsvc::SERVICE_CONTROL_PRESHUTDOWN,
-    Shutdown = winsvc::SERVICE_CONTROL_SHUTDOWN,
-    Stop = winsvc::SERVICE_CONTROL_STOP,
-    DeviceEvent = winsvc::SERVICE_CONTROL_DEVICEEVENT,
+    Continue,
+    Interrogate,
+    NetBindAdd,
+    NetBindDisable,
+    NetBindEnable,
+    NetBindRemove,
+    ParamChange,
+    Pause,
+    Preshutdown,
+    Shutdown,
+    Stop,
+    DeviceEvent(DeviceEvent),
+    HardwareProfileChange(HardwareProfileChangeParam),
+    PowerEvent(PowerEventParam),
+    SessionChange(SessionChangeParam),
+    TimeChange(TimeChangeParam),
+    TriggerEvent,
+    UserModeReboot,
+    LowResources,
+    SystemLowResources,
+    /// Control code defined by the service, in the range from 128 to 255.
+    UserDefined(u32),
+    /// A control the system sent that could not be decoded, for example an event added in a later
+    /// version of Windows. The event handler receives it along with the raw event data, and
+    /// decides how to answer it.
+    Unknown {
+        control: u32,
+        event_type: u32,
+    },
 }
 
 impl ServiceControl {
+    /// Parse a control code that carries no event data.
+    ///
+    /// Returns an error for the controls that carry event data, use
+    /// [`ServiceControl::from_raw_event`] to decode those.
     pub fn from_raw(raw_value: u32) -> Result<Self> {
         let service_control = match raw_value {
-            x if x == ServiceControl::Continue.to_raw() => ServiceControl::Continue,
-            x if x == ServiceControl::Interrogate.to_raw() => ServiceControl::Interrogate,
-            x if x == ServiceControl::NetBindAdd.to_raw() => ServiceControl::NetBindAdd,
-            x if x == ServiceControl::NetBindDisable.to_raw() => ServiceControl::NetBindDisable,
-            x if x == ServiceControl::NetBindEnable.to_raw() => ServiceControl::NetBindEnable,
-            x if x == ServiceControl::NetBindRemove.to_raw() => ServiceControl::NetBindRemove,
-            x if x == ServiceControl::ParamChange.to_raw() => ServiceControl::ParamChange,
-            x if x == ServiceControl::Pause.to_raw() => ServiceControl::Pause,
-            x if x == ServiceControl::Preshutdown.to_raw() => ServiceControl::Preshutdown,
-            x if x == ServiceControl::Shutdown.to_raw() => ServiceControl::Shutdown,
-            x if x == ServiceControl::Stop.to_raw() => ServiceControl::Stop,
-            x if x == ServiceControl::DeviceEvent.to_raw() => ServiceControl::DeviceEvent,
+            winsvc::SERVICE_CONTROL_CONTINUE => ServiceControl::Continue,
+            winsvc::SERVICE_CONTROL_INTERROGATE => ServiceControl::Interrogate,
+            winsvc::SERVICE_CONTROL_NETBINDADD => ServiceControl::NetBindAdd,
+            winsvc::SERVICE_CONTROL_NETBINDDISABLE => ServiceControl::NetBindDisable,
+            winsvc::SERVICE_CONTROL_NETBINDENABLE => ServiceControl::NetBindEnable,
+            winsvc::SERVICE_CONTROL_NETBINDREMOVE => ServiceControl::NetBindRemove,
+            winsvc::SERVICE_CONTROL_PARAMCHANGE => ServiceControl::ParamChange,
+            winsvc::SERVICE_CONTROL_PAUSE => ServiceControl::Pause,
+            winsvc::SERVICE_CONTROL_PRESHUTDOWN => ServiceControl::Preshutdown,
+            winsvc::SERVICE_CONTROL_SHUTDOWN => ServiceControl::Shutdown,
+            winsvc::SERVICE_CONTROL_STOP => ServiceControl::Stop,
+            winsvc::SERVICE_CONTROL_TRIGGEREVENT => ServiceControl::TriggerEvent,
+            winsvc::SERVICE_CONTROL_USERMODEREBOOT => ServiceControl::UserModeReboot,
+            winsvc::SERVICE_CONTROL_LOWRESOURCES => ServiceControl::LowResources,
+            winsvc::SERVICE_CONTROL_SYSTEMLOWRESOURCES => ServiceControl::SystemLowResources,
+            winsvc::SERVICE_CONTROL_DEVICEEVENT
+            | winsvc::SERVICE_CONTROL_HARDWAREPROFILECHANGE
+            | winsvc::SERVICE_CONTROL_POWEREVENT
+            | winsvc::SERVICE_CONTROL_SESSIONCHANGE
+            | winsvc::SERVICE_CONTROL_TIMECHANGE => {
+                Err(ErrorKind::MissingServiceControlEventData(raw_value))?
+            }
+            winsvc::SERVICE_USER_DEFINED_CONTROL_MIN..=winsvc::SERVICE_USER_DEFINED_CONTROL_MAX => {
+                ServiceControl::UserDefined(raw_value)
+            }
             other => Err(ErrorKind::InvalidServiceControl(other))?,
         };
         Ok(service_control)
     }
 
+    /// Decode a control along with the event type and data that the system passes to the
+    /// control handler.
+    ///
+    /// # Safety
+    ///
+    /// `event_data` must be null or point to the event data the system passed with `control` and
+    /// `event_type`.
+    pub unsafe fn from_raw_event(
+        raw_value: u32,
+        event_type: u32,
+        event_data: *const c_void,
+    ) -> Result<Self> {
+        let missing_data = || ErrorKind::MissingServiceControlEventData(raw_value);
+        let service_control = match raw_value {
+            winsvc::SERVICE_CONTROL_DEVICEEVENT => {
+                if event_data.is_null() {
+                    Err(missing_data())?;
+                }
+                ServiceControl::DeviceEvent(DeviceEvent::from_raw_event(event_type, event_data)?)
+            }
+            winsvc::SERVICE_CONTROL_HARDWAREPROFILECHANGE => ServiceControl::HardwareProfileChange(
+                HardwareProfileChangeParam::from_raw(event_type)?,
+            ),
+            winsvc::SERVICE_CONTROL_POWEREVENT => {
+                let param = if event_type == winuser::PBT_POWERSETTINGCHANGE {
+                    let setting = (event_data as *const RawPowerBroadcastSetting)
+                        .as_ref()
+                        .ok_or_else(missing_data)?;
+                    let data = (event_data as *const u8)
+                        .add(mem::size_of::<Guid>() + mem::size_of::<u32>());
+                    PowerEventParam::PowerSettingChange(PowerSetting {
+                        power_setting: setting.power_setting,
+                        data: slice::from_raw_parts(data, setting.data_length as usize).to_vec(),
+                    })
+                } else {
+                    PowerEventParam::from_raw(event_type)?
+                };
+                ServiceControl::PowerEvent(param)
+            }
+            winsvc::SERVICE_CONTROL_SESSIONCHANGE => {
+                let notification = (event_data as *const RawSessionNotification)
+                    .as_ref()
+                    .ok_or_else(missing_data)?;
+                ServiceControl::SessionChange(SessionChangeParam {
+                    reason: SessionChangeReason::from_raw(event_type)?,
+                    session_id: notification.session_id,
+                })
+            }
+            winsvc::SERVICE_CONTROL_TIMECHANGE => {
+                let info = (event_data as *const RawTimeChangeInfo)
+                    .as_ref()
+                    .ok_or_else(missing_data)?;
+                ServiceControl::TimeChange(TimeChangeParam {
+                    old_time: system_time_from_file_time(info.old_time)?,
+                    new_time: system_time_from_file_time(info.new_time)?,
+                })
+            }
+            other => ServiceControl::from_raw(other)?,
+        };
+        Ok(service_control)
+    }
+
+    pub fn to_raw(&self) -> u32 {
+        match *self {
+            ServiceControl::Continue => winsvc::SERVICE_CONTROL_CONTINUE,
+            ServiceControl::Interrogate => winsvc::SERVICE_CONTROL_INTERROGATE,
+            ServiceControl::NetBindAdd => winsvc::SERVICE_CONTROL_NETBINDADD,
+            ServiceControl::NetBindDisable => winsvc::SERVICE_CONTROL_NETBINDDISABLE,
+            ServiceControl::NetBindEnable => winsvc::SERVICE_CONTROL_NETBINDENABLE,
+            ServiceControl::NetBindRemove => winsvc::SERVICE_CONTROL_NETBINDREMOVE,
+            ServiceControl::ParamChange => winsvc::SERVICE_CONTROL_PARAMCHANGE,
+            ServiceControl::Pause => winsvc::SERVICE_CONTROL_PAUSE,
+            ServiceControl::Preshutdown => winsvc::SERVICE_CONTROL_PRESHUTDOWN,
+            ServiceControl::Shutdown => winsvc::SERVICE_CONTROL_SHUTDOWN,
+            ServiceControl::Stop => winsvc::SERVICE_CONTROL_STOP,
+            ServiceControl::DeviceEvent(_) => winsvc::SERVICE_CONTROL_DEVICEEVENT,
+            ServiceControl::HardwareProfileChange(_) => {
+                winsvc::SERVICE_CONTROL_HARDWAREPROFILECHANGE
+            }
+            ServiceControl::PowerEvent(_) => winsvc::SERVICE_CONTROL_POWEREVENT,
+            ServiceControl::SessionChange(_) => winsvc::SERVICE_CONTROL_SESSIONCHANGE,
+            ServiceControl::TimeChange(_) => winsvc::SERVICE_CONTROL_TIMECHANGE,
+            ServiceControl::TriggerEvent => winsvc::SERVICE_CONTROL_TRIGGEREVENT,
+            ServiceControl::UserModeReboot => winsvc::SERVICE_CONTROL_USERMODEREBOOT,
+            ServiceControl::LowResources => winsvc::SERVICE_CONTROL_LOWRESOURCES,
+            ServiceControl::SystemLowResources => winsvc::SERVICE_CONTROL_SYSTEMLOWRESOURCES,
+            ServiceControl::UserDefined(raw_value) => raw_value,
+            ServiceControl::Unknown { control, .. } => control,
+        }
+    }
+}
+
+/// Change of the hardware profile reported by [`ServiceControl::HardwareProfileChange`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum HardwareProfileChangeParam {
+    /// The system asks for permission to change the hardware profile.
+    QueryChangeConfig = dbt::DBT_QUERYCHANGECONFIG,
+    /// The hardware profile changed.
+    ConfigChanged = dbt::DBT_CONFIGCHANGED,
+    /// The change of the hardware profile was canceled.
+    ConfigChangeCanceled = dbt::DBT_CONFIGCHANGECANCELED,
+}
+
+impl HardwareProfileChangeParam {
+    pub fn from_raw(raw_value: u32) -> Result<Self> {
+        let param = match raw_value {
+            dbt::DBT_QUERYCHANGECONFIG => HardwareProfileChangeParam::QueryChangeConfig,
+            dbt::DBT_CONFIGCHANGED => HardwareProfileChangeParam::ConfigChanged,
+            dbt::DBT_CONFIGCHANGECANCELED => HardwareProfileChangeParam::ConfigChangeCanceled,
+            other => Err(ErrorKind::InvalidHardwareProfileChangeType(other))?,
+        };
+        Ok(param)
+    }
+
     pub fn to_raw(&self) -> u32 {
         *self as u32
     }
 }
 
+/// Power management event reported by [`ServiceControl::PowerEvent`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PowerEventParam {
+    /// The power status of the computer changed, for example it switched to battery power.
+    PowerStatusChange,
+    /// The computer woke up automatically to handle an event.
+    ResumeAutomatic,
+    /// The computer woke up after being suspended because of user activity.
+    ResumeSuspend,
+    /// The computer is about to enter a suspended state.
+    Suspend,
+    /// A power setting the service registered for changed.
+    PowerSettingChange(PowerSetting),
+}
+
+impl PowerEventParam {
+    /// Parse an event type that carries no event data.
+    pub fn from_raw(raw_value: u32) -> Result<Self> {
+        let param = match raw_value {
+            winuser::PBT_APMPOWERSTATUSCHANGE => PowerEventParam::PowerStatusChange,
+            winuser::PBT_APMRESUMEAUTOMATIC => PowerEventParam::ResumeAutomatic,
+            winuser::PBT_APMRESUMESUSPEND => PowerEventParam::ResumeSuspend,
+            winuser::PBT_APMSUSPEND => PowerEventParam::Suspend,
+            other => Err(ErrorKind::InvalidPowerEventType(other))?,
+        };
+        Ok(param)
+    }
+
+    pub fn to_raw(&self) -> u32 {
+        match *self {
+            PowerEventParam::PowerStatusChange => winuser::PBT_APMPOWERSTATUSCHANGE,
+            PowerEventParam::ResumeAutomatic => winuser::PBT_APMRESUMEAUTOMATIC,
+            PowerEventParam::ResumeSuspend => winuser::PBT_APMRESUMESUSPEND,
+            PowerEventParam::Suspend => winuser::PBT_APMSUSPEND,
+            PowerEventParam::PowerSettingChange(_) => winuser::PBT_POWERSETTINGCHANGE,
+        }
+    }
+}
+
+/// New value of a power setting, from the `POWERBROADCAST_SETTING` structure.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PowerSetting {
+    /// The GUID of the power setting.
+    pub power_setting: Guid,
+
+    /// The new value of the setting. The format depends on the setting.
+    pub data: Vec<u8>,
+}
+
+/// Reason of a [`ServiceControl::SessionChange`] event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum SessionChangeReason {
+    ConsoleConnect = winuser::WTS_CONSOLE_CONNECT,
+    ConsoleDisconnect = winuser::WTS_CONSOLE_DISCONNECT,
+    RemoteConnect = winuser::WTS_REMOTE_CONNECT,
+    RemoteDisconnect = winuser::WTS_REMOTE_DISCONNECT,
+    SessionLogon = winuser::WTS_SESSION_LOGON,
+    SessionLogoff = winuser::WTS_SESSION_LOGOFF,
+    SessionLock = winuser::WTS_SESSION_LOCK,
+    SessionUnlock = winuser::WTS_SESSION_UNLOCK,
+    SessionRemoteControl = winuser::WTS_SESSION_REMOTE_CONTROL,
+    SessionCreate = winuser::WTS_SESSION_CREATE,
+    SessionTerminate = winuser::WTS_SESSION_TERMINATE,
+}
+
+impl SessionChangeReason {
+    pub fn from_raw(raw_value: u32) -> Result<Self> {
+        let reason = match raw_value {
+            winuser::WTS_CONSOLE_CONNECT => SessionChangeReason::ConsoleConnect,
+            winuser::WTS_CONSOLE_DISCONNECT => SessionChangeReason::ConsoleDisconnect,
+            winuser::WTS_REMOTE_CONNECT => SessionChangeReason::RemoteConnect,
+            winuser::WTS_REMOTE_DISCONNECT => SessionChangeReason::RemoteDisconnect,
+            winuser::WTS_SESSION_LOGON => SessionChangeReason::SessionLogon,
+            winuser::WTS_SESSION_LOGOFF => SessionChangeReason::SessionLogoff,
+            winuser::WTS_SESSION_LOCK => SessionChangeReason::SessionLock,
+            winuser::WTS_SESSION_UNLOCK => SessionChangeReason::SessionUnlock,
+            winuser::WTS_SESSION_REMOTE_CONTROL => SessionChangeReason::SessionRemoteControl,
+            winuser::WTS_SESSION_CREATE => SessionChangeReason::SessionCreate,
+            winuser::WTS_SESSION_TERMINATE => SessionChangeReason::SessionTerminate,
+            other => Err(ErrorKind::InvalidSessionChangeReason(other))?,
+        };
+        Ok(reason)
+    }
+
+    pub fn to_raw(&self) -> u32 {
+        *self as u32
+    }
+}
+
+/// Session change reported by [`ServiceControl::SessionChange`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionChangeParam {
+    pub reason: SessionChangeReason,
+
+    /// The identifier of the session that changed.
+    pub session_id: u32,
+}
+
+/// Change of the system time reported by [`ServiceControl::TimeChange`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeChangeParam {
+    /// The system time before the change.
+    pub old_time: SystemTime,
+
+    /// The system time after the change.
+    pub new_time: SystemTime,
+}
+
+/// Layout of the `WTSSESSION_NOTIFICATION` structure.
+#[repr(C)]
+struct RawSessionNotification {
+    size: u32,
+    session_id: u32,
+}
+
+/// Layout of the `POWERBROADCAST_SETTING` structure, followed by `data_length` bytes of data.
+#[repr(C)]
+struct RawPowerBroadcastSetting {
+    power_setting: Guid,
+    data_length: u32,
+}
+
+/// Layout of the `SERVICE_TIMECHANGE_INFO` structure.
+#[repr(C)]
+struct RawTimeChangeInfo {
+    new_time: i64,
+    old_time: i64,
+}
+
+/// Number of 100 nanosecond intervals between the `FILETIME` epoch, January 1 1601, and the Unix
+/// epoch.
+const FILETIME_UNIX_EPOCH: i64 = 116_444_736_000_000_000;
+
+/// Convert a `FILETIME` value, in 100 nanosecond intervals since January 1 1601, to `SystemTime`.
+///
+/// Returns an error if the time is out of the range of `SystemTime` on this platform.
+fn system_time_from_file_time(file_time: i64) -> Result<SystemTime> {
+    let intervals = i128::from(file_time) - i128::from(FILETIME_UNIX_EPOCH);
+    let since_epoch = |intervals: i128| {
+        Duration::new(
+            (intervals / 10_000_000) as u64,
+            (intervals % 10_000_000) as u32 * 100,
+        )
+    };
+    let system_time = if intervals >= 0 {
+        UNIX_EPOCH.checked_add(since_epoch(intervals))
+    } else {
+        UNIX_EPOCH.checked_sub(since_epoch(-intervals))
+    };
+    system_time.ok_or_else(|| ErrorKind::InvalidFileTime(file_time).into())
+}
+
 /// Service state returned as a part of [`ServiceStatus`].
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[repr(u32)]
 pub enum ServiceState {
     Stopped = winsvc::SERVICE_STOPPED,
@@ -250,11 +641,367 @@ impl ServiceState {
         Ok(service_state)
     }
 
-    fn to_raw(&self) -> u32 {
+    pub fn to_raw(&self) -> u32 {
         *self as u32
     }
 }
 
+/// Filter for the services by their state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum ServiceStateFilter {
+    /// Services that are not stopped.
+    Active = winsvc::SERVICE_ACTIVE,
+    /// Services that are stopped.
+    Inactive = winsvc::SERVICE_INACTIVE,
+    /// Services in any state.
+    All = winsvc::SERVICE_STATE_ALL,
+}
+
+impl ServiceStateFilter {
+    /// Returns `true` if a service in the given state passes the filter.
+    pub fn matches(&self, state: ServiceState) -> bool {
+        match *self {
+            ServiceStateFilter::Active => state != ServiceState::Stopped,
+            ServiceStateFilter::Inactive => state == ServiceState::Stopped,
+            ServiceStateFilter::All => true,
+        }
+    }
+
+    pub fn to_raw(&self) -> u32 {
+        *self as u32
+    }
+}
+
+bitflags! {
+    /// Flags selecting the types of services to enumerate.
+    pub struct ServiceTypeFilter: u32 {
+        /// Kernel drivers.
+        const KERNEL_DRIVER = winnt::SERVICE_KERNEL_DRIVER;
+
+        /// File system drivers.
+        const FILE_SYSTEM_DRIVER = winnt::SERVICE_FILE_SYSTEM_DRIVER;
+
+        /// Kernel and file system drivers.
+        const DRIVER = winnt::SERVICE_DRIVER;
+
+        /// Services that run in their own process.
+        const WIN32_OWN_PROCESS = winnt::SERVICE_WIN32_OWN_PROCESS;
+
+        /// Services that share a process with other services.
+        const WIN32_SHARE_PROCESS = winnt::SERVICE_WIN32_SHARE_PROCESS;
+
+        /// Services that run in their own process or share a process.
+        const WIN32 = winnt::SERVICE_WIN32;
+    }
+}
+
+impl ServiceTypeFilter {
+    /// Returns `true` if a service of the given type passes the filter.
+    pub fn matches(&self, service_type: ServiceType) -> bool {
+        self.bits() & service_type.to_raw() != 0
+    }
+}
+
+/// Type of the security identifier the system adds to the process token of the service.
+///
+/// See <https://docs.microsoft.com/en-us/windows/win32/api/winsvc/ns-winsvc-service_sid_info>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum ServiceSidType {
+    /// The service has no security identifier.
+    None = winsvc::SERVICE_SID_TYPE_NONE,
+    /// The security identifier is added to the process token as a group.
+    Unrestricted = winsvc::SERVICE_SID_TYPE_UNRESTRICTED,
+    /// Like `Unrestricted`, but the process token is also restricted to the security identifier,
+    /// the world and the write-restricted identifiers.
+    Restricted = winsvc::SERVICE_SID_TYPE_RESTRICTED,
+}
+
+impl ServiceSidType {
+    pub fn from_raw(raw_value: u32) -> Result<Self> {
+        let sid_type = match raw_value {
+            x if x == ServiceSidType::None.to_raw() => ServiceSidType::None,
+            x if x == ServiceSidType::Unrestricted.to_raw() => ServiceSidType::Unrestricted,
+            x if x == ServiceSidType::Restricted.to_raw() => ServiceSidType::Restricted,
+            _ => Err(ErrorKind::InvalidServiceSidType(raw_value))?,
+        };
+        Ok(sid_type)
+    }
+
+    pub fn to_raw(&self) -> u32 {
+        *self as u32
+    }
+}
+
+/// Kinds of the optional service settings, see [`ServiceSetting`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum ServiceSettingKind {
+    Description = winsvc::SERVICE_CONFIG_DESCRIPTION,
+    FailureActions = winsvc::SERVICE_CONFIG_FAILURE_ACTIONS,
+    DelayedAutoStart = winsvc::SERVICE_CONFIG_DELAYED_AUTO_START_INFO,
+    PreshutdownTimeout = winsvc::SERVICE_CONFIG_PRESHUTDOWN_INFO,
+    RequiredPrivileges = winsvc::SERVICE_CONFIG_REQUIRED_PRIVILEGES_INFO,
+    SidType = winsvc::SERVICE_CONFIG_SERVICE_SID_INFO,
+    FailureActionsOnNonCrashFailures = winsvc::SERVICE_CONFIG_FAILURE_ACTIONS_FLAG,
+    PreferredNode = winsvc::SERVICE_CONFIG_PREFERRED_NODE,
+    Triggers = winsvc::SERVICE_CONFIG_TRIGGER_INFO,
+}
+
+impl ServiceSettingKind {
+    /// Returns the information level passed to `ChangeServiceConfig2` and
+    /// `QueryServiceConfig2` for the setting.
+    pub fn to_raw(&self) -> u32 {
+        *self as u32
+    }
+}
+
+/// An optional service setting.
+///
+/// These settings are stored apart from the main [`ServiceConfig`] and are changed one at a time
+/// with `ChangeServiceConfig2`.
+///
+/// See <https://docs.microsoft.com/en-us/windows/win32/api/winsvc/nf-winsvc-changeserviceconfig2w>
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ServiceSetting {
+    /// The description of the service. An empty description removes it.
+    Description(#[serde(with = "schema::os_string")] OsString),
+
+    /// Recovery actions taken when the service fails.
+    FailureActions(ServiceFailureActions),
+
+    /// Whether an auto-start service is started shortly after the other auto-start services
+    /// instead of during system boot. Has no effect on services with other start types.
+    DelayedAutoStart(bool),
+
+    /// How long the system waits for the service to handle the preshutdown control.
+    PreshutdownTimeout(#[serde(with = "schema::duration_millis")] Duration),
+
+    /// Privileges the service process needs, for example `SeChangeNotifyPrivilege`. The system
+    /// removes all other privileges from the process token.
+    RequiredPrivileges(#[serde(with = "schema::os_string::vec")] Vec<OsString>),
+
+    /// Type of the security identifier of the service.
+    SidType(ServiceSidType),
+
+    /// Whether the failure actions are also run when the service stops with an error exit
+    /// code, rather than only when its process terminates unexpectedly.
+    FailureActionsOnNonCrashFailures(bool),
+
+    /// The NUMA node the service process prefers to run on, `None` when there is no preference.
+    PreferredNode(Option<u16>),
+
+    /// Events that start or stop the service. An empty list removes all triggers.
+    Triggers(Vec<ServiceTrigger>),
+}
+
+impl ServiceSetting {
+    /// Returns the kind of the setting.
+    pub fn kind(&self) -> ServiceSettingKind {
+        match *self {
+            ServiceSetting::Description(_) => ServiceSettingKind::Description,
+            ServiceSetting::FailureActions(_) => ServiceSettingKind::FailureActions,
+            ServiceSetting::DelayedAutoStart(_) => ServiceSettingKind::DelayedAutoStart,
+            ServiceSetting::PreshutdownTimeout(_) => ServiceSettingKind::PreshutdownTimeout,
+            ServiceSetting::RequiredPrivileges(_) => ServiceSettingKind::RequiredPrivileges,
+            ServiceSetting::SidType(_) => ServiceSettingKind::SidType,
+            ServiceSetting::FailureActionsOnNonCrashFailures(_) => {
+                ServiceSettingKind::FailureActionsOnNonCrashFailures
+            }
+            ServiceSetting::PreferredNode(_) => ServiceSettingKind::PreferredNode,
+            ServiceSetting::Triggers(_) => ServiceSettingKind::Triggers,
+        }
+    }
+}
+
+/// Action the system takes when the service fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum ServiceActionType {
+    /// Do nothing.
+    None = winsvc::SC_ACTION_NONE,
+    /// Restart the service.
+    Restart = winsvc::SC_ACTION_RESTART,
+    /// Reboot the computer.
+    Reboot = winsvc::SC_ACTION_REBOOT,
+    /// Run the command of the [`ServiceFailureActions`].
+    RunCommand = winsvc::SC_ACTION_RUN_COMMAND,
+}
+
+impl ServiceActionType {
+    pub fn from_raw(raw_value: u32) -> Result<Self> {
+        let action_type = match raw_value {
+            x if x == ServiceActionType::None.to_raw() => ServiceActionType::None,
+            x if x == ServiceActionType::Restart.to_raw() => ServiceActionType::Restart,
+            x if x == ServiceActionType::Reboot.to_raw() => ServiceActionType::Reboot,
+            x if x == ServiceActionType::RunCommand.to_raw() => ServiceActionType::RunCommand,
+            _ => Err(ErrorKind::InvalidServiceActionType(raw_value))?,
+        };
+        Ok(action_type)
+    }
+
+    pub fn to_raw(&self) -> u32 {
+        *self as u32
+    }
+}
+
+/// An action taken on failure, after a delay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceAction {
+    /// The action to take.
+    pub action_type: ServiceActionType,
+
+    /// How long to wait before taking the action.
+    #[serde(with = "schema::duration_millis")]
+    pub delay: Duration,
+}
+
+/// Period without failures after which the failure count is reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ServiceFailureResetPeriod {
+    /// The failure count is never reset.
+    Never,
+    /// The failure count is reset after the service has not failed for this long. The period is
+    /// stored in whole seconds.
+    After(#[serde(with = "schema::duration_millis")] Duration),
+}
+
+/// Recovery actions the system takes when the service fails.
+///
+/// The first failure triggers the first action, the second failure the second action and so on.
+/// The last action is repeated for any further failures.
+///
+/// See <https://docs.microsoft.com/en-us/windows/win32/api/winsvc/ns-winsvc-service_failure_actionsw>
+///
+/// # Example
+///
+/// ```rust
+/// use std::time::Duration;
+/// use windows_service::service::{
+///     ServiceAction, ServiceActionType, ServiceFailureActions, ServiceFailureResetPeriod,
+/// };
+///
+/// let restart = ServiceAction {
+///     action_type: ServiceActionType::Restart,
+///     delay: Duration::from_secs(5),
+/// };
+/// let failure_actions = ServiceFailureActions {
+///     reset_period: ServiceFailureResetPeriod::After(Duration::from_secs(24 * 60 * 60)),
+///     reboot_msg: None,
+///     command: None,
+///     actions: vec![restart, restart],
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceFailureActions {
+    /// Period without failures after which the failure count is reset.
+    pub reset_period: ServiceFailureResetPeriod,
+
+    /// Message broadcast to the users of the server before rebooting, `None` for the default
+    /// message.
+    #[serde(with = "schema::os_string::option")]
+    pub reboot_msg: Option<OsString>,
+
+    /// Command line run by [`ServiceActionType::RunCommand`] actions.
+    #[serde(with = "schema::os_string::option")]
+    pub command: Option<OsString>,
+
+    /// Actions taken on consecutive failures.
+    pub actions: Vec<ServiceAction>,
+}
+
+/// The values of `SERVICE_FAILURE_ACTIONSW`, with the data it points to held in owned buffers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RawFailureActions {
+    /// The reset period in seconds, or `INFINITE`.
+    pub reset_period: u32,
+    /// The reboot message, empty for none.
+    pub reboot_msg: WideCString,
+    /// The command line, empty for none.
+    pub command: WideCString,
+    pub actions: Vec<RawServiceAction>,
+}
+
+/// Mirrors the layout of `SC_ACTION`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub(crate) struct RawServiceAction {
+    pub action_type: u32,
+    /// The delay in milliseconds.
+    pub delay: u32,
+}
+
+impl ServiceFailureActions {
+    pub(crate) fn to_raw(&self) -> Result<RawFailureActions> {
+        let reset_period = match self.reset_period {
+            ServiceFailureResetPeriod::Never => winbase::INFINITE,
+            // Clamp below `INFINITE` to keep the meaning of long periods.
+            ServiceFailureResetPeriod::After(period) => {
+                period.as_secs().min(u64::from(winbase::INFINITE - 1)) as u32
+            }
+        };
+        let reboot_msg = WideCString::from_str(self.reboot_msg.clone().unwrap_or_default())
+            .chain_err(|| ErrorKind::InvalidRebootMessage)?;
+        let command = WideCString::from_str(self.command.clone().unwrap_or_default())
+            .chain_err(|| ErrorKind::InvalidFailureCommand)?;
+        let actions = self
+            .actions
+            .iter()
+            .map(|action| RawServiceAction {
+                action_type: action.action_type.to_raw(),
+                delay: duration_millis(action.delay),
+            })
+            .collect();
+        Ok(RawFailureActions {
+            reset_period,
+            reboot_msg,
+            command,
+            actions,
+        })
+    }
+
+    #[cfg(any(windows, test))]
+    pub(crate) fn from_raw(raw: &RawFailureActions) -> Result<Self> {
+        let reset_period = match raw.reset_period {
+            winbase::INFINITE => ServiceFailureResetPeriod::Never,
+            seconds => ServiceFailureResetPeriod::After(Duration::from_secs(u64::from(seconds))),
+        };
+        let optional = |string: &WideCString| {
+            if string.is_empty() {
+                None
+            } else {
+                Some(string.to_os_string())
+            }
+        };
+        let actions = raw
+            .actions
+            .iter()
+            .map(|action| {
+                Ok(ServiceAction {
+                    action_type: ServiceActionType::from_raw(action.action_type)?,
+                    delay: Duration::from_millis(u64::from(action.delay)),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ServiceFailureActions {
+            reset_period,
+            reboot_msg: optional(&raw.reboot_msg),
+            command: optional(&raw.command),
+            actions,
+        })
+    }
+
+    /// Returns `true` if any of the actions restarts the service.
+    pub(crate) fn restarts_service(&self) -> bool {
+        self.actions
+            .iter()
+            .any(|action| action.action_type == ServiceActionType::Restart)
+    }
+}
+
 /// Service exit code abstraction.
 ///
 /// This struct provides a logic around the relationship between [`dwWin32ExitCode`] and
@@ -267,16 +1014,17 @@ impl ServiceState {
 /// Refer to the corresponding MSDN article for more info:\
 /// <https://msdn.microsoft.com/en-us/library/windows/desktop/ms685996(v=vs.85).aspx>
 ///
-/// [`dwWin32ExitCode`]: winsvc::SERVICE_STATUS::dwWin32ExitCode
-/// [`dwServiceSpecificExitCode`]: winsvc::SERVICE_STATUS::dwServiceSpecificExitCode
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+/// [`dwWin32ExitCode`]: SERVICE_STATUS::dwWin32ExitCode
+/// [`dwServiceSpecificExitCode`]: SERVICE_STATUS::dwServiceSpecificExitCode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ServiceExitCode {
     Win32(u32),
     ServiceSpecific(u32),
 }
 
 impl ServiceExitCode {
-    fn copy_to(&self, raw_service_status: &mut winsvc::SERVICE_STATUS) {
+    #[cfg(windows)]
+    fn copy_to(&self, raw_service_status: &mut SERVICE_STATUS) {
         match *self {
             ServiceExitCode::Win32(win32_error_code) => {
                 raw_service_status.dwWin32ExitCode = win32_error_code;
@@ -296,8 +1044,9 @@ impl Default for ServiceExitCode {
     }
 }
 
-impl<'a> From<&'a winsvc::SERVICE_STATUS> for ServiceExitCode {
-    fn from(service_status: &'a winsvc::SERVICE_STATUS) -> Self {
+#[cfg(windows)]
+impl<'a> From<&'a SERVICE_STATUS> for ServiceExitCode {
+    fn from(service_status: &'a SERVICE_STATUS) -> Self {
         if service_status.dwWin32ExitCode == ERROR_SERVICE_SPECIFIC_ERROR {
             ServiceExitCode::ServiceSpecific(service_status.dwServiceSpecificExitCode)
         } else {
@@ -306,8 +1055,9 @@ impl<'a> From<&'a winsvc::SERVICE_STATUS> for ServiceExitCode {
     }
 }
 
-impl<'a> From<&'a winsvc::SERVICE_STATUS_PROCESS> for ServiceExitCode {
-    fn from(service_status: &'a winsvc::SERVICE_STATUS_PROCESS) -> Self {
+#[cfg(windows)]
+impl<'a> From<&'a SERVICE_STATUS_PROCESS> for ServiceExitCode {
+    fn from(service_status: &'a SERVICE_STATUS_PROCESS) -> Self {
         if service_status.dwWin32ExitCode == ERROR_SERVICE_SPECIFIC_ERROR {
             ServiceExitCode::ServiceSpecific(service_status.dwServiceSpecificExitCode)
         } else {
@@ -318,8 +1068,15 @@ impl<'a> From<&'a winsvc::SERVICE_STATUS_PROCESS> for ServiceExitCode {
 
 bitflags! {
     /// Flags describing accepted types of service control events.
-    #[derive(Serialize)]
     pub struct ServiceControlAccept: u32 {
+        /// The service is notified when the computer's hardware profile changes. This allows the
+        /// service to receive `ServiceControl::HardwareProfileChange` events.
+        const HARDWARE_PROFILE_CHANGE = winsvc::SERVICE_ACCEPT_HARDWAREPROFILECHANGE;
+
+        /// The service is notified when the system is low on memory, see
+        /// `ServiceControl::LowResources`.
+        const LOW_RESOURCES = winsvc::SERVICE_ACCEPT_LOWRESOURCES;
+
         /// The service is a network component that can accept changes in its binding without being
         /// stopped and restarted. This allows service to receive `ServiceControl::Netbind*`
         /// family of events.
@@ -331,19 +1088,43 @@ bitflags! {
         /// The service can be paused and continued.
         const PAUSE_CONTINUE = winsvc::SERVICE_ACCEPT_PAUSE_CONTINUE;
 
+        /// The service is notified when the computer's power status changes. This allows the
+        /// service to receive `ServiceControl::PowerEvent` events.
+        const POWER_EVENT = winsvc::SERVICE_ACCEPT_POWEREVENT;
+
         /// The service can perform preshutdown tasks.
         /// Mutually exclusive with shutdown.
         const PRESHUTDOWN = winsvc::SERVICE_ACCEPT_PRESHUTDOWN;
 
+        /// The service is notified when the computer's session status changes. This allows the
+        /// service to receive `ServiceControl::SessionChange` events.
+        const SESSION_CHANGE = winsvc::SERVICE_ACCEPT_SESSIONCHANGE;
+
         /// The service is notified when system shutdown occurs.
         /// Mutually exclusive with preshutdown.
         const SHUTDOWN = winsvc::SERVICE_ACCEPT_SHUTDOWN;
 
         /// The service can be stopped.
         const STOP = winsvc::SERVICE_ACCEPT_STOP;
+
+        /// The service is notified when the system is low on resources, see
+        /// `ServiceControl::SystemLowResources`.
+        const SYSTEM_LOW_RESOURCES = winsvc::SERVICE_ACCEPT_SYSTEMLOWRESOURCES;
+
+        /// The service is notified when the system time changes. This allows the service to
+        /// receive `ServiceControl::TimeChange` events.
+        const TIME_CHANGE = winsvc::SERVICE_ACCEPT_TIMECHANGE;
+
+        /// The service is notified when an event it registered a trigger for occurs, see
+        /// `ServiceControl::TriggerEvent`.
+        const TRIGGER_EVENT = winsvc::SERVICE_ACCEPT_TRIGGEREVENT;
+
+        /// The service is notified when the computer restarts without restarting the kernel,
+        /// see `ServiceControl::UserModeReboot`.
+        const USER_MODE_REBOOT = winsvc::SERVICE_ACCEPT_USERMODEREBOOT;
     }
 }
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ServiceStatusExt {
     pub status: ServiceStatus,
@@ -354,7 +1135,8 @@ pub struct ServiceStatusExt {
 }
 
 impl ServiceStatusExt {
-    pub fn from_raw(raw_status: winsvc::SERVICE_STATUS_PROCESS) -> Result<Self> {
+    #[cfg(windows)]
+    pub fn from_raw(raw_status: SERVICE_STATUS_PROCESS) -> Result<Self> {
         Ok(ServiceStatusExt {
             service_flags: raw_status.dwServiceFlags,
             process_id: raw_status.dwProcessId,
@@ -371,8 +1153,8 @@ impl ServiceStatusExt {
 /// particular how to fill in the `exit_code`, `checkpoint`, `wait_hint` fields:\
 /// <https://msdn.microsoft.com/en-us/library/windows/desktop/ms685996(v=vs.85).aspx>
 ///
-/// [`SERVICE_STATUS`]: winsvc::SERVICE_STATUS
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+/// [`SERVICE_STATUS`]: SERVICE_STATUS
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ServiceStatus {
     /// Type of service.
@@ -399,12 +1181,14 @@ pub struct ServiceStatus {
     /// This basically works as a timeout until the system assumes that the service hung.
     /// This could be either circumvented by updating the [`ServiceStatus::current_state`] or
     /// incrementing a [`ServiceStatus::checkpoint`] value.
-    pub wait_hint: Duration
+    #[serde(with = "schema::duration_millis")]
+    pub wait_hint: Duration,
 }
 
 impl ServiceStatus {
-    pub(crate) fn to_raw(&self) -> winsvc::SERVICE_STATUS {
-        let mut raw_status = unsafe { mem::zeroed::<winsvc::SERVICE_STATUS>() };
+    #[cfg(windows)]
+    pub(crate) fn to_raw(&self) -> SERVICE_STATUS {
+        let mut raw_status = unsafe { mem::zeroed::<SERVICE_STATUS>() };
         raw_status.dwServiceType = self.service_type.to_raw();
         raw_status.dwCurrentState = self.current_state.to_raw();
         raw_status.dwControlsAccepted = self.controls_accepted.bits();
@@ -413,13 +1197,13 @@ impl ServiceStatus {
 
         raw_status.dwCheckPoint = self.checkpoint;
 
-        // we lose precision here but dwWaitHint should never be too big.
-        raw_status.dwWaitHint = (self.wait_hint.as_secs() * 1000) as u32;
+        raw_status.dwWaitHint = duration_millis(self.wait_hint);
 
         raw_status
     }
 
-    pub fn from_raw(raw_status: winsvc::SERVICE_STATUS) -> Result<Self> {
+    #[cfg(windows)]
+    pub fn from_raw(raw_status: SERVICE_STATUS) -> Result<Self> {
         Ok(ServiceStatus {
             service_type: ServiceType::from_raw(raw_status.dwServiceType)?,
             current_state: ServiceState::from_raw(raw_status.dwCurrentState)?,
@@ -432,7 +1216,8 @@ impl ServiceStatus {
         })
     }
 
-    pub fn from_raw_ex(raw_status: winsvc::SERVICE_STATUS_PROCESS) -> Result<Self> {
+    #[cfg(windows)]
+    pub fn from_raw_ex(raw_status: SERVICE_STATUS_PROCESS) -> Result<Self> {
         Ok(ServiceStatus {
             service_type: ServiceType::from_raw(raw_status.dwServiceType)?,
             current_state: ServiceState::from_raw(raw_status.dwCurrentState)?,
@@ -448,18 +1233,335 @@ impl ServiceStatus {
 }
 
 
+/// Service configuration as stored in the system services registry.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceConfig {
+    /// The service type
+    pub service_type: ServiceType,
+
+    /// The service startup options
+    pub start_type: ServiceStartType,
+
+    /// The severity of the error, and action taken, if this service fails to start.
+    pub error_control: ServiceErrorControl,
+
+    /// Command line used to launch the service binary, including the launch arguments, exactly as
+    /// stored by the system.
+    #[serde(default, with = "schema::os_string")]
+    pub binary_path: OsString,
+
+    /// Path to the service binary, parsed from [`binary_path`](Self::binary_path).
+    #[serde(with = "schema::os_string")]
+    pub executable_path: PathBuf,
+
+    /// Launch arguments passed to `main` when system starts the service, parsed from
+    /// [`binary_path`](Self::binary_path).
+    #[serde(with = "schema::os_string::vec")]
+    pub launch_arguments: Vec<OsString>,
+
+    /// The load ordering group this service belongs to.
+    #[serde(with = "schema::os_string::option")]
+    pub load_order_group: Option<OsString>,
+
+    /// Unique tag of the service within its load ordering group, zero when not assigned.
+    pub tag_id: u32,
+
+    /// Services or load ordering groups that must start before this service.
+    pub dependencies: Vec<ServiceDependency>,
+
+    /// Account the service runs under, `None` for drivers that did not specify it.
+    #[serde(with = "schema::os_string::option")]
+    pub account_name: Option<OsString>,
+
+    /// User-friendly service name
+    #[serde(with = "schema::os_string")]
+    pub display_name: OsString,
+}
+
+/// The name and status of a service.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceEntry {
+    /// Service name
+    #[serde(with = "schema::os_string")]
+    pub name: OsString,
+
+    /// User-friendly service name
+    #[serde(with = "schema::os_string")]
+    pub display_name: OsString,
+
+    /// The service status
+    pub status: ServiceStatus,
+}
+
+/// The name and extended status of a service, as reported by
+/// [`ServiceManager::enumerate`].
+///
+/// [`ServiceManager::enumerate`]: super::service_manager::ServiceManager::enumerate
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceEntryExt {
+    /// Service name
+    #[serde(with = "schema::os_string")]
+    pub name: OsString,
+
+    /// User-friendly service name
+    #[serde(with = "schema::os_string")]
+    pub display_name: OsString,
+
+    /// The service status, along with the process the service runs in
+    pub status: ServiceStatusExt,
+}
+
+/// The services stopped by [`Service::stop_with_dependents`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StopReport {
+    /// The dependent services in the order they were stopped, along with their status before
+    /// they were stopped.
+    pub dependents: Vec<ServiceEntry>,
+
+    /// The status of the service after it stopped.
+    pub status: ServiceStatus,
+}
+
+impl StopReport {
+    /// Returns the stopped dependents in the order to start them again, after starting the
+    /// service itself.
+    pub fn restart_order(&self) -> impl Iterator<Item = &ServiceEntry> {
+        self.dependents.iter().rev()
+    }
+}
+
+/// A set of changes to the configuration of an existing service.
+///
+/// Only the fields that are set are applied, the rest of the configuration is left unchanged.
+///
+/// # Example
+///
+/// ```rust
+/// use windows_service::service::{ServiceConfigChange, ServiceStartType};
+///
+/// let change = ServiceConfigChange::new()
+///     .start_type(ServiceStartType::AutoStart)
+///     .executable_path(r"C:\Program Files\Agent\agent.exe")
+///     .launch_arguments(vec!["--service"]);
+/// assert_eq!(change.display_name, None);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceConfigChange {
+    /// The new service type
+    pub service_type: Option<ServiceType>,
+
+    /// The new service startup options
+    pub start_type: Option<ServiceStartType>,
+
+    /// The new severity of the error, and action taken, if this service fails to start.
+    pub error_control: Option<ServiceErrorControl>,
+
+    /// The new path to the service binary
+    pub executable_path: Option<PathBuf>,
+
+    /// The new launch arguments passed to `main` when system starts the service.
+    pub launch_arguments: Option<Vec<OsString>>,
+
+    /// The new services or load ordering groups that must start before this service.
+    /// Use an empty list to remove all dependencies.
+    pub dependencies: Option<Vec<ServiceDependency>>,
+
+    /// The new account to use for running the service.
+    pub account_name: Option<OsString>,
+
+    /// The new account password.
+    pub account_password: Option<OsString>,
+
+    /// The new user-friendly service name
+    pub display_name: Option<OsString>,
+}
+
+impl ServiceConfigChange {
+    /// Create a change set that does not change anything.
+    pub fn new() -> Self {
+        ServiceConfigChange::default()
+    }
+
+    /// Change the service type.
+    pub fn service_type(mut self, service_type: ServiceType) -> Self {
+        self.service_type = Some(service_type);
+        self
+    }
+
+    /// Change the service startup options.
+    pub fn start_type(mut self, start_type: ServiceStartType) -> Self {
+        self.start_type = Some(start_type);
+        self
+    }
+
+    /// Change the error control of the service.
+    pub fn error_control(mut self, error_control: ServiceErrorControl) -> Self {
+        self.error_control = Some(error_control);
+        self
+    }
+
+    /// Change the path to the service binary.
+    pub fn executable_path<P: Into<PathBuf>>(mut self, executable_path: P) -> Self {
+        self.executable_path = Some(executable_path.into());
+        self
+    }
+
+    /// Change the launch arguments of the service.
+    ///
+    /// Unless the executable path is changed as well, it is kept exactly as written in the current
+    /// command line. It is only written again when the service type changes between a driver
+    /// and a service running in a process, which write the executable path differently.
+    pub fn launch_arguments<I, A>(mut self, launch_arguments: I) -> Self
+    where
+        I: IntoIterator<Item = A>,
+        A: Into<OsString>,
+    {
+        self.launch_arguments = Some(launch_arguments.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Change the services or load ordering groups that must start before this service.
+    pub fn dependencies<I>(mut self, dependencies: I) -> Self
+    where
+        I: IntoIterator<Item = ServiceDependency>,
+    {
+        self.dependencies = Some(dependencies.into_iter().collect());
+        self
+    }
+
+    /// Change the account used for running the service.
+    pub fn account_name<T: Into<OsString>>(mut self, account_name: T) -> Self {
+        self.account_name = Some(account_name.into());
+        self
+    }
+
+    /// Change the account password.
+    pub fn account_password<T: Into<OsString>>(mut self, account_password: T) -> Self {
+        self.account_password = Some(account_password.into());
+        self
+    }
+
+    /// Change the user-friendly service name.
+    pub fn display_name<T: Into<OsString>>(mut self, display_name: T) -> Self {
+        self.display_name = Some(display_name.into());
+        self
+    }
+
+    /// Build the new command line when the executable path or launch arguments are changed.
+    ///
+    /// The system stores both as a single command line, so the parts that are not changed are
+    /// taken from the current configuration, which is only queried when needed.
+    pub(crate) fn launch_command<F>(&self, current_config: F) -> Result<Option<WideCString>>
+    where
+        F: FnOnce() -> Result<ServiceConfig>,
+    {
+        match (
+            self.service_type,
+            self.executable_path.as_ref(),
+            self.launch_arguments.as_ref(),
+        ) {
+            (_, None, None) => Ok(None),
+            (Some(service_type), Some(executable_path), Some(launch_arguments)) => {
+                launch_command(service_type, executable_path, launch_arguments).map(Some)
+            }
+            (service_type, None, Some(launch_arguments)) => {
+                let current_config = current_config()?;
+                let service_type = service_type.unwrap_or(current_config.service_type);
+                if is_driver(service_type) == is_driver(current_config.service_type) {
+                    // Keep the executable exactly as written in the current command line, rather
+                    // than escaping it again after it was parsed.
+                    replace_launch_arguments(
+                        service_type,
+                        &current_config.binary_path,
+                        launch_arguments,
+                    ).map(Some)
+                } else {
+                    // Drivers write the executable without escaping, so it has to be written
+                    // again for the new service type.
+                    launch_command(
+                        service_type,
+                        &current_config.executable_path,
+                        launch_arguments,
+                    ).map(Some)
+                }
+            }
+            (service_type, Some(executable_path), launch_arguments) => {
+                let current_config = current_config()?;
+                launch_command(
+                    service_type.unwrap_or(current_config.service_type),
+                    executable_path,
+                    launch_arguments.unwrap_or(&current_config.launch_arguments),
+                ).map(Some)
+            }
+        }
+    }
+}
+
+/// Query a setting of the given kind and unwrap its value.
+macro_rules! query_setting {
+    ($service:expr, $kind:ident) => {
+        match $service.query_setting(ServiceSettingKind::$kind)? {
+            ServiceSetting::$kind(value) => Ok(value),
+            other => Err(ErrorKind::UnexpectedServiceSetting(
+                ServiceSettingKind::$kind,
+                other.kind(),
+            ).into()),
+        }
+    };
+}
+
 /// A struct that represents a system service.
 ///
 /// The instances of the [`Service`] can be obtained via [`ServiceManager`].
 ///
 /// [`ServiceManager`]: super::service_manager::ServiceManager
 pub struct Service {
-    service_handle: ScHandle,
+    backend: Arc<dyn ServiceBackend>,
 }
 
 impl Service {
-    pub(crate) fn new(service_handle: ScHandle) -> Self {
-        Service { service_handle }
+    pub(crate) fn new(backend: Box<dyn ServiceBackend>) -> Self {
+        Service {
+            backend: Arc::from(backend),
+        }
+    }
+
+    /// Returns the backend, to operate on the service from the thread of an async future.
+    #[cfg(feature = "async")]
+    pub(crate) fn shared_backend(&self) -> Arc<dyn ServiceBackend> {
+        self.backend.clone()
+    }
+
+    /// Start the service.
+    ///
+    /// The arguments are passed to the `service_main` of the service, following the service name
+    /// that the system always passes as the first argument.
+    ///
+    /// Requires the [`ServiceAccess::START`] permission.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use windows_service::service::ServiceAccess;
+    /// use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+    ///
+    /// # #[cfg(windows)]
+    /// # fn main() -> windows_service::Result<()> {
+    /// let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    /// let my_service = manager.open_service("my_service", ServiceAccess::START)?;
+    /// my_service.start(&["--verbose"])?;
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(windows))]
+    /// # fn main() {}
+    /// ```
+    pub fn start<S: AsRef<OsStr>>(&self, service_arguments: &[S]) -> Result<()> {
+        let service_arguments: Vec<&OsStr> =
+            service_arguments.iter().map(AsRef::as_ref).collect();
+        self.backend.start(&service_arguments)
     }
 
     /// Stop the service.
@@ -467,120 +1569,893 @@ impl Service {
         self.send_control_command(ServiceControl::Stop)
     }
 
-    /// Get the service status from the system.
-    pub fn query_status(&self) -> Result<ServiceStatus> {
-        let mut raw_status = unsafe { mem::zeroed::<winsvc::SERVICE_STATUS>() };
-        let success = unsafe {
-            winsvc::QueryServiceStatus(self.service_handle.raw_handle(), &mut raw_status)
-        };
-        if success == 1 {
-            ServiceStatus::from_raw(raw_status)
-        } else {
-            Err(io::Error::last_os_error().into())
+    /// Stop the service after stopping all services that depend on it.
+    ///
+    /// The dependents are stopped one at a time in the order given by [`Service::dependents`],
+    /// waiting up to `timeout` for each of them and for the service itself to stop, as in
+    /// [`Service::wait_for_state`]. If stopping any of them fails, the services that were stopped
+    /// until then remain stopped.
+    ///
+    /// Requires the [`ServiceAccess::ENUMERATE_DEPENDENTS`], [`ServiceAccess::STOP`] and
+    /// [`ServiceAccess::QUERY_STATUS`] permissions on the service, and the latter two on the
+    /// dependents.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    /// use windows_service::service::ServiceAccess;
+    /// use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+    ///
+    /// # #[cfg(windows)]
+    /// # fn main() -> windows_service::Result<()> {
+    /// let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    /// let service_access =
+    ///     ServiceAccess::ENUMERATE_DEPENDENTS | ServiceAccess::STOP | ServiceAccess::QUERY_STATUS;
+    /// let my_service = manager.open_service("my_service", service_access)?;
+    /// let report = my_service.stop_with_dependents(Duration::from_secs(30))?;
+    ///
+    /// // Upgrade the service...
+    ///
+    /// let my_service = manager.open_service("my_service", ServiceAccess::START)?;
+    /// my_service.start::<&str>(&[])?;
+    /// for dependent in report.restart_order() {
+    ///     manager
+    ///         .open_service(&dependent.name, ServiceAccess::START)?
+    ///         .start::<&str>(&[])?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(windows))]
+    /// # fn main() {}
+    /// ```
+    pub fn stop_with_dependents(&self, timeout: Duration) -> Result<StopReport> {
+        let mut stopped_dependents = Vec::new();
+        for dependent in self.dependents(ServiceStateFilter::Active)? {
+            let service = Service::new(self.backend.open_service(
+                &dependent.name,
+                ServiceAccess::STOP | ServiceAccess::QUERY_STATUS,
+            )?);
+            service.stop_and_wait(timeout)?;
+            stopped_dependents.push(dependent);
         }
+        let status = self.stop_and_wait(timeout)?;
+
+        Ok(StopReport {
+            dependents: stopped_dependents,
+            status,
+        })
     }
 
-    /// Delete the service from system registry.
-    pub fn delete(self) -> io::Result<()> {
-        let success = unsafe { winsvc::DeleteService(self.service_handle.raw_handle()) };
-        if success == 1 {
-            Ok(())
-        } else {
-            Err(io::Error::last_os_error())
-        }
+    /// Pause the service.
+    ///
+    /// Requires the [`ServiceAccess::PAUSE_CONTINUE`] permission.
+    pub fn pause(&self) -> Result<ServiceStatus> {
+        self.send_control_command(ServiceControl::Pause)
     }
 
-    /// Private helper to send the control commands to the system.
-    fn send_control_command(&self, command: ServiceControl) -> Result<ServiceStatus> {
-        let mut raw_status = unsafe { mem::zeroed::<winsvc::SERVICE_STATUS>() };
-        let success = unsafe {
-            winsvc::ControlService(
-                self.service_handle.raw_handle(),
-                command.to_raw(),
-                &mut raw_status,
-            )
-        };
+    /// Resume the paused service.
+    ///
+    /// Requires the [`ServiceAccess::PAUSE_CONTINUE`] permission.
+    pub fn resume(&self) -> Result<ServiceStatus> {
+        self.send_control_command(ServiceControl::Continue)
+    }
 
-        if success == 1 {
-            ServiceStatus::from_raw(raw_status).map_err(|err| err.into())
-        } else {
-            Err(io::Error::last_os_error().into())
+    /// Ask the service to report its current status to the system.
+    ///
+    /// Requires the [`ServiceAccess::INTERROGATE`] permission.
+    pub fn interrogate(&self) -> Result<ServiceStatus> {
+        self.send_control_command(ServiceControl::Interrogate)
+    }
+
+    /// Send a control code defined by the service, which the service receives as
+    /// [`ServiceControl::UserDefined`].
+    ///
+    /// The code must be in the range from 128 to 255, lower codes are reserved by the system.
+    /// Requires the [`ServiceAccess::USER_DEFINED_CONTROL`] permission.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use windows_service::service::ServiceAccess;
+    /// use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+    ///
+    /// # #[cfg(windows)]
+    /// # fn main() -> windows_service::Result<()> {
+    /// const ROTATE_LOGS: u8 = 128;
+    ///
+    /// let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    /// let my_service = manager.open_service("my_service", ServiceAccess::USER_DEFINED_CONTROL)?;
+    /// my_service.send_user_control(ROTATE_LOGS)?;
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(windows))]
+    /// # fn main() {}
+    /// ```
+    pub fn send_user_control(&self, code: u8) -> Result<ServiceStatus> {
+        let code = u32::from(code);
+        if code < winsvc::SERVICE_USER_DEFINED_CONTROL_MIN {
+            Err(ErrorKind::InvalidServiceControl(code))?;
         }
+        self.send_control_command(ServiceControl::UserDefined(code))
     }
 
-    pub fn modify_start_type(&self, start_type: ServiceStartType) -> io::Result<()> {
-        let success = unsafe {
-            winsvc::ChangeServiceConfigW(
-                self.service_handle.raw_handle(),
-                SERVICE_NO_CHANGE,
-                start_type.to_raw(),
-                SERVICE_NO_CHANGE,
-                ptr::null(),
-                ptr::null(),
-                ptr::null_mut(),
-                ptr::null(),
-                ptr::null(),
-                ptr::null(),
-                ptr::null()
-            )
-        };
+    /// Block until the service reaches the given state, polling its status.
+    ///
+    /// While the service is in a pending state, the wait follows the progress it reports: the
+    /// service is considered hung if it does not advance [`ServiceStatus::checkpoint`] within
+    /// [`ServiceStatus::wait_hint`], independently of `timeout`. The wait fails with
+    ///
+    /// * [`ErrorKind::ServiceFailed`] if the service stopped instead, with its exit code.
+    /// * [`ErrorKind::ServiceHung`] if the service stopped making progress.
+    /// * [`ErrorKind::UnexpectedServiceState`] if the service settled in another state.
+    /// * [`ErrorKind::Timeout`] if the service was still making progress after `timeout`.
+    ///
+    /// Requires the [`ServiceAccess::QUERY_STATUS`] permission.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    /// use windows_service::service::{ServiceAccess, ServiceState};
+    /// use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+    ///
+    /// # #[cfg(windows)]
+    /// # fn main() -> windows_service::Result<()> {
+    /// let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    /// let my_service = manager.open_service(
+    ///     "my_service",
+    ///     ServiceAccess::STOP | ServiceAccess::QUERY_STATUS,
+    /// )?;
+    /// my_service.stop()?;
+    /// my_service.wait_for_state(ServiceState::Stopped, Duration::from_secs(30))?;
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(windows))]
+    /// # fn main() {}
+    /// ```
+    ///
+    /// [`ErrorKind::ServiceFailed`]: ::ErrorKind::ServiceFailed
+    /// [`ErrorKind::ServiceHung`]: ::ErrorKind::ServiceHung
+    /// [`ErrorKind::UnexpectedServiceState`]: ::ErrorKind::UnexpectedServiceState
+    /// [`ErrorKind::Timeout`]: ::ErrorKind::Timeout
+    pub fn wait_for_state(
+        &self,
+        target_state: ServiceState,
+        timeout: Duration,
+    ) -> Result<ServiceStatus> {
+        wait::wait_for_state(
+            || self.query_status(),
+            &wait::SystemClock,
+            target_state,
+            timeout,
+        )
+    }
 
-        if success == 1 {
-            Ok(())
-        } else {
-            Err(io::Error::last_os_error().into())
-        }
+    /// Start the service and wait for it to be running, without blocking the async executor.
+    ///
+    /// The service is started when the returned future is first polled, and the future resolves
+    /// once the service reports the [`ServiceState::Running`] state, or fails as described in
+    /// [`Service::wait_for_state`].
+    ///
+    /// Requires the [`ServiceAccess::START`] and [`ServiceAccess::QUERY_STATUS`] permissions and
+    /// the `async` feature.
+    ///
+    /// # Example
+    ///
+    /// ```rust,edition2018,no_run
+    /// use std::time::Duration;
+    /// use windows_service::service::{Service, ServiceStatus};
+    ///
+    /// async fn restart(my_service: &Service) -> windows_service::Result<ServiceStatus> {
+    ///     let timeout = Duration::from_secs(30);
+    ///     my_service.stop_async(timeout).await?;
+    ///     my_service.start_async(&["--verbose"], timeout).await
+    /// }
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn start_async<S: AsRef<OsStr>>(
+        &self,
+        service_arguments: &[S],
+        timeout: Duration,
+    ) -> ServiceStateFuture<'_> {
+        let service_arguments = service_arguments
+            .iter()
+            .map(|argument| argument.as_ref().to_os_string())
+            .collect();
+        ServiceStateFuture::start(self, service_arguments, timeout)
     }
-}
 
+    /// Stop the service and wait for it to be stopped, without blocking the async executor.
+    ///
+    /// The stop control is sent when the returned future is first polled, and the future
+    /// resolves once the service reports the [`ServiceState::Stopped`] state, or fails as
+    /// described in [`Service::wait_for_state`].
+    ///
+    /// Requires the [`ServiceAccess::STOP`] and [`ServiceAccess::QUERY_STATUS`] permissions and
+    /// the `async` feature.
+    #[cfg(feature = "async")]
+    pub fn stop_async(&self, timeout: Duration) -> ServiceStateFuture<'_> {
+        ServiceStateFuture::stop(self, timeout)
+    }
 
-pub struct EnumListServiceResult {
-    entry_point: *const u8,
-    index: usize,
-    size: usize,
-}
+    /// Wait for the service to reach the target state, without blocking the async executor.
+    ///
+    /// The status is polled and the wait fails just like in [`Service::wait_for_state`], only
+    /// the time between two status queries is awaited rather than slept.
+    ///
+    /// Requires the [`ServiceAccess::QUERY_STATUS`] permission and the `async` feature.
+    #[cfg(feature = "async")]
+    pub fn wait_for_state_async(
+        &self,
+        target_state: ServiceState,
+        timeout: Duration,
+    ) -> ServiceStateFuture<'_> {
+        ServiceStateFuture::wait(self, target_state, timeout)
+    }
 
-impl EnumListServiceResult {
-    pub fn from_raw(entry_point: *const u8, size: u32) -> EnumListServiceResult {
-        EnumListServiceResult {entry_point, index: 0, size : size as usize}
+    /// Watch the service for state transitions.
+    ///
+    /// The watch reports a [`ServiceEvent::StateChanged`] whenever the service enters a state
+    /// selected by the mask, starting with the current state if it is selected. Include
+    /// [`ServiceNotifyMask::DELETE_PENDING`] to be told when the service is marked for deletion,
+    /// which ends the watch either way.
+    ///
+    /// Requires the [`ServiceAccess::QUERY_STATUS`] permission.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use windows_service::service::ServiceAccess;
+    /// use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+    /// use windows_service::watch::{ServiceEvent, ServiceNotifyMask};
+    ///
+    /// # #[cfg(windows)]
+    /// # fn main() -> windows_service::Result<()> {
+    /// let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    /// let my_service = manager.open_service("my_service", ServiceAccess::QUERY_STATUS)?;
+    /// for event in my_service.watch(ServiceNotifyMask::STOPPED | ServiceNotifyMask::RUNNING)? {
+    ///     if let ServiceEvent::StateChanged(status) = event? {
+    ///         println!("my_service is {:?}", status.status.current_state);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(windows))]
+    /// # fn main() {}
+    /// ```
+    ///
+    /// [`ServiceEvent::StateChanged`]: super::watch::ServiceEvent::StateChanged
+    /// [`ServiceNotifyMask::DELETE_PENDING`]: super::watch::ServiceNotifyMask::DELETE_PENDING
+    pub fn watch(&self, mask: ServiceNotifyMask) -> Result<ServiceWatch> {
+        let (sender, events) = mpsc::channel();
+        let handle = self.backend.watch(mask, sender)?;
+        Ok(ServiceWatch::new(events, handle))
+    }
+
+    /// Get the service status from the system.
+    pub fn query_status(&self) -> Result<ServiceStatus> {
+        self.backend.query_status()
     }
-}
 
-impl Iterator for EnumListServiceResult {
-    type Item = winsvc::ENUM_SERVICE_STATUS_PROCESSW;
-    fn next(&mut self) -> Option<winsvc::ENUM_SERVICE_STATUS_PROCESSW> {
+    /// Get the service configuration from the system.
+    ///
+    /// Requires the [`ServiceAccess::QUERY_CONFIG`] permission.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use windows_service::service::ServiceAccess;
+    /// use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+    ///
+    /// # #[cfg(windows)]
+    /// # fn main() -> windows_service::Result<()> {
+    /// let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    /// let my_service = manager.open_service("my_service", ServiceAccess::QUERY_CONFIG)?;
+    /// let config = my_service.query_config()?;
+    /// println!("my_service runs {}", config.executable_path.display());
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(windows))]
+    /// # fn main() {}
+    /// ```
+    pub fn query_config(&self) -> Result<ServiceConfig> {
+        self.backend.query_config()
+    }
 
-        if self.index < self.size {
+    /// Change the service configuration, leaving the settings that are not part of the change
+    /// unchanged.
+    ///
+    /// Requires the [`ServiceAccess::SERVICE_CHANGE_CONFIG`] permission. Changing the executable
+    /// path or the launch arguments also requires [`ServiceAccess::QUERY_CONFIG`], because the
+    /// system stores them as a single command line and the rest of it is read from the current
+    /// configuration. This can be avoided by setting the service type, executable path and
+    /// launch arguments together.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use windows_service::service::{ServiceAccess, ServiceConfigChange};
+    /// use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+    ///
+    /// # #[cfg(windows)]
+    /// # fn main() -> windows_service::Result<()> {
+    /// let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    /// let my_service = manager.open_service(
+    ///     "my_service",
+    ///     ServiceAccess::QUERY_CONFIG | ServiceAccess::SERVICE_CHANGE_CONFIG,
+    /// )?;
+    /// my_service.change_config(
+    ///     &ServiceConfigChange::new().executable_path(r"C:\Program Files\Agent\agent-v2.exe"),
+    /// )?;
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(windows))]
+    /// # fn main() {}
+    /// ```
+    pub fn change_config(&self, change: &ServiceConfigChange) -> Result<()> {
+        self.backend.change_config(change)
+    }
 
-            let result: *mut winsvc::ENUM_SERVICE_STATUS_PROCESSW = unsafe {
-                mem::transmute(self.entry_point.offset((self.index * mem::size_of::<winsvc::ENUM_SERVICE_STATUS_PROCESSW>()) as isize))
-            };
+    /// Get an optional service setting from the system.
+    ///
+    /// Requires the [`ServiceAccess::QUERY_CONFIG`] permission. The typed getters, such as
+    /// [`Service::description`], are more convenient when the kind is known up front.
+    pub fn query_setting(&self, kind: ServiceSettingKind) -> Result<ServiceSetting> {
+        self.backend.query_setting(kind)
+    }
 
-            self.index += 1;
+    /// Change an optional service setting.
+    ///
+    /// Requires the [`ServiceAccess::SERVICE_CHANGE_CONFIG`] permission.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use windows_service::service::{ServiceAccess, ServiceSetting};
+    /// use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+    ///
+    /// # #[cfg(windows)]
+    /// # fn main() -> windows_service::Result<()> {
+    /// let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    /// let my_service = manager.open_service("my_service", ServiceAccess::SERVICE_CHANGE_CONFIG)?;
+    /// my_service.change_setting(&ServiceSetting::Description("Keeps the agent running".into()))?;
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(windows))]
+    /// # fn main() {}
+    /// ```
+    pub fn change_setting(&self, setting: &ServiceSetting) -> Result<()> {
+        self.backend.change_setting(setting)
+    }
 
-            Some(unsafe {*result})
+    /// Get the service description, empty if the service has none.
+    pub fn description(&self) -> Result<OsString> {
+        query_setting!(self, Description)
+    }
 
-        } else {
-            None
+    /// Set the service description, an empty description removes it.
+    pub fn set_description<S: Into<OsString>>(&self, description: S) -> Result<()> {
+        self.change_setting(&ServiceSetting::Description(description.into()))
+    }
+
+    /// Get the recovery actions taken when the service fails.
+    pub fn failure_actions(&self) -> Result<ServiceFailureActions> {
+        query_setting!(self, FailureActions)
+    }
+
+    /// Set the recovery actions taken when the service fails.
+    ///
+    /// Restart actions also require the [`ServiceAccess::START`] permission, and reboot actions
+    /// require the caller to hold the `SeShutdownPrivilege` privilege.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    /// use windows_service::service::{
+    ///     ServiceAccess, ServiceAction, ServiceActionType, ServiceFailureActions,
+    ///     ServiceFailureResetPeriod,
+    /// };
+    /// use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+    ///
+    /// # #[cfg(windows)]
+    /// # fn main() -> windows_service::Result<()> {
+    /// let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    /// let my_service = manager.open_service(
+    ///     "my_service",
+    ///     ServiceAccess::SERVICE_CHANGE_CONFIG | ServiceAccess::START,
+    /// )?;
+    /// my_service.set_failure_actions(&ServiceFailureActions {
+    ///     reset_period: ServiceFailureResetPeriod::After(Duration::from_secs(24 * 60 * 60)),
+    ///     reboot_msg: None,
+    ///     command: None,
+    ///     actions: vec![ServiceAction {
+    ///         action_type: ServiceActionType::Restart,
+    ///         delay: Duration::from_secs(10),
+    ///     }],
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(windows))]
+    /// # fn main() {}
+    /// ```
+    pub fn set_failure_actions(&self, failure_actions: &ServiceFailureActions) -> Result<()> {
+        self.change_setting(&ServiceSetting::FailureActions(failure_actions.clone()))
+    }
+
+    /// Get whether the auto-start service is started with a delay after boot.
+    pub fn delayed_auto_start(&self) -> Result<bool> {
+        query_setting!(self, DelayedAutoStart)
+    }
+
+    /// Set whether the auto-start service is started with a delay after boot.
+    pub fn set_delayed_auto_start(&self, delayed: bool) -> Result<()> {
+        self.change_setting(&ServiceSetting::DelayedAutoStart(delayed))
+    }
+
+    /// Get how long the system waits for the service to handle the preshutdown control.
+    pub fn preshutdown_timeout(&self) -> Result<Duration> {
+        query_setting!(self, PreshutdownTimeout)
+    }
+
+    /// Set how long the system waits for the service to handle the preshutdown control.
+    pub fn set_preshutdown_timeout(&self, timeout: Duration) -> Result<()> {
+        self.change_setting(&ServiceSetting::PreshutdownTimeout(timeout))
+    }
+
+    /// Get the privileges the service process needs.
+    pub fn required_privileges(&self) -> Result<Vec<OsString>> {
+        query_setting!(self, RequiredPrivileges)
+    }
+
+    /// Set the privileges the service process needs, an empty list removes the restriction.
+    pub fn set_required_privileges<I, S>(&self, privileges: I) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        let privileges = privileges.into_iter().map(Into::into).collect();
+        self.change_setting(&ServiceSetting::RequiredPrivileges(privileges))
+    }
+
+    /// Get the type of the service security identifier.
+    pub fn sid_type(&self) -> Result<ServiceSidType> {
+        query_setting!(self, SidType)
+    }
+
+    /// Set the type of the service security identifier.
+    pub fn set_sid_type(&self, sid_type: ServiceSidType) -> Result<()> {
+        self.change_setting(&ServiceSetting::SidType(sid_type))
+    }
+
+    /// Get whether the failure actions also run when the service stops with an error.
+    pub fn failure_actions_on_non_crash_failures(&self) -> Result<bool> {
+        query_setting!(self, FailureActionsOnNonCrashFailures)
+    }
+
+    /// Set whether the failure actions also run when the service stops with an error.
+    pub fn set_failure_actions_on_non_crash_failures(&self, enabled: bool) -> Result<()> {
+        self.change_setting(&ServiceSetting::FailureActionsOnNonCrashFailures(enabled))
+    }
+
+    /// Get the preferred NUMA node of the service.
+    pub fn preferred_node(&self) -> Result<Option<u16>> {
+        query_setting!(self, PreferredNode)
+    }
+
+    /// Set the preferred NUMA node of the service, `None` removes the preference.
+    pub fn set_preferred_node(&self, node: Option<u16>) -> Result<()> {
+        self.change_setting(&ServiceSetting::PreferredNode(node))
+    }
+
+    /// Get the events that start or stop the service.
+    pub fn triggers(&self) -> Result<Vec<ServiceTrigger>> {
+        query_setting!(self, Triggers)
+    }
+
+    /// Set the events that start or stop the service, replacing the current triggers. An empty
+    /// list removes all triggers.
+    ///
+    /// See the [`service_trigger`] module for an example.
+    ///
+    /// [`service_trigger`]: super::service_trigger
+    pub fn set_triggers(&self, triggers: &[ServiceTrigger]) -> Result<()> {
+        self.change_setting(&ServiceSetting::Triggers(triggers.to_vec()))
+    }
+
+    /// List the services that depend on this service, directly or through its load ordering
+    /// group, and are in a state that passes the filter.
+    ///
+    /// The services are listed in the reverse order of their start order, so that each service
+    /// comes before the services it depends on.
+    ///
+    /// Requires the [`ServiceAccess::ENUMERATE_DEPENDENTS`] permission.
+    pub fn dependents(&self, state_filter: ServiceStateFilter) -> Result<Vec<ServiceEntry>> {
+        self.backend.dependents(state_filter)
+    }
+
+    /// Delete the service from system registry.
+    pub fn delete(self) -> io::Result<()> {
+        self.backend.delete()
+    }
+
+    /// Private helper to stop the service unless it is already stopping, and wait until it stops.
+    fn stop_and_wait(&self, timeout: Duration) -> Result<ServiceStatus> {
+        match self.query_status()?.current_state {
+            ServiceState::Stopped | ServiceState::StopPending => (),
+            _ => {
+                self.stop()?;
+            }
         }
+        self.wait_for_state(ServiceState::Stopped, timeout)
+    }
 
+    /// Private helper to send the control commands to the system.
+    fn send_control_command(&self, command: ServiceControl) -> Result<ServiceStatus> {
+        self.backend.control(command)
     }
 }
 
-#[derive(Debug, Serialize)]
+/// A serializable snapshot of a service listed by [`ServiceManager::list_services`].
+///
+/// The configuration fields are `None` if the configuration could not be queried.
+///
+/// [`ServiceManager::list_services`]: super::service_manager::ServiceManager::list_services
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ServiceDetail {
     pub status: ServiceStatusExt,
-    pub name: String,
-    pub display_name: String,
-    pub binary_path: Option<String>,
+    #[serde(with = "schema::os_string")]
+    pub name: OsString,
+    #[serde(with = "schema::os_string")]
+    pub display_name: OsString,
+    #[serde(with = "schema::os_string::option")]
+    pub binary_path: Option<OsString>,
     pub start_type: Option<ServiceStartType>,
     pub error_control: Option<ServiceErrorControl>,
-    pub load_order_group: Option<String>,
+    #[serde(with = "schema::os_string::option")]
+    pub load_order_group: Option<OsString>,
     pub tag_id: Option<u32>,
-    pub dependencies: Option<String>,
-    pub start_name: Option<String>
+    #[serde(default, deserialize_with = "schema::legacy_dependencies")]
+    pub dependencies: Option<Vec<ServiceDependency>>,
+    #[serde(with = "schema::os_string::option")]
+    pub start_name: Option<OsString>,
+    #[serde(default)]
+    pub failure_actions: Option<ServiceFailureActions>,
+}
+
+/// A service listed by [`ServiceManager::list_services`], along with its configuration.
+///
+/// The configuration and the recovery actions are queried separately for every service, so they
+/// can fail for some services while the rest are listed fine, for example because access to the
+/// service is denied or the service was deleted after it was enumerated.
+///
+/// [`ServiceManager::list_services`]: super::service_manager::ServiceManager::list_services
+#[derive(Debug)]
+pub struct ServiceListing {
+    /// The name and status of the service.
+    pub entry: ServiceEntryExt,
+
+    /// The service configuration, or the error that prevented querying it.
+    pub config: Result<ServiceConfig>,
+
+    /// The recovery actions of the service, or the error that prevented querying them.
+    pub failure_actions: Result<ServiceFailureActions>,
+}
+
+impl ServiceListing {
+    /// Returns a serializable snapshot of the service.
+    pub fn to_detail(&self) -> ServiceDetail {
+        let config = self.config.as_ref().ok();
+        ServiceDetail {
+            status: self.entry.status.clone(),
+            name: self.entry.name.clone(),
+            display_name: self.entry.display_name.clone(),
+            binary_path: config
+                .map(|config| config.binary_path.clone())
+                .filter(|binary_path| !binary_path.is_empty()),
+            start_type: config.map(|config| config.start_type),
+            error_control: config.map(|config| config.error_control),
+            load_order_group: config.and_then(|config| config.load_order_group.clone()),
+            tag_id: config.map(|config| config.tag_id),
+            dependencies: config.map(|config| config.dependencies.clone()),
+            start_name: config.and_then(|config| config.account_name.clone()),
+            failure_actions: self.failure_actions.as_ref().ok().cloned(),
+        }
+    }
+}
+
+impl ServiceDetail {
+    /// Returns the service name for display, replacing invalid Unicode with `U+FFFD`.
+    ///
+    /// Use [`ServiceDetail::name`] to open the service, the lossy name may refer to another
+    /// service or none at all.
+    pub fn name_lossy(&self) -> Cow<'_, str> {
+        self.name.to_string_lossy()
+    }
+
+    /// Returns the user-friendly service name for display, replacing invalid Unicode with
+    /// `U+FFFD`.
+    pub fn display_name_lossy(&self) -> Cow<'_, str> {
+        self.display_name.to_string_lossy()
+    }
 }
 
+/// Convert the duration to milliseconds, clamped to the range of a `DWORD`.
+pub(crate) fn duration_millis(duration: Duration) -> u32 {
+    let millis = duration
+        .as_secs()
+        .saturating_mul(1000)
+        .saturating_add(u64::from(duration.subsec_millis()));
+    millis.min(u64::from(u32::MAX)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    #[test]
+    fn test_service_control_raw_round_trip() {
+        for raw_value in (1..=0x0F).chain(128..=255) {
+            if let Ok(control) = ServiceControl::from_raw(raw_value) {
+                assert_eq!(control.to_raw(), raw_value);
+            }
+        }
+        assert_eq!(
+            ServiceControl::from_raw(128).unwrap(),
+            ServiceControl::UserDefined(128)
+        );
+        assert_eq!(
+            ServiceControl::from_raw(255).unwrap(),
+            ServiceControl::UserDefined(255)
+        );
+        assert!(ServiceControl::from_raw(127).is_err());
+        assert!(ServiceControl::from_raw(256).is_err());
+    }
+
+    #[test]
+    fn test_service_control_without_event_data() {
+        for &raw_value in &[0x20, 0x40, 0x60, 0x61] {
+            let control = unsafe { ServiceControl::from_raw_event(raw_value, 0, ptr::null()) };
+            assert_eq!(control.unwrap().to_raw(), raw_value);
+        }
+        for &raw_value in &[0x0B, 0x0D, 0x0E, 0x10] {
+            assert!(ServiceControl::from_raw(raw_value).is_err());
+        }
+        let missing_data = unsafe {
+            ServiceControl::from_raw_event(
+                winsvc::SERVICE_CONTROL_SESSIONCHANGE,
+                winuser::WTS_SESSION_LOGON,
+                ptr::null(),
+            )
+        };
+        assert!(missing_data.is_err());
+    }
 
+    #[test]
+    fn test_service_control_session_change() {
+        let notification = RawSessionNotification {
+            size: mem::size_of::<RawSessionNotification>() as u32,
+            session_id: 3,
+        };
+        let control = unsafe {
+            ServiceControl::from_raw_event(
+                winsvc::SERVICE_CONTROL_SESSIONCHANGE,
+                winuser::WTS_SESSION_LOGOFF,
+                &notification as *const _ as *const c_void,
+            )
+        };
+        assert_eq!(
+            control.unwrap(),
+            ServiceControl::SessionChange(SessionChangeParam {
+                reason: SessionChangeReason::SessionLogoff,
+                session_id: 3,
+            })
+        );
+        assert!(SessionChangeReason::from_raw(0x0C).is_err());
+    }
 
+    #[test]
+    fn test_service_control_power_event() {
+        #[repr(C)]
+        struct Setting {
+            header: RawPowerBroadcastSetting,
+            data: [u8; 4],
+        }
+        let power_setting = Guid::from_values(
+            0x6fe69556,
+            0x704a,
+            0x47a0,
+            [0x8f, 0x24, 0xc2, 0x8d, 0x93, 0x6f, 0xda, 0x47],
+        );
+        let setting = Setting {
+            header: RawPowerBroadcastSetting {
+                power_setting,
+                data_length: 4,
+            },
+            data: [1, 0, 0, 0],
+        };
+        let control = unsafe {
+            ServiceControl::from_raw_event(
+                winsvc::SERVICE_CONTROL_POWEREVENT,
+                winuser::PBT_POWERSETTINGCHANGE,
+                &setting as *const _ as *const c_void,
+            )
+        };
+        assert_eq!(
+            control.unwrap(),
+            ServiceControl::PowerEvent(PowerEventParam::PowerSettingChange(PowerSetting {
+                power_setting,
+                data: vec![1, 0, 0, 0],
+            }))
+        );
+
+        let control = unsafe {
+            ServiceControl::from_raw_event(
+                winsvc::SERVICE_CONTROL_POWEREVENT,
+                winuser::PBT_APMRESUMESUSPEND,
+                ptr::null(),
+            )
+        };
+        assert_eq!(
+            control.unwrap(),
+            ServiceControl::PowerEvent(PowerEventParam::ResumeSuspend)
+        );
+    }
+
+    #[test]
+    fn test_service_control_time_change() {
+        let info = RawTimeChangeInfo {
+            new_time: FILETIME_UNIX_EPOCH + 15_000_000,
+            old_time: FILETIME_UNIX_EPOCH - 5,
+        };
+        let control = unsafe {
+            ServiceControl::from_raw_event(
+                winsvc::SERVICE_CONTROL_TIMECHANGE,
+                0,
+                &info as *const _ as *const c_void,
+            )
+        };
+        assert_eq!(
+            control.unwrap(),
+            ServiceControl::TimeChange(TimeChangeParam {
+                old_time: UNIX_EPOCH - Duration::from_nanos(500),
+                new_time: UNIX_EPOCH + Duration::from_millis(1500),
+            })
+        );
+    }
+
+    #[test]
+    fn test_service_control_time_change_out_of_range() {
+        let info = RawTimeChangeInfo {
+            new_time: FILETIME_UNIX_EPOCH,
+            old_time: i64::MIN,
+        };
+        let control = unsafe {
+            ServiceControl::from_raw_event(
+                winsvc::SERVICE_CONTROL_TIMECHANGE,
+                0,
+                &info as *const _ as *const c_void,
+            )
+        };
+        // `SystemTime` cannot hold times before 1601 on Windows, where decoding fails instead of
+        // panicking in the control handler.
+        if cfg!(windows) {
+            match control.unwrap_err().kind() {
+                ErrorKind::InvalidFileTime(i64::MIN) => (),
+                other => panic!("Unexpected error: {:?}", other),
+            }
+        } else {
+            assert!(control.is_ok());
+        }
+    }
+
+    #[test]
+    fn test_service_control_hardware_profile_change() {
+        let control = unsafe {
+            ServiceControl::from_raw_event(
+                winsvc::SERVICE_CONTROL_HARDWAREPROFILECHANGE,
+                dbt::DBT_CONFIGCHANGED,
+                ptr::null(),
+            )
+        };
+        assert_eq!(
+            control.unwrap(),
+            ServiceControl::HardwareProfileChange(HardwareProfileChangeParam::ConfigChanged)
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_service_status_to_raw_wait_hint() {
+        let mut status = ServiceStatus {
+            service_type: ServiceType::OwnProcess,
+            current_state: ServiceState::StartPending,
+            controls_accepted: ServiceControlAccept::empty(),
+            exit_code: ServiceExitCode::default(),
+            checkpoint: 1,
+            wait_hint: Duration::from_millis(1500),
+        };
+        assert_eq!(status.to_raw().dwWaitHint, 1500);
+        status.wait_hint = Duration::from_secs(u64::from(u32::MAX));
+        assert_eq!(status.to_raw().dwWaitHint, u32::MAX);
+        status.wait_hint = Duration::from_secs(u64::MAX);
+        assert_eq!(status.to_raw().dwWaitHint, u32::MAX);
+    }
+
+    #[test]
+    fn test_failure_actions_to_raw() {
+        let failure_actions = ServiceFailureActions {
+            reset_period: ServiceFailureResetPeriod::After(Duration::from_millis(90_500)),
+            reboot_msg: Some(OsString::from("Rebooting")),
+            command: None,
+            actions: vec![
+                ServiceAction {
+                    action_type: ServiceActionType::Restart,
+                    delay: Duration::from_secs(5),
+                },
+                ServiceAction {
+                    action_type: ServiceActionType::Reboot,
+                    delay: Duration::from_secs(60 * 24 * 60 * 60),
+                },
+            ],
+        };
+        let raw = failure_actions.to_raw().unwrap();
+
+        assert_eq!(raw.reset_period, 90);
+        assert_eq!(raw.reboot_msg.to_os_string(), OsString::from("Rebooting"));
+        assert!(raw.command.is_empty());
+        assert_eq!(
+            raw.actions,
+            vec![
+                RawServiceAction {
+                    action_type: winsvc::SC_ACTION_RESTART,
+                    delay: 5000,
+                },
+                // The delay is clamped instead of wrapping around.
+                RawServiceAction {
+                    action_type: winsvc::SC_ACTION_REBOOT,
+                    delay: u32::MAX,
+                },
+            ]
+        );
+        assert_eq!(::std::mem::size_of::<RawServiceAction>(), 8);
+    }
+
+    #[test]
+    fn test_failure_actions_from_raw() {
+        let raw = RawFailureActions {
+            reset_period: winbase::INFINITE,
+            reboot_msg: WideCString::default(),
+            command: WideCString::from_str("notify.exe").unwrap(),
+            actions: vec![RawServiceAction {
+                action_type: winsvc::SC_ACTION_RUN_COMMAND,
+                delay: 1500,
+            }],
+        };
+        let failure_actions = ServiceFailureActions::from_raw(&raw).unwrap();
+
+        assert_eq!(failure_actions.reset_period, ServiceFailureResetPeriod::Never);
+        assert_eq!(failure_actions.reboot_msg, None);
+        assert_eq!(failure_actions.command, Some(OsString::from("notify.exe")));
+        assert_eq!(
+            failure_actions.actions,
+            vec![ServiceAction {
+                action_type: ServiceActionType::RunCommand,
+                delay: Duration::from_millis(1500),
+            }]
+        );
+        assert_eq!(failure_actions.to_raw().unwrap(), raw);
+
+        let invalid = RawFailureActions {
+            actions: vec![RawServiceAction {
+                action_type: 4,
+                delay: 0,
+            }],
+            ..raw
+        };
+        assert!(ServiceFailureActions::from_raw(&invalid).is_err());
+    }
+}
@@ -1,54 +1,143 @@
 use std::ffi::OsStr;
+#[cfg(feature = "async")]
+use std::future::Future;
 use std::io;
+use std::mem;
+use std::os::windows::io::RawHandle;
+use std::sync::Arc;
 use widestring::WideCString;
-use winapi::shared::winerror::{ERROR_CALL_NOT_IMPLEMENTED, NO_ERROR};
-use winapi::um::winsvc;
-
-use service::{ServiceControl, ServiceStatus};
+use winapi::um::dbt::{
+    DBT_DEVTYP_DEVICEINTERFACE, DBT_DEVTYP_HANDLE, DEV_BROADCAST_DEVICEINTERFACE_W,
+    DEV_BROADCAST_HANDLE,
+};
+use winapi::um::{winsvc, winuser};
+
+#[cfg(feature = "async")]
+use asynchronous::{self, ReportPending, ServiceControlStream};
+use guid::Guid;
+use handler_context::{self, HandlerContext, HandlerFn};
+pub use handler_context::ServiceControlHandlerResult;
+use service::{ServiceControl, ServiceState, ServiceStatus};
 use {ErrorKind, Result, ResultExt};
-use winapi::shared::usbiodef;
-use winapi::um::winuser;
-use winapi::shared::ntdef::HANDLE;
-use winapi::shared::minwindef::LPVOID;
-use std::mem;
-use winapi::um::dbt::DBT_DEVTYP_DEVICEINTERFACE;
-use winapi::um::dbt::DEV_BROADCAST_DEVICEINTERFACE_A;
-
 
 /// A struct that holds a unique token for updating the status of the corresponding service.
-#[derive(Debug, Clone, Copy)]
-pub struct ServiceStatusHandle(winsvc::SERVICE_STATUS_HANDLE);
+///
+/// The handle keeps the event handler of the service registered until the service reports the
+/// [`ServiceState::Stopped`] state. The handler is dropped then, and any control the system still
+/// delivers is answered without calling it.
+#[derive(Debug, Clone)]
+pub struct ServiceStatusHandle {
+    handle: winsvc::SERVICE_STATUS_HANDLE,
+    context: Arc<HandlerContext>,
+}
 
 impl ServiceStatusHandle {
-    fn from_handle(handle: winsvc::SERVICE_STATUS_HANDLE) -> Self {
-        ServiceStatusHandle(handle)
+    fn from_handle(handle: winsvc::SERVICE_STATUS_HANDLE, context: Arc<HandlerContext>) -> Self {
+        ServiceStatusHandle { handle, context }
     }
 
     /// Report the new service status to the system.
     pub fn set_service_status(&self, service_status: ServiceStatus) -> io::Result<()> {
+        let stopped = service_status.current_state == ServiceState::Stopped;
         let mut raw_service_status = service_status.to_raw();
-        let result = unsafe { winsvc::SetServiceStatus(self.0, &mut raw_service_status) };
+        let result = unsafe { winsvc::SetServiceStatus(self.handle, &mut raw_service_status) };
         if result == 0 {
             Err(io::Error::last_os_error())
         } else {
+            if stopped {
+                self.context.release();
+            }
             Ok(())
         }
     }
 
-    pub fn subscribe_device_event(&self) {
-        let filter = Box::new(DEV_BROADCAST_DEVICEINTERFACE_A {
-            dbcc_size: mem::size_of::<DEV_BROADCAST_DEVICEINTERFACE_A>() as u32,
-            dbcc_devicetype: DBT_DEVTYP_DEVICEINTERFACE,
-            dbcc_reserved: 0,
-            dbcc_classguid: usbiodef::GUID_DEVINTERFACE_USB_DEVICE,
-            dbcc_name: [0],
-        });
-        unsafe {
-            winuser::RegisterDeviceNotificationA(
-                self.0 as HANDLE,
-                Box::into_raw(filter) as LPVOID,
-                winuser::DEVICE_NOTIFY_SERVICE_HANDLE,
-            );
+    /// Report the pending status and its progress to the system while the future runs.
+    ///
+    /// The checkpoint of the status is incremented every half of its wait hint until the future
+    /// completes, see [`asynchronous::report_pending`]. Requires the `async` feature.
+    ///
+    /// # Example
+    ///
+    /// ```rust,edition2018,no_run
+    /// use std::time::Duration;
+    /// use windows_service::service::{
+    ///     ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType,
+    /// };
+    /// use windows_service::service_control_handler::ServiceStatusHandle;
+    ///
+    /// async fn stop(status_handle: ServiceStatusHandle) -> windows_service::Result<()> {
+    ///     let mut status = ServiceStatus {
+    ///         service_type: ServiceType::OwnProcess,
+    ///         current_state: ServiceState::StopPending,
+    ///         controls_accepted: ServiceControlAccept::empty(),
+    ///         exit_code: ServiceExitCode::Win32(0),
+    ///         checkpoint: 1,
+    ///         wait_hint: Duration::from_secs(10),
+    ///     };
+    ///     status_handle.report_pending(status.clone(), flush_state()).await?;
+    ///
+    ///     status.current_state = ServiceState::Stopped;
+    ///     status.checkpoint = 0;
+    ///     status.wait_hint = Duration::default();
+    ///     status_handle.set_service_status(status)?;
+    ///     Ok(())
+    /// }
+    ///
+    /// async fn flush_state() {
+    ///     // Write the state of the service to disk...
+    /// }
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn report_pending<F: Future>(
+        &self,
+        status: ServiceStatus,
+        future: F,
+    ) -> ReportPending<F, impl FnMut(ServiceStatus) -> io::Result<()>> {
+        let status_handle = self.clone();
+        let report = move |status| status_handle.set_service_status(status);
+        asynchronous::report_pending(status, report, future)
+    }
+
+    /// Subscribe to the events of the devices of a device interface class.
+    ///
+    /// The service receives the events as [`ServiceControl::DeviceEvent`] controls until the
+    /// returned [`DeviceNotification`] is dropped.
+    pub fn subscribe_device_interface(&self, class_guid: Guid) -> io::Result<DeviceNotification> {
+        let mut filter: DEV_BROADCAST_DEVICEINTERFACE_W = unsafe { mem::zeroed() };
+        filter.dbcc_size = mem::size_of::<DEV_BROADCAST_DEVICEINTERFACE_W>() as u32;
+        filter.dbcc_devicetype = DBT_DEVTYP_DEVICEINTERFACE;
+        filter.dbcc_classguid = class_guid.into();
+        unsafe { self.register_device_notification(&mut filter as *mut _ as *mut _) }
+    }
+
+    /// Subscribe to the events of the device opened with the given handle, including the
+    /// custom events raised by its driver.
+    ///
+    /// The service receives the events as [`ServiceControl::DeviceEvent`] controls until the
+    /// returned [`DeviceNotification`] is dropped. Close the device handle when receiving a
+    /// query remove or remove pending event for it, or the device cannot be removed.
+    pub fn subscribe_handle(&self, handle: RawHandle) -> io::Result<DeviceNotification> {
+        let mut filter: DEV_BROADCAST_HANDLE = unsafe { mem::zeroed() };
+        filter.dbch_size = mem::size_of::<DEV_BROADCAST_HANDLE>() as u32;
+        filter.dbch_devicetype = DBT_DEVTYP_HANDLE;
+        filter.dbch_handle = handle as _;
+        unsafe { self.register_device_notification(&mut filter as *mut _ as *mut _) }
+    }
+
+    /// The system copies the filter, so it only has to live for the duration of the call.
+    unsafe fn register_device_notification(
+        &self,
+        filter: *mut ::std::os::raw::c_void,
+    ) -> io::Result<DeviceNotification> {
+        let handle = winuser::RegisterDeviceNotificationW(
+            self.handle as _,
+            filter as _,
+            winuser::DEVICE_NOTIFY_SERVICE_HANDLE,
+        );
+        if handle.is_null() {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(DeviceNotification(handle))
         }
     }
 }
@@ -58,33 +147,36 @@ impl ServiceStatusHandle {
 // https://msdn.microsoft.com/en-us/library/windows/desktop/ms686241(v=vs.85).aspx
 unsafe impl Send for ServiceStatusHandle {}
 
-/// Abstraction over the return value of service control handler.
-/// The meaning of each of variants in this enum depends on the type of received event.
+/// A subscription to device events, created by [`ServiceStatusHandle::subscribe_device_interface`]
+/// or [`ServiceStatusHandle::subscribe_handle`].
 ///
-/// See the "Return value" section of corresponding MSDN article for more info:
-///
-/// <https://msdn.microsoft.com/en-us/library/windows/desktop/ms683241(v=vs.85).aspx>
+/// Dropping the `DeviceNotification` ends the subscription.
 #[derive(Debug)]
-pub enum ServiceControlHandlerResult {
-    /// Either used to aknowledge the call or grant the permission in advanced events.
-    NoError,
-    /// The received event is not implemented.
-    NotImplemented,
-    /// This variant is used to deny permission and return the reason error code in advanced
-    /// events.
-    Other(u32),
-}
+pub struct DeviceNotification(winuser::HDEVNOTIFY);
 
-impl ServiceControlHandlerResult {
-    pub fn to_raw(&self) -> u32 {
-        match *self {
-            ServiceControlHandlerResult::NoError => NO_ERROR,
-            ServiceControlHandlerResult::NotImplemented => ERROR_CALL_NOT_IMPLEMENTED,
-            ServiceControlHandlerResult::Other(code) => code,
+impl DeviceNotification {
+    /// End the subscription, reporting the error that dropping it would ignore.
+    pub fn unregister(self) -> io::Result<()> {
+        let result = unsafe { winuser::UnregisterDeviceNotification(self.0) };
+        mem::forget(self);
+        if result == 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
         }
     }
 }
 
+impl Drop for DeviceNotification {
+    fn drop(&mut self) {
+        unsafe { winuser::UnregisterDeviceNotification(self.0) };
+    }
+}
+
+// The notification handle can be unregistered from any thread.
+unsafe impl Send for DeviceNotification {}
+unsafe impl Sync for DeviceNotification {}
+
 /// Register a closure for receiving service events.
 ///
 /// Returns [`ServiceStatusHandle`] that can be used to report the service status back to the
@@ -121,35 +213,20 @@ where
     S: AsRef<OsStr>,
     F: Fn(ServiceControl) -> ServiceControlHandlerResult + 'static + Send,
 {
-    // Move closure data on heap.
-    // The Box<HandlerFn> is a trait object and is stored on stack at this point.
-    let heap_event_handler = Box::new(event_handler) as Box<HandlerFn>;
-
-    // Box again to move trait object to heap.
-    let boxed_event_handler: Box<Box<HandlerFn>> = Box::new(heap_event_handler);
-
-    // Important: leak the Box<Box<HandlerFn>> which will be released in `service_control_handler`.
-    let context = Box::into_raw(boxed_event_handler) as *mut ::std::os::raw::c_void;
-
-    let service_name =
-        WideCString::from_str(service_name).chain_err(|| ErrorKind::InvalidServiceName)?;
-    let status_handle = unsafe {
-        winsvc::RegisterServiceCtrlHandlerExW(
-            service_name.as_ptr(),
-            Some(service_control_handler),
-            context,
-        )
-    };
-
-    if status_handle.is_null() {
-        Err(io::Error::last_os_error().into())
-    } else {
-        Ok(ServiceStatusHandle::from_handle(status_handle))
-    }
+    register_handler(
+        service_name,
+        Box::new(move |control, _, _| event_handler(control)),
+    )
 }
 
 
-/// Register a closure for receiving service events.
+/// Register a closure for receiving service events along with the raw event type and event data
+/// that the system passed with them.
+///
+/// The events are decoded into [`ServiceControl`] like with [`register`], including the device
+/// events of the subscriptions made with [`ServiceStatusHandle::subscribe_device_interface`] and
+/// [`ServiceStatusHandle::subscribe_handle`], so the raw data is only needed to read parts of the
+/// events that are not decoded.
 ///
 /// Returns [`ServiceStatusHandle`] that can be used to report the service status back to the
 /// system.
@@ -158,6 +235,7 @@ where
 ///
 /// ```rust,no_run
 /// use std::ffi::OsString;
+/// use windows_service::device_event::DeviceEvent;
 /// use windows_service::service::ServiceControl;
 /// use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
 ///
@@ -168,16 +246,20 @@ where
 /// }
 ///
 /// fn run_service() -> windows_service::Result<()> {
-///     let event_handler = move |control_event, event_type, event_data| -> ServiceControlHandlerResult {
+///     let event_handler = move |control_event, _event_type, _event_data| {
 ///         match control_event {
-///             ServiceControl::DeviceEvent => {
-///                 let dbh: *mut DEV_BROADCAST_HDR = event_data as *mut DEV_BROADCAST_HDR;
+///             ServiceControl::DeviceEvent(DeviceEvent::Arrival(_device)) => {
+///                 // Open the device...
 ///                 ServiceControlHandlerResult::NoError
-///             },
+///             }
+///             ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
 ///             _ => ServiceControlHandlerResult::NotImplemented,
 ///         }
 ///     };
-///     let status_handle = service_control_handler::register("my_service_name", event_handler)?;
+///     let status_handle =
+///         service_control_handler::register_with_event_data("my_service_name", event_handler)?;
+///     let usb_device = "a5dcbf10-6530-11d2-901f-00c04fb951ed".parse()?;
+///     let _notification = status_handle.subscribe_device_interface(usb_device)?;
 ///     Ok(())
 /// }
 ///
@@ -188,100 +270,70 @@ pub fn register_with_event_data<S, F>(service_name: S, event_handler: F, ) -> Re
         S: AsRef<OsStr>,
         F: Fn(ServiceControl, u32, *mut ::std::os::raw::c_void) -> ServiceControlHandlerResult + 'static + Send,
 {
-    // Move closure data on heap.
-    // The Box<HandlerFn> is a trait object and is stored on stack at this point.
-    let heap_event_handler = Box::new(event_handler) as Box<HandlerFnWithData>;
-
-    // Box again to move trait object to heap.
-    let boxed_event_handler: Box<Box<HandlerFnWithData>> = Box::new(heap_event_handler);
+    register_handler(service_name, Box::new(event_handler))
+}
 
-    // Important: leak the Box<Box<HandlerFn>> which will be released in `service_control_handler`.
-    let context = Box::into_raw(boxed_event_handler) as *mut ::std::os::raw::c_void;
+/// Register for receiving service events as a stream.
+///
+/// The event handler that is registered with the system forwards every event to the returned
+/// stream and reports it as handled with [`ServiceControlHandlerResult::NoError`]. Since the
+/// system only sends the events the service accepts, accept only the events the service is ready
+/// to handle through [`ServiceStatus::controls_accepted`]. The stream ends once the service
+/// reports the [`ServiceState::Stopped`] state through the returned [`ServiceStatusHandle`], so
+/// keep receiving from it while stopping. Requires the `async` feature.
+///
+/// Returns [`ServiceStatusHandle`] that can be used to report the service status back to the
+/// system, along with the stream of events.
+///
+/// # Example
+///
+/// ```rust,edition2018,no_run
+/// use windows_service::service::ServiceControl;
+/// use windows_service::service_control_handler;
+///
+/// async fn run_service() -> windows_service::Result<()> {
+///     let (status_handle, mut controls) = service_control_handler::register_stream("my_service")?;
+///     // Tell the system that the service is running...
+///     while let Some(control) = controls.recv().await {
+///         if control == ServiceControl::Stop {
+///             // Stop the service and report the `Stopped` state, which ends the stream...
+///         }
+///     }
+///     Ok(())
+/// }
+/// ```
+///
+/// [`ServiceStatus::controls_accepted`]: ::service::ServiceStatus::controls_accepted
+#[cfg(feature = "async")]
+pub fn register_stream<S>(service_name: S) -> Result<(ServiceStatusHandle, ServiceControlStream)>
+where
+    S: AsRef<OsStr>,
+{
+    let (sender, stream) = asynchronous::control_channel();
+    let status_handle = register_handler(service_name, sender.into_handler())?;
+    Ok((status_handle, stream))
+}
 
+/// Register the handler with the system, retaining its context for as long as the system may
+/// call it.
+fn register_handler<S: AsRef<OsStr>>(
+    service_name: S,
+    event_handler: Box<HandlerFn>,
+) -> Result<ServiceStatusHandle> {
     let service_name =
         WideCString::from_str(service_name).chain_err(|| ErrorKind::InvalidServiceName)?;
+    let context = HandlerContext::new(event_handler);
     let status_handle = unsafe {
         winsvc::RegisterServiceCtrlHandlerExW(
             service_name.as_ptr(),
-            Some(service_control_handler_with_event_data),
-            context,
+            Some(handler_context::service_control_handler),
+            HandlerContext::retain(&context),
         )
     };
 
     if status_handle.is_null() {
         Err(io::Error::last_os_error().into())
     } else {
-        Ok(ServiceStatusHandle::from_handle(status_handle))
-    }
-}
-
-/// Alias for control event handler closure.
-type HandlerFn = Fn(ServiceControl) -> ServiceControlHandlerResult;
-
-/// Static service control handler
-#[allow(dead_code)]
-extern "system" fn service_control_handler(
-    control: u32,
-    _event_type: u32,
-    _event_data: *mut ::std::os::raw::c_void,
-    context: *mut ::std::os::raw::c_void,
-) -> u32 {
-    // Important: cast context to &mut Box<HandlerFn> without taking ownership.
-    let handler_fn = unsafe { &mut *(context as *mut Box<HandlerFn>) };
-
-    match ServiceControl::from_raw(control) {
-        Ok(service_control) => {
-            let return_code = ((handler_fn)(service_control)).to_raw();
-
-            // Important: release context upon Stop, Shutdown or Preshutdown at the end of the
-            // service lifecycle.
-            match service_control {
-                ServiceControl::Stop | ServiceControl::Shutdown | ServiceControl::Preshutdown => {
-                    let _owned_boxed_handler: Box<Box<HandlerFn>> =
-                        unsafe { Box::from_raw(context as *mut Box<HandlerFn>) };
-                }
-                _ => (),
-            };
-
-            return_code
-        }
-
-        // Report all unknown control commands as unimplemented
-        Err(_) => ServiceControlHandlerResult::NotImplemented.to_raw(),
-    }
-}
-
-type HandlerFnWithData = Fn(ServiceControl, u32, *mut ::std::os::raw::c_void) -> ServiceControlHandlerResult;
-
-/// Static service control handler
-#[allow(dead_code)]
-extern "system" fn service_control_handler_with_event_data(
-    control: u32,
-    event_type: u32,
-    event_data: *mut ::std::os::raw::c_void,
-    context: *mut ::std::os::raw::c_void,
-) -> u32 {
-    // Important: cast context to &mut Box<HandlerFn> without taking ownership.
-    let handler_fn = unsafe { &mut *(context as *mut Box<HandlerFnWithData>) };
-
-    match ServiceControl::from_raw(control) {
-        Ok(service_control) => {
-            let return_code = ((handler_fn)(service_control, event_type, event_data)).to_raw();
-
-            // Important: release context upon Stop, Shutdown or Preshutdown at the end of the
-            // service lifecycle.
-            match service_control {
-                ServiceControl::Stop | ServiceControl::Shutdown | ServiceControl::Preshutdown => {
-                    let _owned_boxed_handler: Box<Box<HandlerFnWithData>> =
-                        unsafe { Box::from_raw(context as *mut Box<HandlerFnWithData>) };
-                }
-                _ => (),
-            };
-
-            return_code
-        }
-
-        // Report all unknown control commands as unimplemented
-        Err(_) => ServiceControlHandlerResult::NotImplemented.to_raw(),
+        Ok(ServiceStatusHandle::from_handle(status_handle, context))
     }
 }
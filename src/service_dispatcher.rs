@@ -1,11 +1,17 @@
 use std::ffi::{OsStr, OsString};
-use std::{io, ptr};
+use std::sync::Arc;
+use std::{fmt, io, ptr};
 
-use widestring::{WideCStr, WideCString};
+use widestring::WideCString;
 use winapi::um::winsvc;
 
+use handler_context::HandlerContext;
+use service_entry::{self, ServiceMainFn};
 use {ErrorKind, Result, ResultExt};
 
+#[doc(hidden)]
+pub use service_entry::parse_raw_arguments;
+
 /// A macro to generate an entry point function (aka "service_main") for Windows service.
 ///
 /// The `$function_name` function parses service arguments provided by the system
@@ -48,6 +54,10 @@ macro_rules! define_windows_service {
     };
 }
 
+/// Signature of the entry point function of a service, as generated by
+/// [`define_windows_service!`].
+pub type ServiceMainFunction = extern "system" fn(u32, *mut *mut u16);
+
 /// Start service control dispatcher.
 ///
 /// Once started the service control dispatcher blocks the current thread execution
@@ -57,6 +67,8 @@ macro_rules! define_windows_service {
 ///
 /// On failure: immediately returns an error, no threads are spawned.
 ///
+/// Use [`ServiceDispatcher`] to host several services in the same process.
+///
 /// # Example
 ///
 /// ```rust,no_run
@@ -81,42 +93,202 @@ macro_rules! define_windows_service {
 /// }
 /// ```
 ///
-pub fn start<T: AsRef<OsStr>>(
-    service_name: T,
-    service_main: extern "system" fn(u32, *mut *mut u16),
-) -> Result<()> {
-    let service_name =
-        WideCString::from_str(service_name).chain_err(|| ErrorKind::InvalidServiceName)?;
-    let service_table: &[winsvc::SERVICE_TABLE_ENTRYW] = &[
-        winsvc::SERVICE_TABLE_ENTRYW {
-            lpServiceName: service_name.as_ptr(),
-            lpServiceProc: Some(service_main),
-        },
-        // the last item has to be { null, null }
-        winsvc::SERVICE_TABLE_ENTRYW {
-            lpServiceName: ptr::null(),
-            lpServiceProc: None,
-        },
-    ];
-
-    let result = unsafe { winsvc::StartServiceCtrlDispatcherW(service_table.as_ptr()) };
-    if result == 0 {
-        Err(io::Error::last_os_error().into())
-    } else {
-        Ok(())
-    }
+pub fn start<T: AsRef<OsStr>>(service_name: T, service_main: ServiceMainFunction) -> Result<()> {
+    ServiceDispatcher::new()
+        .service(service_name, service_main)
+        .start()
 }
 
-/// Parse raw arguments received in `service_main` into `Vec<OsString>`.
+/// Start service control dispatcher with a service implemented by a closure.
 ///
-/// This is an implementation detail and *should not* be called directly!
-#[doc(hidden)]
-pub unsafe fn parse_raw_arguments(argc: u32, argv: *mut *mut u16) -> Vec<OsString> {
-    (0..argc)
-        .into_iter()
-        .map(|i| {
-            let array_element_ptr: *mut *mut u16 = argv.offset(i as isize);
-            WideCStr::from_ptr_str(*array_element_ptr).to_os_string()
-        })
-        .collect()
+/// Same as [`start`], except that the service is implemented by a closure receiving the parsed
+/// service arguments, without the need for [`define_windows_service!`]. The closure can capture
+/// the configuration of the service instead of reading it from globals.
+///
+/// Use [`ServiceDispatcher::service_fn`] to host several services in the same process.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// extern crate windows_service;
+///
+/// use windows_service::service_dispatcher;
+///
+/// fn main() -> windows_service::Result<()> {
+///     let log_path = std::env::temp_dir().join("myservice.log");
+///
+///     // Start the service, blocking this thread until the service is stopped.
+///     service_dispatcher::run("myservice", move |arguments| {
+///         // The entry point where execution will start on a background thread, with access to
+///         // `log_path`.
+///     })
+/// }
+/// ```
+pub fn run<T, F>(service_name: T, service_main: F) -> Result<()>
+where
+    T: AsRef<OsStr>,
+    F: Fn(Vec<OsString>) + Send + Sync + 'static,
+{
+    ServiceDispatcher::new()
+        .service_fn(service_name, service_main)
+        .start()
+}
+
+/// The services hosted by a process, started together by the service control dispatcher.
+///
+/// An executable can host several services that are installed with the
+/// [`ServiceType::Win32ShareProcess`] type. The system starts the process once, and calls the
+/// entry point of each service on its own thread when the service is started. Each service
+/// registers its own event handler with [`service_control_handler::register`] under its own name,
+/// and reports its own status through the returned handle.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// #[macro_use]
+/// extern crate windows_service;
+///
+/// use std::ffi::OsString;
+/// use windows_service::service_dispatcher::ServiceDispatcher;
+///
+/// define_windows_service!(ffi_collector_main, collector_main);
+/// define_windows_service!(ffi_uploader_main, uploader_main);
+///
+/// fn collector_main(arguments: Vec<OsString>) {
+///     // Register the event handler for "collector" and run the service...
+/// }
+///
+/// fn uploader_main(arguments: Vec<OsString>) {
+///     // Register the event handler for "uploader" and run the service...
+/// }
+///
+/// fn main() -> windows_service::Result<()> {
+///     // Block this thread until all the services of the process are stopped.
+///     ServiceDispatcher::new()
+///         .service("collector", ffi_collector_main)
+///         .service("uploader", ffi_uploader_main)
+///         .start()
+/// }
+/// ```
+///
+/// [`ServiceType::Win32ShareProcess`]: ::service::ServiceType::Win32ShareProcess
+/// [`service_control_handler::register`]: ::service_control_handler::register
+#[derive(Debug, Clone, Default)]
+pub struct ServiceDispatcher {
+    services: Vec<(OsString, ServiceMain)>,
+}
+
+/// The entry point of a service added to the dispatcher.
+#[derive(Clone)]
+enum ServiceMain {
+    Function(ServiceMainFunction),
+    Closure(Arc<ServiceMainFn>),
+}
+
+impl fmt::Debug for ServiceMain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ServiceMain::Function(function) => f.debug_tuple("Function").field(&function).finish(),
+            ServiceMain::Closure(_) => f.write_str("Closure"),
+        }
+    }
+}
+
+impl ServiceDispatcher {
+    /// Create a dispatcher without any services.
+    pub fn new() -> Self {
+        ServiceDispatcher::default()
+    }
+
+    /// Add a service with the given name and entry point, usually generated by
+    /// [`define_windows_service!`].
+    pub fn service<T: AsRef<OsStr>>(
+        mut self,
+        service_name: T,
+        service_main: ServiceMainFunction,
+    ) -> Self {
+        self.services.push((
+            service_name.as_ref().to_os_string(),
+            ServiceMain::Function(service_main),
+        ));
+        self
+    }
+
+    /// Add a service with the given name, implemented by a closure receiving the parsed service
+    /// arguments.
+    ///
+    /// The closure is called on a background thread each time the system starts the service, and
+    /// has the same responsibilities as the function passed to [`define_windows_service!`].
+    ///
+    /// The closure is found by the name the system starts the service with, ignoring case. When
+    /// it is the only closure of the dispatcher, it is called whatever that name, since a process
+    /// hosting a single service may be installed under another name. A service without a closure
+    /// is reported as stopped with `ERROR_SERVICE_NOT_IN_EXE`.
+    pub fn service_fn<T, F>(mut self, service_name: T, service_main: F) -> Self
+    where
+        T: AsRef<OsStr>,
+        F: Fn(Vec<OsString>) + Send + Sync + 'static,
+    {
+        self.services.push((
+            service_name.as_ref().to_os_string(),
+            ServiceMain::Closure(Arc::new(service_main)),
+        ));
+        self
+    }
+
+    /// Start the service control dispatcher with the added services.
+    ///
+    /// Blocks the current thread until all the services are stopped. Upon successful
+    /// initialization, the system calls the entry point of each service that is started on a
+    /// background thread.
+    ///
+    /// On failure: immediately returns an error, no threads are spawned.
+    pub fn start(self) -> Result<()> {
+        let service_names = self
+            .services
+            .iter()
+            .map(|(name, _)| {
+                WideCString::from_str(name).chain_err(|| ErrorKind::InvalidServiceName)
+            })
+            .collect::<Result<Vec<WideCString>>>()?;
+        let service_table = service_names
+            .iter()
+            .zip(&self.services)
+            .map(|(name, (_, service_main))| winsvc::SERVICE_TABLE_ENTRYW {
+                lpServiceName: name.as_ptr(),
+                lpServiceProc: Some(match *service_main {
+                    ServiceMain::Function(function) => function,
+                    ServiceMain::Closure(_) => service_entry::service_main,
+                }),
+            })
+            // the last item has to be { null, null }
+            .chain(Some(winsvc::SERVICE_TABLE_ENTRYW {
+                lpServiceName: ptr::null(),
+                lpServiceProc: None,
+            }))
+            .collect::<Vec<_>>();
+
+        // Keep the closures registered until the system no longer starts the services.
+        let registration = service_entry::register(
+            self.services
+                .into_iter()
+                .filter_map(|(name, service_main)| match service_main {
+                    ServiceMain::Closure(closure) => Some((name, closure)),
+                    ServiceMain::Function(_) => None,
+                })
+                .collect(),
+        );
+
+        let result = unsafe { winsvc::StartServiceCtrlDispatcherW(service_table.as_ptr()) };
+
+        drop(registration);
+        // The system no longer calls the event handlers once the dispatcher returns.
+        HandlerContext::release_retained();
+
+        if result == 0 {
+            Err(io::Error::last_os_error().into())
+        } else {
+            Ok(())
+        }
+    }
 }
@@ -0,0 +1,289 @@
+//! Entry points of the services implemented as closures.
+//!
+//! The system starts a service by calling the entry point from the service table, passing the
+//! name of the service as the first argument. Closures cannot be passed to the system directly, so
+//! they are registered here under the name of their service for the duration of the service
+//! control dispatcher, and the table points to the [`service_main`] trampoline, which looks up the
+//! closure by that name and calls it with the parsed arguments.
+//!
+//! The system passes the name the service was installed with, which a process hosting a single
+//! service may not know in advance, so the only registered closure is called whatever the name.
+//! If no closure can be found, the service is reported as stopped with
+//! `ERROR_SERVICE_NOT_IN_EXE` so that starting it fails right away.
+
+use std::ffi::{OsStr, OsString};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use widestring::WideCStr;
+
+/// Alias for the closure implementing a service, receiving the service arguments.
+pub(crate) type ServiceMainFn = dyn Fn(Vec<OsString>) + Send + Sync;
+
+/// Closures registered for the running service control dispatcher, by service name.
+static REGISTERED_SERVICES: Mutex<Vec<(OsString, Arc<ServiceMainFn>)>> = Mutex::new(Vec::new());
+
+/// Guard keeping closures registered until it is dropped.
+pub(crate) struct Registration {
+    services: Vec<Arc<ServiceMainFn>>,
+}
+
+/// Register the closures under their service names, until the returned guard is dropped.
+pub(crate) fn register(services: Vec<(OsString, Arc<ServiceMainFn>)>) -> Registration {
+    let registration = Registration {
+        services: services
+            .iter()
+            .map(|(_, service_main)| service_main.clone())
+            .collect(),
+    };
+    lock(&REGISTERED_SERVICES).extend(services);
+    registration
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        lock(&REGISTERED_SERVICES).retain(|(_, registered)| {
+            !self
+                .services
+                .iter()
+                .any(|service_main| Arc::ptr_eq(service_main, registered))
+        });
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Find the closure registered for the service, comparing names the way the system does,
+/// ignoring case. Falls back to the only registered closure when no name matches.
+fn find(service_name: &OsStr) -> Option<Arc<ServiceMainFn>> {
+    let services = lock(&REGISTERED_SERVICES);
+    services
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(service_name))
+        .or_else(|| match services.as_slice() {
+            [only] => Some(only),
+            _ => None,
+        })
+        .map(|(_, service_main)| service_main.clone())
+}
+
+/// Call the closure registered for the service named by the first argument.
+///
+/// Returns `false` when no closure is registered for the service.
+fn dispatch(arguments: Vec<OsString>) -> bool {
+    let service_main = match arguments
+        .first()
+        .and_then(|service_name| find(service_name))
+    {
+        Some(service_main) => service_main,
+        None => return false,
+    };
+    // The registry is not locked while the service runs, so that other services can start.
+    service_main(arguments);
+    true
+}
+
+/// Static entry point of the services implemented as closures.
+pub(crate) extern "system" fn service_main(argc: u32, argv: *mut *mut u16) {
+    let arguments = unsafe { parse_raw_arguments(argc, argv) };
+    let service_name = arguments.first().cloned();
+    if !dispatch(arguments) {
+        if let Some(service_name) = service_name {
+            report_not_in_process(&service_name);
+        }
+    }
+}
+
+/// Report the service that has no closure as stopped, instead of leaving the system waiting for
+/// it to report its status until the start times out.
+#[cfg(windows)]
+fn report_not_in_process(service_name: &OsStr) {
+    use service::{
+        ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType,
+    };
+    use service_control_handler::{self, ServiceControlHandlerResult};
+    use std::time::Duration;
+    use sys::winerror::ERROR_SERVICE_NOT_IN_EXE;
+
+    let status_handle = match service_control_handler::register(service_name, |_| {
+        ServiceControlHandlerResult::NotImplemented
+    }) {
+        Ok(status_handle) => status_handle,
+        Err(_) => return,
+    };
+    // Only a process hosting several services gets here, a single closure is always found.
+    let _ = status_handle.set_service_status(ServiceStatus {
+        service_type: ServiceType::Win32ShareProcess,
+        current_state: ServiceState::Stopped,
+        controls_accepted: ServiceControlAccept::empty(),
+        exit_code: ServiceExitCode::Win32(ERROR_SERVICE_NOT_IN_EXE),
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+    });
+}
+
+#[cfg(not(windows))]
+fn report_not_in_process(_service_name: &OsStr) {}
+
+/// Parse raw arguments received in `service_main` into `Vec<OsString>`.
+///
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub unsafe fn parse_raw_arguments(argc: u32, argv: *mut *mut u16) -> Vec<OsString> {
+    (0..argc)
+        .map(|i| {
+            let array_element_ptr: *mut *mut u16 = argv.offset(i as isize);
+            WideCStr::from_ptr_str(*array_element_ptr).to_os_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use widestring::WideCString;
+
+    /// Arguments laid out like the system passes them to the entry point.
+    struct RawArguments {
+        _strings: Vec<WideCString>,
+        pointers: Vec<*mut u16>,
+    }
+
+    impl RawArguments {
+        fn new(arguments: &[&str]) -> Self {
+            let strings = arguments
+                .iter()
+                .map(|argument| WideCString::from_str(argument).unwrap())
+                .collect::<Vec<_>>();
+            let pointers = strings
+                .iter()
+                .map(|string| string.as_ptr() as *mut u16)
+                .collect();
+            RawArguments {
+                _strings: strings,
+                pointers,
+            }
+        }
+
+        fn argc(&self) -> u32 {
+            self.pointers.len() as u32
+        }
+
+        fn argv(&mut self) -> *mut *mut u16 {
+            self.pointers.as_mut_ptr()
+        }
+    }
+
+    /// Serializes the tests that register closures, since the registry is shared by the whole
+    /// process and a single registered closure is called for any service name.
+    static REGISTRY_LOCK: Mutex<()> = Mutex::new(());
+
+    fn register_channel(service_name: &str) -> (Registration, mpsc::Receiver<Vec<OsString>>) {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let registration = register(vec![(
+            OsString::from(service_name),
+            Arc::new(move |arguments| lock(&sender).send(arguments).unwrap()),
+        )]);
+        (registration, receiver)
+    }
+
+    #[test]
+    fn test_parse_raw_arguments() {
+        let mut raw_arguments = RawArguments::new(&["myservice", "--verbose", "ünïcode"]);
+        let arguments = unsafe { parse_raw_arguments(raw_arguments.argc(), raw_arguments.argv()) };
+        assert_eq!(
+            arguments,
+            vec![
+                OsString::from("myservice"),
+                OsString::from("--verbose"),
+                OsString::from("ünïcode"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_raw_arguments_empty() {
+        let arguments = unsafe { parse_raw_arguments(0, ::std::ptr::null_mut()) };
+        assert!(arguments.is_empty());
+    }
+
+    #[test]
+    fn test_service_main_calls_registered_closure() {
+        let _registry = lock(&REGISTRY_LOCK);
+        let (_registration, receiver) = register_channel("entry_closure_service");
+        let mut raw_arguments = RawArguments::new(&["entry_closure_service", "--flag"]);
+
+        service_main(raw_arguments.argc(), raw_arguments.argv());
+
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            vec![
+                OsString::from("entry_closure_service"),
+                OsString::from("--flag"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dispatch_ignores_case_of_service_name() {
+        let _registry = lock(&REGISTRY_LOCK);
+        let (_registration, receiver) = register_channel("entry_case_service");
+
+        assert!(dispatch(vec![OsString::from("Entry_Case_Service")]));
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            vec![OsString::from("Entry_Case_Service")]
+        );
+    }
+
+    #[test]
+    fn test_dispatch_selects_service_by_name() {
+        let _registry = lock(&REGISTRY_LOCK);
+        let (_first, first_receiver) = register_channel("entry_first_service");
+        let (_second, second_receiver) = register_channel("entry_second_service");
+
+        assert!(dispatch(vec![OsString::from("entry_second_service")]));
+        assert!(first_receiver.try_recv().is_err());
+        assert!(second_receiver.try_recv().is_ok());
+    }
+
+    #[test]
+    fn test_dropping_registration_unregisters_closures() {
+        let _registry = lock(&REGISTRY_LOCK);
+        let (registration, receiver) = register_channel("entry_dropped_service");
+        drop(registration);
+
+        assert!(!dispatch(vec![OsString::from("entry_dropped_service")]));
+        assert!(!dispatch(Vec::new()));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_dispatch_falls_back_to_only_closure() {
+        let _registry = lock(&REGISTRY_LOCK);
+        let (_registration, receiver) = register_channel("entry_table_name");
+
+        // The system passes the name the service was installed with.
+        assert!(dispatch(vec![OsString::from("entry_installed_name")]));
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            vec![OsString::from("entry_installed_name")]
+        );
+    }
+
+    #[test]
+    fn test_dispatch_without_matching_closure() {
+        let _registry = lock(&REGISTRY_LOCK);
+        let (_first, first_receiver) = register_channel("entry_first_shared_service");
+        let (_second, second_receiver) = register_channel("entry_second_shared_service");
+
+        assert!(!dispatch(vec![OsString::from("entry_unknown_service")]));
+        assert!(first_receiver.try_recv().is_err());
+        assert!(second_receiver.try_recv().is_err());
+    }
+}
@@ -1,22 +1,24 @@
 use std::borrow::Cow;
-use std::ffi::OsStr;
-use std::{io, ptr};
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::vec;
 
 use widestring::{NulError, WideCString, WideString};
-use winapi::um::winsvc;
-use winapi::um::winnt;
-use winapi::shared::winerror;
 
-use sc_handle::ScHandle;
-use service::{Service, ServiceAccess, ServiceInfo};
+#[cfg(windows)]
+use backend::win32::Win32ServiceManager;
+use backend::ServiceManagerBackend;
+use service::{
+    Service, ServiceAccess, ServiceConfig, ServiceDependency, ServiceEntryExt, ServiceInfo,
+    ServiceListing, ServiceStateFilter, ServiceType, ServiceTypeFilter,
+};
 use shell_escape;
+use sys::winsvc;
+use watch::ServiceWatch;
 
 use {ErrorKind, Result, ResultExt};
-use widestring::WideCStr;
-use std;
-use service::*;
-use std::mem;
-use service::EnumListServiceResult;
 
 bitflags! {
     /// Flags describing access permissions for [`ServiceManager`].
@@ -32,9 +34,71 @@ bitflags! {
     }
 }
 
+/// Selects the services returned by [`ServiceManager::enumerate`].
+///
+/// # Example
+///
+/// ```rust
+/// use windows_service::service::{ServiceStateFilter, ServiceTypeFilter};
+/// use windows_service::service_manager::ServiceFilter;
+///
+/// let running_drivers = ServiceFilter::new()
+///     .service_type(ServiceTypeFilter::DRIVER)
+///     .state(ServiceStateFilter::Active);
+/// assert_eq!(running_drivers.load_order_group, None);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceFilter {
+    /// The types of services to return.
+    pub service_type: ServiceTypeFilter,
+
+    /// The states of services to return.
+    pub state: ServiceStateFilter,
+
+    /// Only return the services of this load ordering group, or the services that do not belong
+    /// to any group if the name is empty. `None` returns services regardless of their group.
+    pub load_order_group: Option<OsString>,
+}
+
+impl ServiceFilter {
+    /// Create a filter that passes services and drivers of any state and group.
+    pub fn new() -> Self {
+        ServiceFilter::default()
+    }
+
+    /// Only return services of the given types.
+    pub fn service_type(mut self, service_type: ServiceTypeFilter) -> Self {
+        self.service_type = service_type;
+        self
+    }
+
+    /// Only return services in the given states.
+    pub fn state(mut self, state: ServiceStateFilter) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Only return services of the given load ordering group. Pass an empty name to return the
+    /// services that do not belong to any group.
+    pub fn load_order_group<T: Into<OsString>>(mut self, load_order_group: T) -> Self {
+        self.load_order_group = Some(load_order_group.into());
+        self
+    }
+}
+
+impl Default for ServiceFilter {
+    fn default() -> Self {
+        ServiceFilter {
+            service_type: ServiceTypeFilter::WIN32 | ServiceTypeFilter::DRIVER,
+            state: ServiceStateFilter::All,
+            load_order_group: None,
+        }
+    }
+}
+
 /// Service manager.
 pub struct ServiceManager {
-    manager_handle: ScHandle,
+    backend: Box<dyn ServiceManagerBackend>,
 }
 
 impl ServiceManager {
@@ -42,32 +106,28 @@ impl ServiceManager {
     ///
     /// # Arguments
     ///
-    /// * `machine`  - The name of machine.
-    ///                Pass `None` to connect to local machine.
-    /// * `database` - The name of database to connect to.
-    ///                Pass `None` to connect to active database.
+    /// * `machine`  - The name of machine. Pass `None` to connect to local machine.
+    /// * `database` - The name of database to connect to. Pass `None` to connect to active
+    ///   database.
     ///
+    #[cfg(windows)]
     fn new<M: AsRef<OsStr>, D: AsRef<OsStr>>(
         machine: Option<M>,
         database: Option<D>,
         request_access: ServiceManagerAccess,
     ) -> Result<Self> {
-        let machine_name = to_wide(machine).chain_err(|| ErrorKind::InvalidMachineName)?;
-        let database_name = to_wide(database).chain_err(|| ErrorKind::InvalidDatabaseName)?;
-        let handle = unsafe {
-            winsvc::OpenSCManagerW(
-                machine_name.map_or(ptr::null(), |s| s.as_ptr()),
-                database_name.map_or(ptr::null(), |s| s.as_ptr()),
-                request_access.bits(),
-            )
-        };
-
-        if handle.is_null() {
-            Err(io::Error::last_os_error().into())
-        } else {
-            Ok(ServiceManager {
-                manager_handle: unsafe { ScHandle::new(handle) },
-            })
+        let backend = Win32ServiceManager::connect(machine, database, request_access)?;
+        Ok(ServiceManager::with_backend(backend))
+    }
+
+    /// Create a service manager that delegates all operations to the given backend.
+    ///
+    /// See [`FakeScm`] for a backend that does not require a running service control manager.
+    ///
+    /// [`FakeScm`]: super::backend::FakeScm
+    pub fn with_backend<B: ServiceManagerBackend + 'static>(backend: B) -> Self {
+        ServiceManager {
+            backend: Box::new(backend),
         }
     }
 
@@ -75,10 +135,11 @@ impl ServiceManager {
     ///
     /// # Arguments
     ///
-    /// * `database`       - The name of database to connect to.
-    ///                      Pass `None` to connect to active database.
+    /// * `database`       - The name of database to connect to. Pass `None` to connect to active
+    ///   database.
     /// * `request_access` - Desired access permissions.
     ///
+    #[cfg(windows)]
     pub fn local_computer<D: AsRef<OsStr>>(
         database: Option<D>,
         request_access: ServiceManagerAccess,
@@ -91,10 +152,11 @@ impl ServiceManager {
     /// # Arguments
     ///
     /// * `machine`        - The name of remote machine.
-    /// * `database`       - The name of database to connect to.
-    ///                      Pass `None` to connect to active database.
+    /// * `database`       - The name of database to connect to. Pass `None` to connect to active
+    ///   database.
     /// * `request_access` - desired access permissions.
     ///
+    #[cfg(windows)]
     pub fn remote_computer<M: AsRef<OsStr>, D: AsRef<OsStr>>(
         machine: M,
         database: Option<D>,
@@ -105,23 +167,27 @@ impl ServiceManager {
 
     /// Create a service.
     ///
+    /// The optional [`ServiceInfo::settings`] are applied right after the service is created. If
+    /// any of them fails, the service is deleted and the error is returned.
+    ///
     /// # Arguments
     ///
     /// * `service_info`   - The service information that will be saved to the system services
-    ///                      registry.
-    /// * `service_access` - Desired access permissions for the returned [`Service`]
-    ///                      instance.
+    ///   registry.
+    /// * `service_access` - Desired access permissions for the returned [`Service`] instance.
     ///
     /// # Example
     ///
     /// ```rust,no_run
     /// use std::ffi::OsString;
-    /// use std::path::PathBuf;
+    /// use std::path::{Path, PathBuf};
     /// use windows_service::service::{
-    ///     ServiceAccess, ServiceErrorControl, ServiceInfo, ServiceStartType, ServiceType,
+    ///     ServiceAccess, ServiceDependency, ServiceErrorControl, ServiceInfo, ServiceSetting,
+    ///     ServiceStartType, ServiceType,
     /// };
     /// use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
     ///
+    /// # #[cfg(windows)]
     /// fn main() -> windows_service::Result<()> {
     ///     let manager =
     ///         ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CREATE_SERVICE)?;
@@ -134,69 +200,27 @@ impl ServiceManager {
     ///         error_control: ServiceErrorControl::Normal,
     ///         executable_path: PathBuf::from(r"C:\path\to\my\service.exe"),
     ///         launch_arguments: vec![],
+    ///         load_order_group: None,
+    ///         dependencies: vec![ServiceDependency::Service(OsString::from("Tcpip"))],
     ///         account_name: None, // run as System
     ///         account_password: None,
+    ///         settings: vec![ServiceSetting::Description(OsString::from("Does my work"))],
     ///     };
     ///
     ///     let my_service = manager.create_service(my_service_info, ServiceAccess::QUERY_STATUS)?;
     ///     Ok(())
     /// }
+    /// # #[cfg(not(windows))]
+    /// # fn main() {}
     /// ```
     pub fn create_service(
         &self,
         service_info: ServiceInfo,
         service_access: ServiceAccess,
     ) -> Result<Service> {
-        let service_name =
-            WideCString::from_str(service_info.name).chain_err(|| ErrorKind::InvalidServiceName)?;
-        let display_name = WideCString::from_str(service_info.display_name)
-            .chain_err(|| ErrorKind::InvalidDisplayName)?;
-        let account_name =
-            to_wide(service_info.account_name).chain_err(|| ErrorKind::InvalidAccountName)?;
-        let account_password =
-            to_wide(service_info.account_password).chain_err(|| ErrorKind::InvalidAccountPassword)?;
-
-        // escape executable path and arguments and combine them into single command
-        let executable_path = match service_info.service_type {
-            ServiceType::KernelDriver => Ok(WideString::from_str(&service_info.executable_path)),
-            _ => escape_wide(service_info.executable_path)
-        }.chain_err(|| ErrorKind::InvalidExecutablePath)?;
-
-        let mut launch_command_buffer = WideString::new();
-        launch_command_buffer.push(executable_path);
-
-        for launch_argument in service_info.launch_arguments.iter() {
-            let wide = escape_wide(launch_argument).chain_err(|| ErrorKind::InvalidLaunchArgument)?;
-
-            launch_command_buffer.push_str(" ");
-            launch_command_buffer.push(wide);
-        }
-
-        let launch_command = WideCString::from_wide_str(launch_command_buffer).unwrap();
-
-        let service_handle = unsafe {
-            winsvc::CreateServiceW(
-                self.manager_handle.raw_handle(),
-                service_name.as_ptr(),
-                display_name.as_ptr(),
-                service_access.bits(),
-                service_info.service_type.to_raw(),
-                service_info.start_type.to_raw(),
-                service_info.error_control.to_raw(),
-                launch_command.as_ptr(),
-                ptr::null(),     // load ordering group
-                ptr::null_mut(), // tag id within the load ordering group
-                ptr::null(),     // service dependencies
-                account_name.map_or(ptr::null(), |s| s.as_ptr()),
-                account_password.map_or(ptr::null(), |s| s.as_ptr()),
-            )
-        };
-
-        if service_handle.is_null() {
-            Err(io::Error::last_os_error().into())
-        } else {
-            Ok(Service::new(unsafe { ScHandle::new(service_handle) }))
-        }
+        self.backend
+            .create_service(&service_info, service_access)
+            .map(Service::new)
     }
 
     /// Open an existing service.
@@ -212,11 +236,14 @@ impl ServiceManager {
     /// use windows_service::service::ServiceAccess;
     /// use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
     ///
+    /// # #[cfg(windows)]
     /// # fn main() -> windows_service::Result<()> {
     /// let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
     /// let my_service = manager.open_service("my_service", ServiceAccess::QUERY_STATUS)?;
     /// # Ok(())
     /// # }
+    /// # #[cfg(not(windows))]
+    /// # fn main() {}
     /// ```
     ///
     pub fn open_service<T: AsRef<OsStr>>(
@@ -224,125 +251,336 @@ impl ServiceManager {
         name: T,
         request_access: ServiceAccess,
     ) -> Result<Service> {
-        let service_name = WideCString::from_str(name).chain_err(|| ErrorKind::InvalidServiceName)?;
-        let service_handle = unsafe {
-            winsvc::OpenServiceW(
-                self.manager_handle.raw_handle(),
-                service_name.as_ptr(),
-                request_access.bits(),
-            )
-        };
+        self.backend
+            .open_service(name.as_ref(), request_access)
+            .map(Service::new)
+    }
+
+    /// List all services and drivers in the database along with their configuration.
+    ///
+    /// Every service is opened with [`ServiceAccess::QUERY_CONFIG`] to query its configuration.
+    /// Failures to do so are reported per service in [`ServiceListing::config`] and
+    /// [`ServiceListing::failure_actions`], only a failure to enumerate the services fails the
+    /// whole listing. Use [`ServiceManager::enumerate`] to list a subset of the services, or to
+    /// skip querying their configuration.
+    pub fn list_services(&self) -> Result<Vec<ServiceListing>> {
+        self.enumerate(ServiceFilter::new())
+            .map(|entry| entry.map(|entry| self.listing(entry)))
+            .collect()
+    }
 
-        if service_handle.is_null() {
-            Err(io::Error::last_os_error().into())
-        } else {
-            Ok(Service::new(unsafe { ScHandle::new(service_handle) }))
+    fn listing(&self, entry: ServiceEntryExt) -> ServiceListing {
+        match self.open_service(&entry.name, ServiceAccess::QUERY_CONFIG) {
+            Ok(service) => ServiceListing {
+                config: service.query_config(),
+                failure_actions: service.failure_actions(),
+                entry,
+            },
+            Err(error) => ServiceListing {
+                entry,
+                failure_actions: Err(copy_error(&error)),
+                config: Err(error),
+            },
         }
     }
 
-    pub fn list_services(&self) -> Result<Vec<ServiceDetail>> {
-
-        let mut service_list: Vec<ServiceDetail> = vec![];
-
-        let mut pcb_bytes_needed = 0;
-        let mut lp_services_returned = 0;
-        let mut lp_resume_handle = 0;
-        unsafe {
-            winsvc::EnumServicesStatusExW(self.manager_handle.raw_handle(),
-                                          winsvc::SC_ENUM_PROCESS_INFO,
-                                  winnt::SERVICE_WIN32 | winnt::SERVICE_DRIVER,//SERVICE_TYPE_ALL,
-                                  winsvc::SERVICE_STATE_ALL,
-                                  std::ptr::null_mut(),
-                                  0,
-                                  &mut pcb_bytes_needed,
-                                  &mut lp_services_returned,
-                                  &mut lp_resume_handle,
-                                  std::ptr::null(),
-            )
-        };
-
-        let last_error = io::Error::last_os_error().raw_os_error().unwrap_or(0);
-
-        if winerror::ERROR_MORE_DATA as i32 == last_error {
-            let mut lp_services = vec![unsafe { std::mem::uninitialized() }; pcb_bytes_needed as usize];
-
-           unsafe {
-                winsvc::EnumServicesStatusExW(self.manager_handle.raw_handle(),
-                                              winsvc::SC_ENUM_PROCESS_INFO,
-                                              winnt::SERVICE_WIN32 | winnt::SERVICE_DRIVER,//SERVICE_TYPE_ALL,
-                                              winsvc::SERVICE_STATE_ALL,
-                                      lp_services.as_mut_ptr(),
-                                      pcb_bytes_needed,
-                                      &mut pcb_bytes_needed,
-                                      &mut lp_services_returned,
-                                      &mut lp_resume_handle,
-                                      std::ptr::null(),
-                )
-            };
-
-            let enum_result = EnumListServiceResult::from_raw(lp_services.as_slice().as_ptr(), lp_services_returned);
-            for service_status in enum_result
-                {
-                    let handle_service = unsafe { winsvc::OpenServiceW(self.manager_handle.raw_handle(),
-                                                              service_status.lpServiceName,
-                                                              winsvc::SC_MANAGER_ALL_ACCESS) };
-
-                    let mut pcb_bytes_needed = 0;
-                    unsafe { winsvc::QueryServiceConfigW(handle_service, std::ptr::null_mut(), 0, &mut pcb_bytes_needed) };
-
-                    if pcb_bytes_needed > 0 {
-                        let mut tmp = vec![0u8; pcb_bytes_needed as usize];
-                        let query_service_config: *mut winsvc::QUERY_SERVICE_CONFIGW = unsafe { mem::transmute(tmp.as_mut_ptr()) };
-
-                        unsafe { winsvc::QueryServiceConfigW(handle_service, query_service_config, pcb_bytes_needed + 0, &mut pcb_bytes_needed) };
-
-                        let service_detail = unsafe { ServiceDetail {
-                            status: ServiceStatusExt::from_raw(service_status.ServiceStatusProcess)?,
-                            name: WideCStr::from_ptr_str(service_status.lpServiceName).to_string_lossy(),
-                            display_name: WideCStr::from_ptr_str(service_status.lpDisplayName).to_string_lossy(),
-                            binary_path: Some(WideCStr::from_ptr_str((*query_service_config).lpBinaryPathName).to_string_lossy()),
-                            start_type: Some(ServiceStartType::from_raw((*query_service_config).dwStartType)?),
-                            error_control: Some(ServiceErrorControl::from_raw((*query_service_config).dwErrorControl)?),
-                            tag_id: Some((*query_service_config).dwErrorControl),
-                            start_name: Some(WideCStr::from_ptr_str((*query_service_config).lpServiceStartName).to_string_lossy()),
-                            load_order_group: Some(WideCStr::from_ptr_str((*query_service_config).lpLoadOrderGroup).to_string_lossy()),
-                            dependencies: Some(WideCStr::from_ptr_str((*query_service_config).lpDependencies).to_string_lossy())
-
-                        }};
-
-                        service_list.push(service_detail);
-                    } else {
-
-                        let service_detail = unsafe { ServiceDetail {
-                            status: ServiceStatusExt::from_raw(service_status.ServiceStatusProcess)?,
-                            name: WideCStr::from_ptr_str(service_status.lpServiceName).to_string_lossy(),
-                            display_name: WideCStr::from_ptr_str(service_status.lpDisplayName).to_string_lossy(),
-                            binary_path: Some(format!("Error when retrieving info for service {}", io::Error::last_os_error())),
-                            start_type: None,
-                            error_control: None,
-                            tag_id: None,
-                            start_name: None,
-                            load_order_group: None,
-                            dependencies: None
-
-                        }};
-
-                        service_list.push(service_detail);
-                    }
-                    unsafe { winsvc::CloseServiceHandle(handle_service)};
+    /// Watch the database for services being created and deleted.
+    ///
+    /// The watch reports a [`ServiceEvent::Created`] or [`ServiceEvent::Deleted`] with the name
+    /// of the service. Requires [`ServiceManagerAccess::ENUMERATE_SERVICE`].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+    /// use windows_service::watch::ServiceEvent;
+    ///
+    /// # #[cfg(windows)]
+    /// # fn main() -> windows_service::Result<()> {
+    /// let manager =
+    ///     ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::ENUMERATE_SERVICE)?;
+    /// for event in manager.watch_created_deleted()? {
+    ///     match event? {
+    ///         ServiceEvent::Created(name) => println!("created {}", name.to_string_lossy()),
+    ///         ServiceEvent::Deleted(name) => println!("deleted {}", name.to_string_lossy()),
+    ///         _ => (),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(windows))]
+    /// # fn main() {}
+    /// ```
+    ///
+    /// [`ServiceEvent::Created`]: super::watch::ServiceEvent::Created
+    /// [`ServiceEvent::Deleted`]: super::watch::ServiceEvent::Deleted
+    pub fn watch_created_deleted(&self) -> Result<ServiceWatch> {
+        let (sender, events) = mpsc::channel();
+        let handle = self.backend.watch_created_deleted(sender)?;
+        Ok(ServiceWatch::new(events, handle))
+    }
+
+    /// Enumerate the services that pass the filter.
+    ///
+    /// The services are fetched from the database one page at a time as the iterator advances.
+    /// Only their name and status are reported, use [`ServiceEnumerator::with_config`] to also
+    /// query their configuration. Enumeration requires
+    /// [`ServiceManagerAccess::ENUMERATE_SERVICE`] and no other rights.
+    ///
+    /// The iterator ends after yielding an error.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use windows_service::service::{ServiceStateFilter, ServiceTypeFilter};
+    /// use windows_service::service_manager::{
+    ///     ServiceFilter, ServiceManager, ServiceManagerAccess,
+    /// };
+    ///
+    /// # #[cfg(windows)]
+    /// # fn main() -> windows_service::Result<()> {
+    /// let manager =
+    ///     ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::ENUMERATE_SERVICE)?;
+    /// let filter = ServiceFilter::new()
+    ///     .service_type(ServiceTypeFilter::DRIVER)
+    ///     .state(ServiceStateFilter::Active);
+    /// for entry in manager.enumerate(filter) {
+    ///     println!("{}", entry?.name.to_string_lossy());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(windows))]
+    /// # fn main() {}
+    /// ```
+    pub fn enumerate(&self, filter: ServiceFilter) -> ServiceEnumerator<'_> {
+        ServiceEnumerator {
+            backend: &*self.backend,
+            filter,
+            page: Vec::new().into_iter(),
+            resume_handle: Some(0),
+        }
+    }
+}
+
+/// Iterator over the services returned by [`ServiceManager::enumerate`].
+pub struct ServiceEnumerator<'a> {
+    backend: &'a dyn ServiceManagerBackend,
+    filter: ServiceFilter,
+    page: vec::IntoIter<ServiceEntryExt>,
+    resume_handle: Option<u32>,
+}
+
+impl<'a> ServiceEnumerator<'a> {
+    /// Query the configuration of every enumerated service.
+    ///
+    /// Each service is opened with [`ServiceAccess::QUERY_CONFIG`]. A service that cannot be
+    /// opened, for example because it was deleted after it was enumerated, yields an error
+    /// without ending the iteration.
+    pub fn with_config(
+        self,
+    ) -> impl Iterator<Item = Result<(ServiceEntryExt, ServiceConfig)>> + 'a {
+        let backend = self.backend;
+        self.map(move |entry| {
+            let entry = entry?;
+            let config = backend
+                .open_service(&entry.name, ServiceAccess::QUERY_CONFIG)?
+                .query_config()?;
+            Ok((entry, config))
+        })
+    }
+}
+
+impl<'a> Iterator for ServiceEnumerator<'a> {
+    type Item = Result<ServiceEntryExt>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.page.next() {
+                return Some(Ok(entry));
+            }
+            let resume_handle = self.resume_handle.take()?;
+            match self.backend.enumerate_page(&self.filter, resume_handle) {
+                Ok(page) => {
+                    self.page = page.services.into_iter();
+                    self.resume_handle = page.resume_handle;
                 }
+                Err(error) => return Some(Err(error)),
+            }
         }
+    }
+}
 
-        Ok(service_list)
+/// Copy the error that opening a service failed with, to report it for every query that could
+/// not be made.
+fn copy_error(error: &::Error) -> ::Error {
+    match *error.kind() {
+        ErrorKind::System(ref error) => match error.raw_os_error() {
+            Some(code) => io::Error::from_raw_os_error(code).into(),
+            None => io::Error::new(error.kind(), error.to_string()).into(),
+        },
+        _ => ErrorKind::Msg(error.to_string()).into(),
     }
 }
 
-fn to_wide<T: AsRef<OsStr>>(s: Option<T>) -> ::std::result::Result<Option<WideCString>, NulError> {
-    if let Some(s) = s {
-        Ok(Some(WideCString::from_str(s)?))
+/// Build the command line used to launch the service from its executable path and launch
+/// arguments.
+pub(crate) fn launch_command(
+    service_type: ServiceType,
+    executable_path: &Path,
+    launch_arguments: &[OsString],
+) -> Result<WideCString> {
+    // escape executable path and arguments and combine them into single command
+    let executable_path = if is_driver(service_type) {
+        WideCString::from_str(executable_path).map(|path| path.to_wide_string())
     } else {
-        Ok(None)
+        escape_wide(executable_path)
+    }.chain_err(|| ErrorKind::InvalidExecutablePath)?;
+
+    append_launch_arguments(executable_path, launch_arguments)
+}
+
+/// Build the command line used to launch the service from the executable as written in the
+/// current command line, kept verbatim, and new launch arguments.
+pub(crate) fn replace_launch_arguments(
+    service_type: ServiceType,
+    command: &OsStr,
+    launch_arguments: &[OsString],
+) -> Result<WideCString> {
+    let (executable, _) = split_executable(service_type, command);
+    let executable =
+        WideCString::from_str(executable).chain_err(|| ErrorKind::InvalidExecutablePath)?;
+    append_launch_arguments(executable.to_wide_string(), launch_arguments)
+}
+
+/// Append the escaped launch arguments to the executable of a command line.
+fn append_launch_arguments(
+    executable: WideString,
+    launch_arguments: &[OsString],
+) -> Result<WideCString> {
+    let mut launch_command_buffer = executable;
+
+    for launch_argument in launch_arguments.iter() {
+        let wide = escape_wide(launch_argument).chain_err(|| ErrorKind::InvalidLaunchArgument)?;
+
+        launch_command_buffer.push_str(" ");
+        launch_command_buffer.push(wide);
     }
+
+    WideCString::from_wide_str(launch_command_buffer)
+        .chain_err(|| ErrorKind::InvalidExecutablePath)
+}
+
+/// Encode the dependencies as a sequence of nul terminated wide strings, terminated by an empty
+/// string.
+pub(crate) fn dependencies_multi_string(dependencies: &[ServiceDependency]) -> Result<Vec<u16>> {
+    wide_multi_string(dependencies.iter().map(ServiceDependency::to_system_identifier))
+        .ok_or_else(|| ErrorKind::InvalidDependency.into())
+}
+
+/// Encode the privilege names the same way as [`dependencies_multi_string`].
+pub(crate) fn privileges_multi_string(privileges: &[OsString]) -> Result<Vec<u16>> {
+    wide_multi_string(privileges.iter().cloned())
+        .ok_or_else(|| ErrorKind::InvalidRequiredPrivilege.into())
+}
+
+/// Encode a sequence of nul terminated wide strings, terminated by an empty string. Returns
+/// `None` if any of the strings is empty or contains a nul.
+pub(crate) fn wide_multi_string<I: IntoIterator<Item = OsString>>(strings: I) -> Option<Vec<u16>> {
+    let mut buffer = Vec::new();
+    for string in strings {
+        if string.is_empty() {
+            // An empty string would terminate the sequence early.
+            return None;
+        }
+        let wide = WideCString::from_str(string).ok()?;
+        buffer.extend_from_slice(wide.as_slice_with_nul());
+    }
+    if buffer.is_empty() {
+        buffer.push(0);
+    }
+    buffer.push(0);
+    Some(buffer)
+}
+
+/// Split the command line stored in the system services registry into the executable path and
+/// launch arguments. This is the inverse of [`launch_command`].
+pub(crate) fn split_launch_command(
+    service_type: ServiceType,
+    command: &OsStr,
+) -> (PathBuf, Vec<OsString>) {
+    let (executable, arguments) = split_executable(service_type, command);
+    let executable = WideString::from_str(&executable);
+    let executable_path = match executable.as_slice().split_first() {
+        // A quoted executable path extends to the closing quote, if any.
+        Some((&quote, quoted)) if quote == u16::from(b'"') && !is_driver(service_type) => {
+            let end = quoted
+                .iter()
+                .position(|&c| c == quote)
+                .unwrap_or(quoted.len());
+            WideString::from_vec(&quoted[..end]).to_os_string()
+        }
+        _ => executable.to_os_string(),
+    };
+    (
+        PathBuf::from(executable_path),
+        shell_escape::split_arguments(&arguments),
+    )
+}
+
+/// Split the command line stored in the system services registry into the executable, as written
+/// in the command line, and the arguments that follow it.
+///
+/// Drivers are loaded by the kernel, which treats the whole command line as the path.
+///
+/// The executable path of other services extends to the closing quote when it is quoted.
+/// Otherwise it may still contain spaces, like in `C:\Program Files\Foo\svc.exe -x`. The system
+/// tries each part of the command line that ends before a space in turn, and runs the first one
+/// that names an existing file. Without looking at the file system, the path is taken to end at the
+/// first `.exe` that is followed by a space or the end of the command line, and at the first space
+/// if there is no such `.exe`.
+fn split_executable(service_type: ServiceType, command: &OsStr) -> (OsString, OsString) {
+    let wide = WideString::from_str(command);
+    let wide = wide.as_slice();
+    let is_space = |c: u16| c == u16::from(b' ') || c == u16::from(b'\t');
+
+    let end = if is_driver(service_type) {
+        wide.len()
+    } else if wide.first() == Some(&u16::from(b'"')) {
+        wide[1..]
+            .iter()
+            .position(|&c| c == u16::from(b'"'))
+            .map_or(wide.len(), |position| position + 2)
+    } else {
+        let first_space = wide.iter().position(|&c| is_space(c)).unwrap_or(wide.len());
+        (0..=wide.len())
+            .filter(|&end| end == wide.len() || is_space(wide[end]))
+            .find(|&end| ends_with_exe(&wide[..end]))
+            .unwrap_or(first_space)
+    };
+
+    (
+        WideString::from_vec(&wide[..end]).to_os_string(),
+        WideString::from_vec(&wide[end..]).to_os_string(),
+    )
+}
+
+/// Check whether a path ends with the `.exe` extension, ignoring case.
+fn ends_with_exe(path: &[u16]) -> bool {
+    const EXTENSION: &[u8] = b".exe";
+    path.len() >= EXTENSION.len()
+        && path[path.len() - EXTENSION.len()..]
+            .iter()
+            .zip(EXTENSION)
+            .all(|(&c, &expected)| c < 0x80 && (c as u8).to_ascii_lowercase() == expected)
+}
+
+/// Whether the service is a driver, whose command line is the path to the driver without any
+/// escaping.
+pub(crate) fn is_driver(service_type: ServiceType) -> bool {
+    matches!(
+        service_type,
+        ServiceType::KernelDriver | ServiceType::FileSystemDriver
+    )
 }
 
 fn escape_wide<T: AsRef<OsStr>>(s: T) -> ::std::result::Result<WideString, NulError> {
@@ -350,3 +588,74 @@ fn escape_wide<T: AsRef<OsStr>>(s: T) -> ::std::result::Result<WideString, NulEr
     let wide = WideCString::from_str(escaped)?;
     Ok(wide.to_wide_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(service_type: ServiceType, command: &str) -> (PathBuf, Vec<OsString>) {
+        split_launch_command(service_type, OsStr::new(command))
+    }
+
+    #[test]
+    fn test_split_launch_command_quoted() {
+        assert_eq!(
+            split(
+                ServiceType::OwnProcess,
+                r#""C:\Program Files\Foo\svc.exe" -x "a b""#
+            ),
+            (
+                PathBuf::from(r"C:\Program Files\Foo\svc.exe"),
+                vec![OsString::from("-x"), OsString::from("a b")]
+            )
+        );
+    }
+
+    #[test]
+    fn test_split_launch_command_unquoted_with_spaces() {
+        assert_eq!(
+            split(ServiceType::OwnProcess, r"C:\Program Files\Foo\svc.EXE -x"),
+            (
+                PathBuf::from(r"C:\Program Files\Foo\svc.EXE"),
+                vec![OsString::from("-x")]
+            )
+        );
+        assert_eq!(
+            split(ServiceType::OwnProcess, r"C:\Program Files\Foo\svc.exe"),
+            (PathBuf::from(r"C:\Program Files\Foo\svc.exe"), vec![])
+        );
+        // Without an `.exe`, the path ends at the first space.
+        assert_eq!(
+            split(ServiceType::OwnProcess, r"C:\Foo\svc -x"),
+            (PathBuf::from(r"C:\Foo\svc"), vec![OsString::from("-x")])
+        );
+    }
+
+    #[test]
+    fn test_driver_command_is_not_escaped() {
+        let path = Path::new(r"C:\Program Files\Foo\filter.sys");
+        for &service_type in &[ServiceType::KernelDriver, ServiceType::FileSystemDriver] {
+            let command = launch_command(service_type, path, &[]).unwrap();
+            assert_eq!(command.to_os_string(), path.as_os_str());
+            assert_eq!(
+                split_launch_command(service_type, &command.to_os_string()),
+                (path.to_path_buf(), vec![])
+            );
+        }
+    }
+
+    #[test]
+    fn test_launch_command_rejects_nul() {
+        let path = Path::new("C:\\Foo\0\\svc.sys");
+        for &service_type in &[
+            ServiceType::KernelDriver,
+            ServiceType::FileSystemDriver,
+            ServiceType::OwnProcess,
+        ] {
+            match launch_command(service_type, path, &[]).unwrap_err().kind() {
+                ErrorKind::InvalidExecutablePath => (),
+                other => panic!("Unexpected error: {:?}", other),
+            }
+        }
+    }
+}
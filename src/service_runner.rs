@@ -0,0 +1,787 @@
+//! A lifecycle driver for services implemented as a [`ServiceRunner`].
+//!
+//! Every service goes through the same steps: register a control handler, report that it is
+//! starting, report that it is running, forward the control events to the code of the service and
+//! finally report that it stopped. [`run`] takes care of these steps and calls the hooks of a
+//! [`ServiceRunner`] for the service specific parts, reporting the pending states around each
+//! hook and the controls that the service accepts in every state.
+//!
+//! The driver talks to the service control manager through a [`ServiceHost`]. On Windows,
+//! [`run_service`] uses the system as the host, while [`SimulatedScm`] records the reported
+//! statuses and sends control events from tests on any platform.
+//!
+//! # Example
+//!
+//! ```rust
+//! use std::ffi::OsString;
+//! use std::thread;
+//! use std::time::Duration;
+//! use windows_service::service::{ServiceControl, ServiceExitCode, ServiceState};
+//! use windows_service::service_runner::{self, ServiceRunner, SimulatedScm};
+//!
+//! struct PingService;
+//!
+//! impl ServiceRunner for PingService {
+//!     type Error = std::io::Error;
+//!
+//!     fn on_start(&mut self, _arguments: Vec<OsString>) -> Result<(), std::io::Error> {
+//!         // Spawn the worker threads...
+//!         Ok(())
+//!     }
+//!
+//!     fn on_stop(&mut self) -> Result<(), std::io::Error> {
+//!         // Stop the worker threads...
+//!         Ok(())
+//!     }
+//! }
+//!
+//! # fn main() -> windows_service::Result<()> {
+//! let scm = SimulatedScm::new();
+//! let host = scm.clone();
+//! let service = thread::spawn(move || service_runner::run(PingService, vec![], host));
+//!
+//! scm.wait_for_state(ServiceState::Running, Duration::from_secs(1));
+//! scm.send_control(ServiceControl::Stop)?;
+//! assert_eq!(service.join().unwrap()?, ServiceExitCode::default());
+//! assert_eq!(scm.status().unwrap().current_state, ServiceState::Stopped);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ffi::OsString;
+use std::io;
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use service::{
+    ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType,
+};
+use status_reporter::{StatusReporter, StatusSink};
+use sys::winerror::{
+    ERROR_INVALID_SERVICE_CONTROL, ERROR_SERVICE_CANNOT_ACCEPT_CTRL, ERROR_SERVICE_NOT_ACTIVE,
+};
+use {ErrorKind, Result};
+
+/// How often progress is reported while a hook runs when the service has no wait hint.
+const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The exit code reported for errors that do not carry one.
+const GENERIC_EXIT_CODE: ServiceExitCode = ServiceExitCode::ServiceSpecific(1);
+
+/// Errors that determine the exit code a service stops with.
+pub trait ToExitCode {
+    /// Returns the exit code to report when the service stops because of this error.
+    fn to_exit_code(&self) -> ServiceExitCode;
+}
+
+impl ToExitCode for ServiceExitCode {
+    fn to_exit_code(&self) -> ServiceExitCode {
+        *self
+    }
+}
+
+/// System errors are reported with their error code, other errors with the service specific
+/// exit code 1.
+impl ToExitCode for io::Error {
+    fn to_exit_code(&self) -> ServiceExitCode {
+        match self.raw_os_error() {
+            Some(code) => ServiceExitCode::Win32(code as u32),
+            None => GENERIC_EXIT_CODE,
+        }
+    }
+}
+
+/// System errors are reported with their error code, other errors with the service specific
+/// exit code 1.
+impl ToExitCode for ::Error {
+    fn to_exit_code(&self) -> ServiceExitCode {
+        match *self.kind() {
+            ErrorKind::System(ref error) => error.to_exit_code(),
+            _ => GENERIC_EXIT_CODE,
+        }
+    }
+}
+
+/// The service specific part of a service, driven by [`run`].
+///
+/// The hooks are called one at a time on the thread that runs the driver, so they should return
+/// as soon as the service reached the next state. The driver keeps reporting progress while a
+/// hook runs, but the service does not handle any other control in the meantime. Long running
+/// work belongs on threads started by [`ServiceRunner::on_start`].
+///
+/// When a hook fails, the service stops with the exit code of the error. If the service was
+/// already started, [`ServiceRunner::on_stop`] is called before reporting the service as
+/// stopped.
+pub trait ServiceRunner {
+    /// The error returned by the hooks.
+    type Error: ToExitCode;
+
+    /// Start the service with the arguments it was started with.
+    fn on_start(&mut self, arguments: Vec<OsString>) -> ::std::result::Result<(), Self::Error>;
+
+    /// Stop the service.
+    fn on_stop(&mut self) -> ::std::result::Result<(), Self::Error>;
+
+    /// Pause the service. Only called if the service accepts
+    /// [`ServiceControlAccept::PAUSE_CONTINUE`].
+    fn on_pause(&mut self) -> ::std::result::Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Continue the paused service. Only called if the service accepts
+    /// [`ServiceControlAccept::PAUSE_CONTINUE`].
+    fn on_continue(&mut self) -> ::std::result::Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Stop the service because the system is shutting down. Only called if the service accepts
+    /// [`ServiceControlAccept::SHUTDOWN`]. Stops the service like [`ServiceRunner::on_stop`] by
+    /// default.
+    fn on_shutdown(&mut self) -> ::std::result::Result<(), Self::Error> {
+        self.on_stop()
+    }
+
+    /// Reread the startup parameters. Only called if the service accepts
+    /// [`ServiceControlAccept::PARAM_CHANGE`].
+    fn on_param_change(&mut self) -> ::std::result::Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// The controls the service accepts besides [`ServiceControlAccept::STOP`], which is always
+    /// accepted. Only [`ServiceControlAccept::PAUSE_CONTINUE`], [`ServiceControlAccept::SHUTDOWN`]
+    /// and [`ServiceControlAccept::PARAM_CHANGE`] are taken into account.
+    fn controls_accepted(&self) -> ServiceControlAccept {
+        ServiceControlAccept::empty()
+    }
+
+    /// The type of the service, reported along with every status.
+    fn service_type(&self) -> ServiceType {
+        ServiceType::OwnProcess
+    }
+
+    /// The wait hint reported with every pending state. Progress is reported every half of it
+    /// while a hook runs.
+    fn wait_hint(&self) -> Duration {
+        Duration::from_secs(10)
+    }
+}
+
+/// The service control manager, as seen from the service.
+pub trait ServiceHost {
+    /// Register the service and forward the control events sent to it to `controls`.
+    fn register(&mut self, controls: mpsc::Sender<ServiceControl>) -> Result<()>;
+
+    /// Report the service status.
+    fn set_service_status(&mut self, status: ServiceStatus) -> Result<()>;
+}
+
+/// Drive the service through its lifecycle until it stops.
+///
+/// The driver registers with the host, reports the service as starting, calls
+/// [`ServiceRunner::on_start`] and reports the service as running. It then calls the hook that
+/// handles each control event, reporting the pending state before the hook and the resulting
+/// state after it, until the service is stopped. The controls accepted in each state are derived
+/// from [`ServiceRunner::controls_accepted`], with no controls accepted in pending states.
+///
+/// The pending states are reported through a [`StatusReporter`]: while a hook runs, a
+/// [`Heartbeat`] reports progress every half of [`ServiceRunner::wait_hint`], so that the system
+/// does not consider the service hung when the hook takes longer than the wait hint.
+///
+/// Returns the exit code the service was reported to stop with. Fails if registering or reporting
+/// a status fails, in which case the hooks are not called anymore.
+///
+/// [`Heartbeat`]: super::status_reporter::Heartbeat
+pub fn run<R, H>(mut runner: R, arguments: Vec<OsString>, mut host: H) -> Result<ServiceExitCode>
+where
+    R: ServiceRunner,
+    H: ServiceHost + Send + 'static,
+{
+    let (sender, controls) = mpsc::channel();
+    host.register(sender)?;
+    let optional_controls = ServiceControlAccept::PAUSE_CONTINUE
+        | ServiceControlAccept::SHUTDOWN
+        | ServiceControlAccept::PARAM_CHANGE;
+    let reporter = Reporter {
+        status: StatusReporter::new(HostSink(Mutex::new(host)), runner.service_type()),
+        controls_accepted: (runner.controls_accepted() & optional_controls)
+            | ServiceControlAccept::STOP,
+        wait_hint: runner.wait_hint(),
+    };
+
+    let started = reporter.pending(ServiceState::StartPending, || runner.on_start(arguments))?;
+    if let Err(error) = started {
+        return reporter.stopped(error.to_exit_code());
+    }
+    reporter.settle(ServiceState::Running)?;
+
+    loop {
+        // The system does not send anything after the handler goes away, stop the service then.
+        let control = controls.recv().unwrap_or(ServiceControl::Stop);
+        let (result, next_state) = match control {
+            ServiceControl::Stop => (
+                reporter.pending(ServiceState::StopPending, || runner.on_stop())?,
+                None,
+            ),
+            ServiceControl::Shutdown => (
+                reporter.pending(ServiceState::StopPending, || runner.on_shutdown())?,
+                None,
+            ),
+            ServiceControl::Pause => (
+                reporter.pending(ServiceState::PausePending, || runner.on_pause())?,
+                Some(ServiceState::Paused),
+            ),
+            ServiceControl::Continue => (
+                reporter.pending(ServiceState::ContinuePending, || runner.on_continue())?,
+                Some(ServiceState::Running),
+            ),
+            // Changing the parameters does not go through a pending state.
+            ServiceControl::ParamChange => match runner.on_param_change() {
+                Ok(()) => continue,
+                Err(error) => (Err(error), Some(reporter.status.state())),
+            },
+            _ => continue,
+        };
+
+        match (result, next_state) {
+            (Ok(()), Some(next_state)) => reporter.settle(next_state)?,
+            (Ok(()), None) => return reporter.stopped(ServiceExitCode::default()),
+            (Err(error), Some(_)) => {
+                // The service fails with the first error, even if stopping it fails too.
+                let _ = reporter.pending(ServiceState::StopPending, || runner.on_stop())?;
+                return reporter.stopped(error.to_exit_code());
+            }
+            (Err(error), None) => return reporter.stopped(error.to_exit_code()),
+        }
+    }
+}
+
+/// The status sink that [`run`] reports to, forwarding the statuses to the host.
+struct HostSink<H>(Mutex<H>);
+
+impl<H: ServiceHost + Send> StatusSink for HostSink<H> {
+    fn report(&self, status: ServiceStatus) -> Result<()> {
+        self.0.lock().unwrap().set_service_status(status)
+    }
+}
+
+/// Reports the statuses of the service driven by [`run`].
+struct Reporter {
+    status: StatusReporter,
+    controls_accepted: ServiceControlAccept,
+    wait_hint: Duration,
+}
+
+impl Reporter {
+    /// Report the pending state and call the hook, reporting progress until it returns.
+    fn pending<T, F: FnOnce() -> T>(&self, state: ServiceState, hook: F) -> Result<T> {
+        self.status.pending(state, self.wait_hint)?;
+        let interval = if self.wait_hint == Duration::default() {
+            DEFAULT_HEARTBEAT_INTERVAL
+        } else {
+            self.wait_hint / 2
+        };
+        let _heartbeat = self.status.heartbeat(interval);
+        Ok(hook())
+    }
+
+    /// Report that the service reached the running or paused state.
+    fn settle(&self, state: ServiceState) -> Result<()> {
+        match state {
+            ServiceState::Paused => self.status.paused(self.controls_accepted),
+            _ => self.status.running(self.controls_accepted),
+        }
+    }
+
+    fn stopped(&self, exit_code: ServiceExitCode) -> Result<ServiceExitCode> {
+        self.status.stopped(exit_code)?;
+        Ok(exit_code)
+    }
+}
+
+/// The system service control manager, hosting the service with the given name.
+#[cfg(windows)]
+#[derive(Debug)]
+pub struct SystemHost {
+    service_name: OsString,
+    status_handle: Option<::service_control_handler::ServiceStatusHandle>,
+}
+
+#[cfg(windows)]
+impl SystemHost {
+    pub fn new<S: Into<OsString>>(service_name: S) -> Self {
+        SystemHost {
+            service_name: service_name.into(),
+            status_handle: None,
+        }
+    }
+}
+
+#[cfg(windows)]
+impl ServiceHost for SystemHost {
+    fn register(&mut self, controls: mpsc::Sender<ServiceControl>) -> Result<()> {
+        use service_control_handler::{self, ServiceControlHandlerResult};
+
+        let event_handler = move |control| -> ServiceControlHandlerResult {
+            // The system answers interrogations with the last reported status.
+            if control != ServiceControl::Interrogate {
+                let _ = controls.send(control);
+            }
+            ServiceControlHandlerResult::NoError
+        };
+        self.status_handle = Some(service_control_handler::register(
+            &self.service_name,
+            event_handler,
+        )?);
+        Ok(())
+    }
+
+    fn set_service_status(&mut self, status: ServiceStatus) -> Result<()> {
+        let status_handle = self
+            .status_handle
+            .as_ref()
+            .expect("The service must be registered before reporting its status");
+        Ok(status_handle.set_service_status(status)?)
+    }
+}
+
+/// Drive the service through its lifecycle with the system as the host, see [`run`].
+///
+/// Call this from the `service_main` of the service, with the arguments it received.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// #[macro_use]
+/// extern crate windows_service;
+///
+/// use std::ffi::OsString;
+/// use windows_service::service_runner::{self, ServiceRunner};
+///
+/// struct MyService;
+///
+/// impl ServiceRunner for MyService {
+///     type Error = windows_service::Error;
+///
+///     fn on_start(&mut self, _arguments: Vec<OsString>) -> windows_service::Result<()> {
+///         Ok(())
+///     }
+///
+///     fn on_stop(&mut self) -> windows_service::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// define_windows_service!(ffi_service_main, my_service_main);
+///
+/// fn my_service_main(arguments: Vec<OsString>) {
+///     if let Err(_e) = service_runner::run_service("my_service", MyService, arguments) {
+///         // Handle errors in some way.
+///     }
+/// }
+///
+/// # fn main() {}
+/// ```
+#[cfg(windows)]
+pub fn run_service<S, R>(
+    service_name: S,
+    runner: R,
+    arguments: Vec<OsString>,
+) -> Result<ServiceExitCode>
+where
+    S: Into<OsString>,
+    R: ServiceRunner,
+{
+    run(runner, arguments, SystemHost::new(service_name))
+}
+
+/// An in-memory service control manager hosting a single service, for testing services on any
+/// platform.
+///
+/// Clones share the same state, so one clone can be handed to [`run`] while another one sends
+/// the control events and inspects the reported statuses. Like the system, the simulated
+/// manager only delivers the controls that the service accepts in its last reported status.
+#[derive(Debug, Clone, Default)]
+pub struct SimulatedScm {
+    shared: Arc<(Mutex<SimulatedState>, Condvar)>,
+}
+
+#[derive(Debug, Default)]
+struct SimulatedState {
+    controls: Option<mpsc::Sender<ServiceControl>>,
+    statuses: Vec<ServiceStatus>,
+}
+
+impl SimulatedScm {
+    pub fn new() -> Self {
+        SimulatedScm::default()
+    }
+
+    /// Returns all statuses reported by the service, in order.
+    pub fn statuses(&self) -> Vec<ServiceStatus> {
+        self.lock().statuses.clone()
+    }
+
+    /// Returns the last status reported by the service.
+    pub fn status(&self) -> Option<ServiceStatus> {
+        self.lock().statuses.last().cloned()
+    }
+
+    /// Send a control event to the service.
+    ///
+    /// Fails with `ERROR_SERVICE_NOT_ACTIVE` if the service is not registered or has stopped,
+    /// with `ERROR_SERVICE_CANNOT_ACCEPT_CTRL` if the service is in a pending state, and with
+    /// `ERROR_INVALID_SERVICE_CONTROL` if the service does not accept the control.
+    pub fn send_control(&self, control: ServiceControl) -> io::Result<()> {
+        let state = self.lock();
+        let (controls, status) = match (&state.controls, state.statuses.last()) {
+            (Some(controls), Some(status)) => (controls, status),
+            _ => return Err(system_error(ERROR_SERVICE_NOT_ACTIVE)),
+        };
+        if control != ServiceControl::Interrogate {
+            match status.current_state {
+                ServiceState::Running | ServiceState::Paused => (),
+                _ => return Err(system_error(ERROR_SERVICE_CANNOT_ACCEPT_CTRL)),
+            }
+        }
+        let required_accept = match control {
+            ServiceControl::Stop => ServiceControlAccept::STOP,
+            ServiceControl::Pause | ServiceControl::Continue => {
+                ServiceControlAccept::PAUSE_CONTINUE
+            }
+            ServiceControl::Shutdown => ServiceControlAccept::SHUTDOWN,
+            ServiceControl::Preshutdown => ServiceControlAccept::PRESHUTDOWN,
+            ServiceControl::ParamChange => ServiceControlAccept::PARAM_CHANGE,
+            ServiceControl::NetBindAdd
+            | ServiceControl::NetBindDisable
+            | ServiceControl::NetBindEnable
+            | ServiceControl::NetBindRemove => ServiceControlAccept::NETBIND_CHANGE,
+            ServiceControl::HardwareProfileChange(_) => {
+                ServiceControlAccept::HARDWARE_PROFILE_CHANGE
+            }
+            ServiceControl::PowerEvent(_) => ServiceControlAccept::POWER_EVENT,
+            ServiceControl::SessionChange(_) => ServiceControlAccept::SESSION_CHANGE,
+            ServiceControl::TimeChange(_) => ServiceControlAccept::TIME_CHANGE,
+            ServiceControl::TriggerEvent => ServiceControlAccept::TRIGGER_EVENT,
+            ServiceControl::UserModeReboot => ServiceControlAccept::USER_MODE_REBOOT,
+            ServiceControl::LowResources => ServiceControlAccept::LOW_RESOURCES,
+            ServiceControl::SystemLowResources => ServiceControlAccept::SYSTEM_LOW_RESOURCES,
+            _ => ServiceControlAccept::empty(),
+        };
+        if !status.controls_accepted.contains(required_accept) {
+            return Err(system_error(ERROR_INVALID_SERVICE_CONTROL));
+        }
+        let _ = controls.send(control);
+        Ok(())
+    }
+
+    /// Wait until the service reports the given state. Returns the status, or `None` if the
+    /// service did not reach the state within the timeout.
+    pub fn wait_for_state(&self, state: ServiceState, timeout: Duration) -> Option<ServiceStatus> {
+        let deadline = Instant::now() + timeout;
+        let (ref mutex, ref condvar) = *self.shared;
+        let mut simulated = mutex.lock().unwrap();
+        loop {
+            match simulated.statuses.last() {
+                Some(status) if status.current_state == state => return Some(status.clone()),
+                _ => (),
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            simulated = condvar.wait_timeout(simulated, deadline - now).unwrap().0;
+        }
+    }
+
+    fn lock(&self) -> ::std::sync::MutexGuard<'_, SimulatedState> {
+        self.shared.0.lock().unwrap()
+    }
+}
+
+fn system_error(code: u32) -> io::Error {
+    io::Error::from_raw_os_error(code as i32)
+}
+
+impl ServiceHost for SimulatedScm {
+    fn register(&mut self, controls: mpsc::Sender<ServiceControl>) -> Result<()> {
+        self.lock().controls = Some(controls);
+        Ok(())
+    }
+
+    fn set_service_status(&mut self, status: ServiceStatus) -> Result<()> {
+        self.report(status)
+    }
+}
+
+impl StatusSink for SimulatedScm {
+    fn report(&self, status: ServiceStatus) -> Result<()> {
+        let mut state = self.lock();
+        if status.current_state == ServiceState::Stopped {
+            // Like the system, stop sending controls to a stopped service.
+            state.controls = None;
+        }
+        state.statuses.push(status);
+        self.shared.1.notify_all();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use service::PowerEventParam;
+    use std::thread;
+    use sys::winerror::ERROR_ACCESS_DENIED;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// The hooks a runner was called with, in order.
+    type Calls = Arc<Mutex<Vec<&'static str>>>;
+
+    /// A runner that records the hooks it was called with and fails the configured one.
+    struct RecordingRunner {
+        calls: Calls,
+        fail: Option<&'static str>,
+        controls_accepted: ServiceControlAccept,
+    }
+
+    impl RecordingRunner {
+        fn call(&mut self, hook: &'static str) -> io::Result<()> {
+            self.calls.lock().unwrap().push(hook);
+            if self.fail == Some(hook) {
+                Err(system_error(ERROR_ACCESS_DENIED))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl ServiceRunner for RecordingRunner {
+        type Error = io::Error;
+
+        fn on_start(&mut self, arguments: Vec<OsString>) -> io::Result<()> {
+            assert_eq!(arguments, vec![OsString::from("test")]);
+            self.call("start")
+        }
+
+        fn on_stop(&mut self) -> io::Result<()> {
+            self.call("stop")
+        }
+
+        fn on_pause(&mut self) -> io::Result<()> {
+            self.call("pause")
+        }
+
+        fn on_continue(&mut self) -> io::Result<()> {
+            self.call("continue")
+        }
+
+        fn on_param_change(&mut self) -> io::Result<()> {
+            self.call("param_change")
+        }
+
+        fn controls_accepted(&self) -> ServiceControlAccept {
+            self.controls_accepted
+        }
+    }
+
+    /// Run the runner on a thread of its own, returning the simulated manager and the thread.
+    fn spawn_runner(
+        fail: Option<&'static str>,
+        controls_accepted: ServiceControlAccept,
+    ) -> (
+        SimulatedScm,
+        Calls,
+        thread::JoinHandle<Result<ServiceExitCode>>,
+    ) {
+        let scm = SimulatedScm::new();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let runner = RecordingRunner {
+            calls: calls.clone(),
+            fail,
+            controls_accepted,
+        };
+        let host = scm.clone();
+        let service = thread::spawn(move || run(runner, vec![OsString::from("test")], host));
+        (scm, calls, service)
+    }
+
+    fn states(scm: &SimulatedScm) -> Vec<ServiceState> {
+        scm.statuses()
+            .iter()
+            .map(|status| status.current_state)
+            .collect()
+    }
+
+    #[test]
+    fn test_run_lifecycle() {
+        let (scm, calls, service) = spawn_runner(
+            None,
+            ServiceControlAccept::PAUSE_CONTINUE | ServiceControlAccept::PARAM_CHANGE,
+        );
+
+        let running = scm.wait_for_state(ServiceState::Running, TIMEOUT).unwrap();
+        assert_eq!(
+            running.controls_accepted,
+            ServiceControlAccept::STOP
+                | ServiceControlAccept::PAUSE_CONTINUE
+                | ServiceControlAccept::PARAM_CHANGE
+        );
+        assert_eq!(running.checkpoint, 0);
+        let start_pending = &scm.statuses()[0];
+        assert_eq!(
+            start_pending.controls_accepted,
+            ServiceControlAccept::empty()
+        );
+        assert_eq!(start_pending.wait_hint, Duration::from_secs(10));
+
+        scm.send_control(ServiceControl::Pause).unwrap();
+        scm.wait_for_state(ServiceState::Paused, TIMEOUT).unwrap();
+        scm.send_control(ServiceControl::Continue).unwrap();
+        scm.wait_for_state(ServiceState::Running, TIMEOUT).unwrap();
+        scm.send_control(ServiceControl::ParamChange).unwrap();
+        scm.send_control(ServiceControl::Stop).unwrap();
+        assert_eq!(service.join().unwrap().unwrap(), ServiceExitCode::default());
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec!["start", "pause", "continue", "param_change", "stop"]
+        );
+        assert_eq!(
+            states(&scm),
+            vec![
+                ServiceState::StartPending,
+                ServiceState::Running,
+                ServiceState::PausePending,
+                ServiceState::Paused,
+                ServiceState::ContinuePending,
+                ServiceState::Running,
+                ServiceState::StopPending,
+                ServiceState::Stopped,
+            ]
+        );
+        assert_eq!(
+            scm.send_control(ServiceControl::Stop)
+                .unwrap_err()
+                .raw_os_error(),
+            Some(ERROR_SERVICE_NOT_ACTIVE as i32)
+        );
+    }
+
+    #[test]
+    fn test_run_rejects_controls_not_accepted() {
+        let (scm, calls, service) = spawn_runner(None, ServiceControlAccept::empty());
+        scm.wait_for_state(ServiceState::Running, TIMEOUT).unwrap();
+        for control in &[
+            ServiceControl::Pause,
+            ServiceControl::Shutdown,
+            ServiceControl::ParamChange,
+            ServiceControl::PowerEvent(PowerEventParam::Suspend),
+        ] {
+            assert_eq!(
+                scm.send_control(control.clone()).unwrap_err().raw_os_error(),
+                Some(ERROR_INVALID_SERVICE_CONTROL as i32)
+            );
+        }
+        scm.send_control(ServiceControl::Stop).unwrap();
+        service.join().unwrap().unwrap();
+        assert_eq!(*calls.lock().unwrap(), vec!["start", "stop"]);
+    }
+
+    #[test]
+    fn test_run_hook_failures() {
+        let denied = ServiceExitCode::Win32(ERROR_ACCESS_DENIED);
+
+        // Nothing to stop when the service fails to start.
+        let (scm, calls, service) = spawn_runner(Some("start"), ServiceControlAccept::empty());
+        assert_eq!(service.join().unwrap().unwrap(), denied);
+        assert_eq!(*calls.lock().unwrap(), vec!["start"]);
+        assert_eq!(
+            states(&scm),
+            vec![ServiceState::StartPending, ServiceState::Stopped]
+        );
+        assert_eq!(scm.status().unwrap().exit_code, denied);
+
+        // The service is stopped when it fails to pause.
+        let (scm, calls, service) =
+            spawn_runner(Some("pause"), ServiceControlAccept::PAUSE_CONTINUE);
+        scm.wait_for_state(ServiceState::Running, TIMEOUT).unwrap();
+        scm.send_control(ServiceControl::Pause).unwrap();
+        assert_eq!(service.join().unwrap().unwrap(), denied);
+        assert_eq!(*calls.lock().unwrap(), vec!["start", "pause", "stop"]);
+        assert_eq!(
+            states(&scm),
+            vec![
+                ServiceState::StartPending,
+                ServiceState::Running,
+                ServiceState::PausePending,
+                ServiceState::StopPending,
+                ServiceState::Stopped,
+            ]
+        );
+
+        let (scm, _, service) = spawn_runner(Some("stop"), ServiceControlAccept::empty());
+        scm.wait_for_state(ServiceState::Running, TIMEOUT).unwrap();
+        scm.send_control(ServiceControl::Stop).unwrap();
+        assert_eq!(service.join().unwrap().unwrap(), denied);
+        assert_eq!(scm.status().unwrap().exit_code, denied);
+    }
+
+    /// A runner that takes longer to stop than its wait hint.
+    struct SlowRunner;
+
+    impl ServiceRunner for SlowRunner {
+        type Error = io::Error;
+
+        fn on_start(&mut self, _arguments: Vec<OsString>) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn on_stop(&mut self) -> io::Result<()> {
+            thread::sleep(Duration::from_millis(250));
+            Ok(())
+        }
+
+        fn wait_hint(&self) -> Duration {
+            Duration::from_millis(100)
+        }
+    }
+
+    #[test]
+    fn test_run_reports_progress_while_hook_runs() {
+        let scm = SimulatedScm::new();
+        let host = scm.clone();
+        let service = thread::spawn(move || run(SlowRunner, vec![], host));
+        scm.wait_for_state(ServiceState::Running, TIMEOUT).unwrap();
+        scm.send_control(ServiceControl::Stop).unwrap();
+        service.join().unwrap().unwrap();
+
+        let stop_pending: Vec<_> = scm
+            .statuses()
+            .into_iter()
+            .filter(|status| status.current_state == ServiceState::StopPending)
+            .collect();
+        assert!(stop_pending.len() >= 3, "{:?}", stop_pending);
+        for (checkpoint, status) in (1..).zip(&stop_pending) {
+            assert_eq!(status.checkpoint, checkpoint);
+            assert_eq!(status.wait_hint, Duration::from_millis(100));
+        }
+        assert_eq!(scm.status().unwrap().current_state, ServiceState::Stopped);
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(
+            system_error(ERROR_ACCESS_DENIED).to_exit_code(),
+            ServiceExitCode::Win32(ERROR_ACCESS_DENIED)
+        );
+        assert_eq!(
+            io::Error::new(io::ErrorKind::Other, "failed").to_exit_code(),
+            ServiceExitCode::ServiceSpecific(1)
+        );
+        assert_eq!(
+            ::Error::from(ErrorKind::InvalidServiceName).to_exit_code(),
+            ServiceExitCode::ServiceSpecific(1)
+        );
+    }
+}
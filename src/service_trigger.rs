@@ -0,0 +1,614 @@
+//! Trigger-start configuration of services.
+//!
+//! A service with triggers is started or stopped by the system when the event described by one
+//! of its triggers occurs, for example when a device arrives or the computer joins a domain, so
+//! that it does not have to keep running just to wait for the event.
+//!
+//! See <https://docs.microsoft.com/en-us/windows/win32/services/service-trigger-events>
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use windows_service::guid::Guid;
+//! use windows_service::service::ServiceAccess;
+//! use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+//! use windows_service::service_trigger::{ServiceTrigger, ServiceTriggerAction};
+//!
+//! # #[cfg(windows)]
+//! # fn main() -> windows_service::Result<()> {
+//! let usb_device: Guid = "a5dcbf10-6530-11d2-901f-00c04fb951ed".parse()?;
+//! let triggers = vec![
+//!     ServiceTrigger::device_interface_arrival(usb_device).string(r"USB\VID_1234&PID_5678"),
+//!     ServiceTrigger::domain_leave().action(ServiceTriggerAction::Stop),
+//! ];
+//!
+//! let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+//! let my_service = manager.open_service("usb_agent", ServiceAccess::SERVICE_CHANGE_CONFIG)?;
+//! my_service.set_triggers(&triggers)?;
+//! # Ok(())
+//! # }
+//! # #[cfg(not(windows))]
+//! # fn main() {}
+//! ```
+
+use std::ffi::OsString;
+
+use widestring::WideCString;
+#[cfg(any(windows, test))]
+use widestring::WideString;
+
+use guid::Guid;
+use schema;
+use service_manager::wide_multi_string;
+use sys::winsvc;
+use {ErrorKind, Result, ResultExt};
+
+const NETWORK_MANAGER_FIRST_IP_ADDRESS_ARRIVAL_GUID: Guid = Guid::from_values(
+    0x4f27f2de,
+    0x14e2,
+    0x430b,
+    [0xa5, 0x49, 0x7c, 0xd4, 0x8c, 0xbc, 0x82, 0x45],
+);
+const NETWORK_MANAGER_LAST_IP_ADDRESS_REMOVAL_GUID: Guid = Guid::from_values(
+    0xcc4ba62a,
+    0x162e,
+    0x4648,
+    [0x84, 0x7a, 0xb6, 0xbd, 0xf9, 0x93, 0xe3, 0x35],
+);
+const DOMAIN_JOIN_GUID: Guid = Guid::from_values(
+    0x1ce20aba,
+    0x9851,
+    0x4421,
+    [0x94, 0x30, 0x1d, 0xde, 0xb7, 0x66, 0xe8, 0x09],
+);
+const DOMAIN_LEAVE_GUID: Guid = Guid::from_values(
+    0xddaf516e,
+    0x58c2,
+    0x4866,
+    [0x95, 0x74, 0xc3, 0xb6, 0x15, 0xd4, 0x2e, 0xa1],
+);
+const FIREWALL_PORT_OPEN_GUID: Guid = Guid::from_values(
+    0xb7569e07,
+    0x8421,
+    0x4ee0,
+    [0xad, 0x10, 0x86, 0x91, 0x5a, 0xfd, 0xad, 0x09],
+);
+const FIREWALL_PORT_CLOSE_GUID: Guid = Guid::from_values(
+    0xa144ed38,
+    0x8e12,
+    0x4de4,
+    [0x9d, 0x96, 0xe6, 0x47, 0x40, 0xb1, 0xa5, 0x24],
+);
+const MACHINE_POLICY_PRESENT_GUID: Guid = Guid::from_values(
+    0x659fcae6,
+    0x5bdb,
+    0x4da9,
+    [0xb1, 0xff, 0xca, 0x2a, 0x17, 0x8d, 0x46, 0xe0],
+);
+const USER_POLICY_PRESENT_GUID: Guid = Guid::from_values(
+    0x54fb46c8,
+    0xf089,
+    0x464c,
+    [0xb1, 0xfd, 0x59, 0xd1, 0xb6, 0x2c, 0x3b, 0x50],
+);
+const RPC_INTERFACE_EVENT_GUID: Guid = Guid::from_values(
+    0xbc90d167,
+    0x9470,
+    0x4139,
+    [0xa9, 0xba, 0xbe, 0x0b, 0xbb, 0xf5, 0xb7, 0x4d],
+);
+const NAMED_PIPE_EVENT_GUID: Guid = Guid::from_values(
+    0x1f81d131,
+    0x3fac,
+    0x4537,
+    [0x9e, 0x0c, 0x7e, 0x7b, 0x0c, 0x2f, 0x4b, 0x55],
+);
+
+/// The kind of event a trigger reacts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum ServiceTriggerType {
+    /// A device of the interface class given by the trigger subtype arrives.
+    DeviceInterfaceArrival = winsvc::SERVICE_TRIGGER_TYPE_DEVICE_INTERFACE_ARRIVAL,
+    /// The first IP address becomes available, or the last one is removed.
+    IpAddressAvailability = winsvc::SERVICE_TRIGGER_TYPE_IP_ADDRESS_AVAILABILITY,
+    /// The computer joins or leaves a domain.
+    DomainJoin = winsvc::SERVICE_TRIGGER_TYPE_DOMAIN_JOIN,
+    /// A firewall port is opened or closed.
+    FirewallPortEvent = winsvc::SERVICE_TRIGGER_TYPE_FIREWALL_PORT_EVENT,
+    /// The machine or user group policy changes.
+    GroupPolicy = winsvc::SERVICE_TRIGGER_TYPE_GROUP_POLICY,
+    /// A packet or request arrives on a named pipe or RPC interface.
+    NetworkEndpoint = winsvc::SERVICE_TRIGGER_TYPE_NETWORK_ENDPOINT,
+    /// A custom system state changes.
+    CustomSystemStateChange = winsvc::SERVICE_TRIGGER_TYPE_CUSTOM_SYSTEM_STATE_CHANGE,
+    /// An event is written by the ETW provider given by the trigger subtype.
+    Custom = winsvc::SERVICE_TRIGGER_TYPE_CUSTOM,
+}
+
+impl ServiceTriggerType {
+    pub fn from_raw(raw_value: u32) -> Result<Self> {
+        let trigger_type = match raw_value {
+            x if x == ServiceTriggerType::DeviceInterfaceArrival.to_raw() => {
+                ServiceTriggerType::DeviceInterfaceArrival
+            }
+            x if x == ServiceTriggerType::IpAddressAvailability.to_raw() => {
+                ServiceTriggerType::IpAddressAvailability
+            }
+            x if x == ServiceTriggerType::DomainJoin.to_raw() => ServiceTriggerType::DomainJoin,
+            x if x == ServiceTriggerType::FirewallPortEvent.to_raw() => {
+                ServiceTriggerType::FirewallPortEvent
+            }
+            x if x == ServiceTriggerType::GroupPolicy.to_raw() => ServiceTriggerType::GroupPolicy,
+            x if x == ServiceTriggerType::NetworkEndpoint.to_raw() => {
+                ServiceTriggerType::NetworkEndpoint
+            }
+            x if x == ServiceTriggerType::CustomSystemStateChange.to_raw() => {
+                ServiceTriggerType::CustomSystemStateChange
+            }
+            x if x == ServiceTriggerType::Custom.to_raw() => ServiceTriggerType::Custom,
+            _ => Err(ErrorKind::InvalidServiceTriggerType(raw_value))?,
+        };
+        Ok(trigger_type)
+    }
+
+    pub fn to_raw(&self) -> u32 {
+        *self as u32
+    }
+}
+
+/// What the system does with the service when the trigger event occurs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum ServiceTriggerAction {
+    /// Start the service.
+    Start = winsvc::SERVICE_TRIGGER_ACTION_SERVICE_START,
+    /// Stop the service.
+    Stop = winsvc::SERVICE_TRIGGER_ACTION_SERVICE_STOP,
+}
+
+impl ServiceTriggerAction {
+    pub fn from_raw(raw_value: u32) -> Result<Self> {
+        let action = match raw_value {
+            x if x == ServiceTriggerAction::Start.to_raw() => ServiceTriggerAction::Start,
+            x if x == ServiceTriggerAction::Stop.to_raw() => ServiceTriggerAction::Stop,
+            _ => Err(ErrorKind::InvalidServiceTriggerAction(raw_value))?,
+        };
+        Ok(action)
+    }
+
+    pub fn to_raw(&self) -> u32 {
+        *self as u32
+    }
+}
+
+/// Data that narrows down the events a trigger reacts to, for example a hardware ID.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ServiceTriggerDataItem {
+    /// A string.
+    String(#[serde(with = "schema::os_string")] OsString),
+    /// A list of strings. The system stores strings and lists of strings the same way, so a list
+    /// with a single string is read back as [`ServiceTriggerDataItem::String`].
+    MultiString(#[serde(with = "schema::os_string::vec")] Vec<OsString>),
+    /// Binary data, such as the state name of a custom system state change.
+    Binary(Vec<u8>),
+    /// The level of an ETW event.
+    Level(u8),
+    /// A bit mask of ETW event keywords, any of which must be set on the event.
+    KeywordAny(u64),
+    /// A bit mask of ETW event keywords, all of which must be set on the event.
+    KeywordAll(u64),
+}
+
+/// A service trigger.
+///
+/// The constructors create triggers that start the service, use [`ServiceTrigger::action`] to
+/// stop it instead.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceTrigger {
+    /// The kind of event the trigger reacts to.
+    pub trigger_type: ServiceTriggerType,
+
+    /// What the system does when the event occurs.
+    pub action: ServiceTriggerAction,
+
+    /// The specific event of the trigger type, such as a device interface class or an ETW
+    /// provider.
+    pub subtype: Guid,
+
+    /// Data items that narrow down the events. The trigger fires if any of them matches.
+    pub data: Vec<ServiceTriggerDataItem>,
+}
+
+impl ServiceTrigger {
+    /// Create a trigger that starts the service when the event occurs.
+    pub fn new(trigger_type: ServiceTriggerType, subtype: Guid) -> Self {
+        ServiceTrigger {
+            trigger_type,
+            action: ServiceTriggerAction::Start,
+            subtype,
+            data: vec![],
+        }
+    }
+
+    /// Trigger on the arrival of a device of the given interface class. Narrow it down with the
+    /// hardware or compatible IDs of the device as string data items.
+    pub fn device_interface_arrival(interface_class: Guid) -> Self {
+        ServiceTrigger::new(ServiceTriggerType::DeviceInterfaceArrival, interface_class)
+    }
+
+    /// Trigger when the first IP address becomes available.
+    pub fn first_ip_address_arrival() -> Self {
+        ServiceTrigger::new(
+            ServiceTriggerType::IpAddressAvailability,
+            NETWORK_MANAGER_FIRST_IP_ADDRESS_ARRIVAL_GUID,
+        )
+    }
+
+    /// Trigger when the last IP address is removed.
+    pub fn last_ip_address_removal() -> Self {
+        ServiceTrigger::new(
+            ServiceTriggerType::IpAddressAvailability,
+            NETWORK_MANAGER_LAST_IP_ADDRESS_REMOVAL_GUID,
+        )
+    }
+
+    /// Trigger when the computer joins a domain.
+    pub fn domain_join() -> Self {
+        ServiceTrigger::new(ServiceTriggerType::DomainJoin, DOMAIN_JOIN_GUID)
+    }
+
+    /// Trigger when the computer leaves a domain.
+    pub fn domain_leave() -> Self {
+        ServiceTrigger::new(ServiceTriggerType::DomainJoin, DOMAIN_LEAVE_GUID)
+    }
+
+    /// Trigger when a firewall port is opened for the protocol, such as `TCP` or `UDP`.
+    pub fn firewall_port_open<S: Into<OsString>>(port: u16, protocol: S) -> Self {
+        ServiceTrigger::new(ServiceTriggerType::FirewallPortEvent, FIREWALL_PORT_OPEN_GUID)
+            .multi_string(vec![OsString::from(port.to_string()), protocol.into()])
+    }
+
+    /// Trigger when a firewall port is closed for the protocol, such as `TCP` or `UDP`.
+    pub fn firewall_port_close<S: Into<OsString>>(port: u16, protocol: S) -> Self {
+        ServiceTrigger::new(ServiceTriggerType::FirewallPortEvent, FIREWALL_PORT_CLOSE_GUID)
+            .multi_string(vec![OsString::from(port.to_string()), protocol.into()])
+    }
+
+    /// Trigger when the machine group policy changes.
+    pub fn machine_policy_present() -> Self {
+        ServiceTrigger::new(ServiceTriggerType::GroupPolicy, MACHINE_POLICY_PRESENT_GUID)
+    }
+
+    /// Trigger when the user group policy changes.
+    pub fn user_policy_present() -> Self {
+        ServiceTrigger::new(ServiceTriggerType::GroupPolicy, USER_POLICY_PRESENT_GUID)
+    }
+
+    /// Trigger when a request arrives on the named pipe, given without the `\\.\pipe\` prefix.
+    pub fn named_pipe<S: Into<OsString>>(pipe_name: S) -> Self {
+        ServiceTrigger::new(ServiceTriggerType::NetworkEndpoint, NAMED_PIPE_EVENT_GUID)
+            .string(pipe_name)
+    }
+
+    /// Trigger when a request arrives on the RPC interface.
+    pub fn rpc_interface(interface: Guid) -> Self {
+        ServiceTrigger::new(ServiceTriggerType::NetworkEndpoint, RPC_INTERFACE_EVENT_GUID)
+            .string(interface.to_string())
+    }
+
+    /// Trigger on the events of the ETW provider. Narrow them down with level and keyword data
+    /// items.
+    pub fn custom(provider: Guid) -> Self {
+        ServiceTrigger::new(ServiceTriggerType::Custom, provider)
+    }
+
+    /// Set what the system does when the event occurs.
+    pub fn action(mut self, action: ServiceTriggerAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Add a string data item.
+    pub fn string<S: Into<OsString>>(mut self, string: S) -> Self {
+        self.data.push(ServiceTriggerDataItem::String(string.into()));
+        self
+    }
+
+    /// Add a data item holding a list of strings.
+    pub fn multi_string<I, S>(mut self, strings: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        let strings = strings.into_iter().map(Into::into).collect();
+        self.data.push(ServiceTriggerDataItem::MultiString(strings));
+        self
+    }
+
+    /// Add a binary data item.
+    pub fn binary<B: Into<Vec<u8>>>(mut self, data: B) -> Self {
+        self.data.push(ServiceTriggerDataItem::Binary(data.into()));
+        self
+    }
+
+    /// Add an ETW event level data item.
+    pub fn level(mut self, level: u8) -> Self {
+        self.data.push(ServiceTriggerDataItem::Level(level));
+        self
+    }
+
+    /// Add an ETW event keyword data item, matching events with any of the keywords.
+    pub fn keyword_any(mut self, keywords: u64) -> Self {
+        self.data.push(ServiceTriggerDataItem::KeywordAny(keywords));
+        self
+    }
+
+    /// Add an ETW event keyword data item, matching events with all of the keywords.
+    pub fn keyword_all(mut self, keywords: u64) -> Self {
+        self.data.push(ServiceTriggerDataItem::KeywordAll(keywords));
+        self
+    }
+
+    pub(crate) fn to_raw(&self) -> Result<RawServiceTrigger> {
+        Ok(RawServiceTrigger {
+            trigger_type: self.trigger_type.to_raw(),
+            action: self.action.to_raw(),
+            subtype: self.subtype,
+            data: self
+                .data
+                .iter()
+                .map(ServiceTriggerDataItem::to_raw)
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    #[cfg(any(windows, test))]
+    pub(crate) fn from_raw(raw: &RawServiceTrigger) -> Result<Self> {
+        Ok(ServiceTrigger {
+            trigger_type: ServiceTriggerType::from_raw(raw.trigger_type)?,
+            action: ServiceTriggerAction::from_raw(raw.action)?,
+            subtype: raw.subtype,
+            data: raw
+                .data
+                .iter()
+                .map(ServiceTriggerDataItem::from_raw)
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl ServiceTriggerDataItem {
+    fn to_raw(&self) -> Result<RawTriggerDataItem> {
+        let (data_type, data) = match *self {
+            ServiceTriggerDataItem::String(ref string) => {
+                let string = WideCString::from_str(string)
+                    .chain_err(|| ErrorKind::InvalidServiceTriggerData)?;
+                let data = wide_bytes(string.as_slice_with_nul());
+                (winsvc::SERVICE_TRIGGER_DATA_TYPE_STRING, data)
+            }
+            ServiceTriggerDataItem::MultiString(ref strings) => {
+                let strings = wide_multi_string(strings.iter().cloned())
+                    .ok_or(ErrorKind::InvalidServiceTriggerData)?;
+                (winsvc::SERVICE_TRIGGER_DATA_TYPE_STRING, wide_bytes(&strings))
+            }
+            ServiceTriggerDataItem::Binary(ref data) => {
+                (winsvc::SERVICE_TRIGGER_DATA_TYPE_BINARY, data.clone())
+            }
+            ServiceTriggerDataItem::Level(level) => {
+                (winsvc::SERVICE_TRIGGER_DATA_TYPE_LEVEL, vec![level])
+            }
+            ServiceTriggerDataItem::KeywordAny(keywords) => (
+                winsvc::SERVICE_TRIGGER_DATA_TYPE_KEYWORD_ANY,
+                keywords.to_le_bytes().to_vec(),
+            ),
+            ServiceTriggerDataItem::KeywordAll(keywords) => (
+                winsvc::SERVICE_TRIGGER_DATA_TYPE_KEYWORD_ALL,
+                keywords.to_le_bytes().to_vec(),
+            ),
+        };
+        Ok(RawTriggerDataItem { data_type, data })
+    }
+
+    #[cfg(any(windows, test))]
+    fn from_raw(raw: &RawTriggerDataItem) -> Result<Self> {
+        let keywords = || -> Result<u64> {
+            let mut bytes = [0u8; 8];
+            if raw.data.len() != bytes.len() {
+                Err(ErrorKind::InvalidServiceTriggerData)?;
+            }
+            bytes.copy_from_slice(&raw.data);
+            Ok(u64::from_le_bytes(bytes))
+        };
+
+        let item = match raw.data_type {
+            winsvc::SERVICE_TRIGGER_DATA_TYPE_STRING => {
+                if raw.data.len() % 2 == 1 {
+                    Err(ErrorKind::InvalidServiceTriggerData)?;
+                }
+                let mut code_units: Vec<u16> = raw
+                    .data
+                    .chunks(2)
+                    .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                    .collect();
+                while code_units.last() == Some(&0) {
+                    code_units.pop();
+                }
+                let mut strings: Vec<OsString> = code_units
+                    .split(|&code_unit| code_unit == 0)
+                    .map(|string| WideString::from_vec(string).to_os_string())
+                    .collect();
+                if strings.len() == 1 {
+                    ServiceTriggerDataItem::String(strings.remove(0))
+                } else {
+                    ServiceTriggerDataItem::MultiString(strings)
+                }
+            }
+            winsvc::SERVICE_TRIGGER_DATA_TYPE_BINARY => {
+                ServiceTriggerDataItem::Binary(raw.data.clone())
+            }
+            winsvc::SERVICE_TRIGGER_DATA_TYPE_LEVEL => match raw.data[..] {
+                [level] => ServiceTriggerDataItem::Level(level),
+                _ => Err(ErrorKind::InvalidServiceTriggerData)?,
+            },
+            winsvc::SERVICE_TRIGGER_DATA_TYPE_KEYWORD_ANY => {
+                ServiceTriggerDataItem::KeywordAny(keywords()?)
+            }
+            winsvc::SERVICE_TRIGGER_DATA_TYPE_KEYWORD_ALL => {
+                ServiceTriggerDataItem::KeywordAll(keywords()?)
+            }
+            _ => Err(ErrorKind::InvalidServiceTriggerDataType(raw.data_type))?,
+        };
+        Ok(item)
+    }
+}
+
+/// The values of `SERVICE_TRIGGER`, with the data it points to held in owned buffers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RawServiceTrigger {
+    pub trigger_type: u32,
+    pub action: u32,
+    pub subtype: Guid,
+    pub data: Vec<RawTriggerDataItem>,
+}
+
+/// The values of `SERVICE_TRIGGER_SPECIFIC_DATA_ITEM`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RawTriggerDataItem {
+    pub data_type: u32,
+    pub data: Vec<u8>,
+}
+
+/// Private helper to lay out wide strings as little-endian bytes.
+fn wide_bytes(code_units: &[u16]) -> Vec<u8> {
+    code_units
+        .iter()
+        .flat_map(|code_unit| code_unit.to_le_bytes().to_vec())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usb_device() -> Guid {
+        "a5dcbf10-6530-11d2-901f-00c04fb951ed".parse().unwrap()
+    }
+
+    #[test]
+    fn test_trigger_to_raw() {
+        let trigger = ServiceTrigger::device_interface_arrival(usb_device())
+            .string("USB")
+            .level(4)
+            .keyword_all(0x0102)
+            .action(ServiceTriggerAction::Stop);
+        let raw = trigger.to_raw().unwrap();
+
+        assert_eq!(
+            raw.trigger_type,
+            winsvc::SERVICE_TRIGGER_TYPE_DEVICE_INTERFACE_ARRIVAL
+        );
+        assert_eq!(raw.action, winsvc::SERVICE_TRIGGER_ACTION_SERVICE_STOP);
+        assert_eq!(raw.subtype, usb_device());
+        assert_eq!(
+            raw.data,
+            vec![
+                RawTriggerDataItem {
+                    data_type: winsvc::SERVICE_TRIGGER_DATA_TYPE_STRING,
+                    data: vec![b'U', 0, b'S', 0, b'B', 0, 0, 0],
+                },
+                RawTriggerDataItem {
+                    data_type: winsvc::SERVICE_TRIGGER_DATA_TYPE_LEVEL,
+                    data: vec![4],
+                },
+                RawTriggerDataItem {
+                    data_type: winsvc::SERVICE_TRIGGER_DATA_TYPE_KEYWORD_ALL,
+                    data: vec![0x02, 0x01, 0, 0, 0, 0, 0, 0],
+                },
+            ]
+        );
+        assert_eq!(ServiceTrigger::from_raw(&raw).unwrap(), trigger);
+    }
+
+    #[test]
+    fn test_multi_string_raw_round_trip() {
+        let trigger = ServiceTrigger::firewall_port_open(5001, "TCP");
+        let raw = trigger.to_raw().unwrap();
+        assert_eq!(
+            raw.data[0].data,
+            vec![b'5', 0, b'0', 0, b'0', 0, b'1', 0, 0, 0, b'T', 0, b'C', 0, b'P', 0, 0, 0, 0, 0]
+        );
+        assert_eq!(ServiceTrigger::from_raw(&raw).unwrap(), trigger);
+
+        // A list with a single string is indistinguishable from a string.
+        let raw = ServiceTrigger::domain_join()
+            .multi_string(vec!["CONTOSO"])
+            .to_raw()
+            .unwrap();
+        assert_eq!(
+            ServiceTrigger::from_raw(&raw).unwrap(),
+            ServiceTrigger::domain_join().string("CONTOSO")
+        );
+    }
+
+    #[test]
+    fn test_invalid_trigger_data() {
+        assert!(ServiceTrigger::named_pipe("a\0b").to_raw().is_err());
+        assert!(ServiceTrigger::domain_join()
+            .multi_string(vec!["", "b"])
+            .to_raw()
+            .is_err());
+
+        let item = |data_type, data| RawTriggerDataItem { data_type, data };
+        for invalid in &[
+            item(winsvc::SERVICE_TRIGGER_DATA_TYPE_STRING, vec![b'a']),
+            item(winsvc::SERVICE_TRIGGER_DATA_TYPE_LEVEL, vec![]),
+            item(winsvc::SERVICE_TRIGGER_DATA_TYPE_KEYWORD_ANY, vec![0; 4]),
+            item(42, vec![]),
+        ] {
+            assert!(ServiceTriggerDataItem::from_raw(invalid).is_err());
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_trigger_subtype_constants() {
+        use winapi::um::winsvc as raw_winsvc;
+
+        assert_eq!(
+            NETWORK_MANAGER_FIRST_IP_ADDRESS_ARRIVAL_GUID,
+            Guid::from(raw_winsvc::NETWORK_MANAGER_FIRST_IP_ADDRESS_ARRIVAL_GUID)
+        );
+        assert_eq!(
+            NETWORK_MANAGER_LAST_IP_ADDRESS_REMOVAL_GUID,
+            Guid::from(raw_winsvc::NETWORK_MANAGER_LAST_IP_ADDRESS_REMOVAL_GUID)
+        );
+        assert_eq!(DOMAIN_JOIN_GUID, Guid::from(raw_winsvc::DOMAIN_JOIN_GUID));
+        assert_eq!(DOMAIN_LEAVE_GUID, Guid::from(raw_winsvc::DOMAIN_LEAVE_GUID));
+        assert_eq!(
+            FIREWALL_PORT_OPEN_GUID,
+            Guid::from(raw_winsvc::FIREWALL_PORT_OPEN_GUID)
+        );
+        assert_eq!(
+            FIREWALL_PORT_CLOSE_GUID,
+            Guid::from(raw_winsvc::FIREWALL_PORT_CLOSE_GUID)
+        );
+        assert_eq!(
+            MACHINE_POLICY_PRESENT_GUID,
+            Guid::from(raw_winsvc::MACHINE_POLICY_PRESENT_GUID)
+        );
+        assert_eq!(
+            USER_POLICY_PRESENT_GUID,
+            Guid::from(raw_winsvc::USER_POLICY_PRESENT_GUID)
+        );
+        assert_eq!(
+            RPC_INTERFACE_EVENT_GUID,
+            Guid::from(raw_winsvc::RPC_INTERFACE_EVENT_GUID)
+        );
+        assert_eq!(
+            NAMED_PIPE_EVENT_GUID,
+            Guid::from(raw_winsvc::NAMED_PIPE_EVENT_GUID)
+        );
+    }
+}
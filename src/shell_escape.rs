@@ -1,7 +1,10 @@
+//! Escaping and parsing of command lines following the rules of the Windows C runtime.
+
 use std::borrow::Cow;
 use std::ffi::{OsStr, OsString};
-use std::iter::repeat;
-use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::iter::{repeat, Peekable};
+
+use widestring::WideString;
 
 /// Common UTF-16 code points.
 mod utf16 {
@@ -15,25 +18,29 @@ mod utf16 {
 
 /// Loselessly escape shell arguments on Windows.
 ///
+/// On other platforms the argument is converted to UTF-16 first, replacing invalid sequences
+/// with the replacement character.
+///
 /// Inspired by https://blogs.msdn.microsoft.com/twistylittlepassagesallalike/2011/04/23/everyone-quotes-command-line-arguments-the-wrong-way/.
 /// Heavily based on https://github.com/sfackler/shell-escape
 pub fn escape(s: Cow<OsStr>) -> Cow<OsStr> {
-    static ESCAPE_CHARS: &'static [u16] = &[
+    static ESCAPE_CHARS: &[u16] = &[
         utf16::DOUBLEQUOTE,
         utf16::SPACE,
         utf16::LINEFEED,
         utf16::HTAB,
         utf16::VTAB,
     ];
-    let needs_escape = s.is_empty() || s.encode_wide().any(|ref c| ESCAPE_CHARS.contains(c));
+    let wide = WideString::from_str(&s);
+    let needs_escape = s.is_empty() || wide.as_slice().iter().any(|c| ESCAPE_CHARS.contains(c));
     if !needs_escape {
         return s;
     }
 
-    let mut escaped_wide_string: Vec<u16> = Vec::with_capacity(s.len() + 2);
+    let mut escaped_wide_string: Vec<u16> = Vec::with_capacity(wide.len() + 2);
     escaped_wide_string.push(utf16::DOUBLEQUOTE);
 
-    let mut chars = s.encode_wide().peekable();
+    let mut chars = wide.as_slice().iter().cloned().peekable();
     loop {
         let mut num_slashes = 0;
         while let Some(&utf16::BACKSLASH) = chars.peek() {
@@ -59,7 +66,108 @@ pub fn escape(s: Cow<OsStr>) -> Cow<OsStr> {
 
     escaped_wide_string.push(utf16::DOUBLEQUOTE);
 
-    Cow::Owned(OsString::from_wide(&escaped_wide_string))
+    Cow::Owned(WideString::from_vec(escaped_wide_string).to_os_string())
+}
+
+/// Split a command line into the program name followed by its arguments.
+///
+/// This is the inverse of [`escape`], and follows the same rules as `CommandLineToArgvW`: the
+/// program name extends to the next whitespace unless it is quoted, in which case it extends to
+/// the closing quote and backslashes have no special meaning. The arguments that follow are
+/// separated by whitespace, may be quoted, and use backslashes to escape quotes.
+pub fn split(command_line: &OsStr) -> Vec<OsString> {
+    let wide = WideString::from_str(command_line);
+    let mut chars = wide.as_slice().iter().cloned().peekable();
+    let mut arguments = Vec::new();
+
+    // The program name
+    let mut program = Vec::new();
+    if chars.peek() == Some(&utf16::DOUBLEQUOTE) {
+        chars.next();
+        program.extend(chars.by_ref().take_while(|&c| c != utf16::DOUBLEQUOTE));
+    } else {
+        while let Some(&c) = chars.peek() {
+            if is_separator(c) {
+                break;
+            }
+            program.push(c);
+            chars.next();
+        }
+    }
+    if wide.is_empty() {
+        return arguments;
+    }
+    arguments.push(WideString::from_vec(program).to_os_string());
+    push_arguments(&mut chars, &mut arguments);
+    arguments
+}
+
+/// Split the arguments that follow the program name in a command line.
+///
+/// Same as [`split`] without the program name, so that the first argument follows the rules of
+/// the other arguments.
+pub fn split_arguments(arguments: &OsStr) -> Vec<OsString> {
+    let wide = WideString::from_str(arguments);
+    let mut chars = wide.as_slice().iter().cloned().peekable();
+    let mut split_arguments = Vec::new();
+    push_arguments(&mut chars, &mut split_arguments);
+    split_arguments
+}
+
+/// Parse whitespace separated arguments until the end of the command line.
+fn push_arguments<I: Iterator<Item = u16>>(chars: &mut Peekable<I>, arguments: &mut Vec<OsString>) {
+    loop {
+        while chars.peek().map_or(false, |&c| is_separator(c)) {
+            chars.next();
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut argument = Vec::new();
+        let mut in_quotes = false;
+        loop {
+            let mut num_slashes = 0;
+            while let Some(&utf16::BACKSLASH) = chars.peek() {
+                chars.next();
+                num_slashes += 1;
+            }
+
+            match chars.peek().cloned() {
+                Some(utf16::DOUBLEQUOTE) => {
+                    chars.next();
+                    argument.extend(repeat(utf16::BACKSLASH).take(num_slashes / 2));
+                    if num_slashes % 2 == 1 {
+                        argument.push(utf16::DOUBLEQUOTE);
+                    } else if in_quotes && chars.peek() == Some(&utf16::DOUBLEQUOTE) {
+                        // A doubled quote within quotes is a literal quote.
+                        chars.next();
+                        argument.push(utf16::DOUBLEQUOTE);
+                    } else {
+                        in_quotes = !in_quotes;
+                    }
+                }
+                Some(c) if is_separator(c) && !in_quotes => {
+                    argument.extend(repeat(utf16::BACKSLASH).take(num_slashes));
+                    break;
+                }
+                Some(c) => {
+                    chars.next();
+                    argument.extend(repeat(utf16::BACKSLASH).take(num_slashes));
+                    argument.push(c);
+                }
+                None => {
+                    argument.extend(repeat(utf16::BACKSLASH).take(num_slashes));
+                    break;
+                }
+            }
+        }
+        arguments.push(WideString::from_vec(argument).to_os_string());
+    }
+}
+
+fn is_separator(c: u16) -> bool {
+    c == utf16::SPACE || c == utf16::HTAB
 }
 
 #[cfg(test)]
@@ -117,4 +225,69 @@ mod tests {
             OsStr::new(r#""\some\directory with\spaces\\""#)
         );
     }
+
+    #[test]
+    fn test_split_empty() {
+        assert!(split(OsStr::new("")).is_empty());
+    }
+
+    #[test]
+    fn test_split_quoted_program() {
+        assert_eq!(
+            split(OsStr::new(r#""C:\Program Files\service.exe" --flag  "a b""#)),
+            vec![
+                OsString::from(r"C:\Program Files\service.exe"),
+                OsString::from("--flag"),
+                OsString::from("a b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_unquoted_program() {
+        assert_eq!(
+            split(OsStr::new(r"C:\Windows\system32\svchost.exe -k netsvcs -p")),
+            vec![
+                OsString::from(r"C:\Windows\system32\svchost.exe"),
+                OsString::from("-k"),
+                OsString::from("netsvcs"),
+                OsString::from("-p"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_arguments() {
+        assert_eq!(
+            split_arguments(OsStr::new(r#" \"quoted\" "C:\a b\\" -x"#)),
+            vec![
+                OsString::from(r#""quoted""#),
+                OsString::from(r"C:\a b\"),
+                OsString::from("-x"),
+            ]
+        );
+        assert!(split_arguments(OsStr::new("  ")).is_empty());
+    }
+
+    #[test]
+    fn test_split_reverses_escape() {
+        let arguments = [
+            "--aaa=bbb-ccc",
+            "",
+            "linker=gcc -L/foo -Wl,bar",
+            r#"--features="default""#,
+            r#"hello \\\"quote\\\""#,
+            r"\some\directory with\spaces\",
+            r"C:\path\",
+        ];
+        let mut command_line = OsString::from("program.exe");
+        for argument in arguments.iter() {
+            command_line.push(" ");
+            command_line.push(escape(Cow::Borrowed(OsStr::new(argument))));
+        }
+
+        let mut expected = vec![OsString::from("program.exe")];
+        expected.extend(arguments.iter().map(OsString::from));
+        assert_eq!(split(&command_line), expected);
+    }
 }
@@ -0,0 +1,420 @@
+//! Reporting the status of a running service without managing checkpoints and wait hints by hand.
+//!
+//! [`StatusReporter`] keeps track of the last reported status. It increments the checkpoint each
+//! time the service reports progress in a pending state, can keep reporting progress from a
+//! background thread during a lengthy operation, and refuses transitions that the service
+//! control manager does not expect, such as reporting [`ServiceState::Paused`] while the service
+//! is starting.
+//!
+//! [`ServiceState::Paused`]: super::service::ServiceState::Paused
+
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use service::{ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType};
+use {ErrorKind, Result};
+
+/// A destination for the status reports of a service.
+///
+/// Implemented by [`ServiceStatusHandle`] to report the status to the system, and by
+/// [`SimulatedScm`] for tests.
+///
+/// [`ServiceStatusHandle`]: super::service_control_handler::ServiceStatusHandle
+/// [`SimulatedScm`]: super::service_runner::SimulatedScm
+pub trait StatusSink: Send {
+    /// Report the service status.
+    fn report(&self, status: ServiceStatus) -> Result<()>;
+}
+
+#[cfg(windows)]
+impl StatusSink for ::service_control_handler::ServiceStatusHandle {
+    fn report(&self, status: ServiceStatus) -> Result<()> {
+        Ok(self.set_service_status(status)?)
+    }
+}
+
+/// Reports the status of a service, tracking its state, checkpoint and wait hint.
+///
+/// The reporter starts out in the [`ServiceState::StartPending`] state that the system puts a
+/// service in when launching it. Each report is checked against the current state, so that for
+/// example a stopped service cannot report being running again, and
+/// [`ErrorKind::InvalidStateTransition`] is returned instead of reporting the status.
+///
+/// The reporter can be cloned to report the status from several threads.
+///
+/// # Example
+///
+/// ```rust
+/// use std::time::Duration;
+/// use windows_service::service::{
+///     ServiceControlAccept, ServiceExitCode, ServiceState, ServiceType,
+/// };
+/// use windows_service::service_runner::SimulatedScm;
+/// use windows_service::status_reporter::StatusReporter;
+///
+/// # fn main() -> windows_service::Result<()> {
+/// // Use the `ServiceStatusHandle` returned by `service_control_handler::register` instead.
+/// let status_handle = SimulatedScm::new();
+/// let reporter = StatusReporter::new(status_handle.clone(), ServiceType::OwnProcess);
+///
+/// reporter.pending(ServiceState::StartPending, Duration::from_secs(5))?;
+/// // Load the configuration...
+/// reporter.progress()?;
+/// // Open the sockets...
+/// reporter.running(ServiceControlAccept::STOP)?;
+/// assert_eq!(status_handle.statuses().len(), 3);
+///
+/// reporter.pending(ServiceState::StopPending, Duration::from_secs(5))?;
+/// reporter.stopped(ServiceExitCode::default())?;
+/// assert!(reporter.running(ServiceControlAccept::STOP).is_err());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`ErrorKind::InvalidStateTransition`]: ::ErrorKind::InvalidStateTransition
+#[derive(Clone)]
+pub struct StatusReporter {
+    shared: Arc<Mutex<Shared>>,
+}
+
+struct Shared {
+    sink: Box<dyn StatusSink>,
+    status: ServiceStatus,
+}
+
+impl StatusReporter {
+    /// Create a reporter for a service of the given type that is starting. Nothing is reported
+    /// until one of the reporting methods is called.
+    pub fn new<S: StatusSink + 'static>(sink: S, service_type: ServiceType) -> Self {
+        StatusReporter {
+            shared: Arc::new(Mutex::new(Shared {
+                sink: Box::new(sink),
+                status: ServiceStatus {
+                    service_type,
+                    current_state: ServiceState::StartPending,
+                    controls_accepted: ServiceControlAccept::empty(),
+                    exit_code: ServiceExitCode::default(),
+                    checkpoint: 0,
+                    wait_hint: Duration::default(),
+                },
+            })),
+        }
+    }
+
+    /// Returns the last reported status.
+    pub fn status(&self) -> ServiceStatus {
+        self.shared.lock().unwrap().status.clone()
+    }
+
+    /// Returns the current state.
+    pub fn state(&self) -> ServiceState {
+        self.shared.lock().unwrap().status.current_state
+    }
+
+    /// Report that the service entered a pending state, which it is expected to leave within the
+    /// wait hint. The checkpoint starts over at 1, and no controls are accepted until the service
+    /// reaches the next state.
+    ///
+    /// Reporting the current pending state again reports progress with the new wait hint.
+    pub fn pending(&self, state: ServiceState, wait_hint: Duration) -> Result<()> {
+        let mut shared = self.shared.lock().unwrap();
+        let current_state = shared.status.current_state;
+        if !is_pending(state) || !is_valid_transition(current_state, state) {
+            Err(ErrorKind::InvalidStateTransition(current_state, state))?;
+        }
+        let checkpoint = if state == current_state {
+            shared.status.checkpoint + 1
+        } else {
+            1
+        };
+        let status = ServiceStatus {
+            current_state: state,
+            controls_accepted: ServiceControlAccept::empty(),
+            exit_code: ServiceExitCode::default(),
+            checkpoint,
+            wait_hint,
+            ..shared.status.clone()
+        };
+        shared.update(status)
+    }
+
+    /// Report progress in the current pending state by incrementing the checkpoint.
+    ///
+    /// Fails with [`ErrorKind::ServiceNotPending`] if the service is not in a pending state.
+    ///
+    /// [`ErrorKind::ServiceNotPending`]: ::ErrorKind::ServiceNotPending
+    pub fn progress(&self) -> Result<()> {
+        self.shared.lock().unwrap().progress()
+    }
+
+    /// Report progress from a background thread every `interval` until the returned
+    /// [`Heartbeat`] is dropped or the service leaves the pending state.
+    ///
+    /// Use this while the service is busy with a single lengthy step and cannot report progress
+    /// itself. The interval should be well below the wait hint. Failures to report the progress
+    /// are ignored by the heartbeat, they show up in the next report made through the reporter.
+    pub fn heartbeat(&self, interval: Duration) -> Heartbeat {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let shared = self.shared.clone();
+        let thread = thread::spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let mut shared = shared.lock().unwrap();
+                if !is_pending(shared.status.current_state) {
+                    break;
+                }
+                let _ = shared.progress();
+            }
+        });
+        Heartbeat {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Report that the service is running and accepts the given controls.
+    pub fn running(&self, controls_accepted: ServiceControlAccept) -> Result<()> {
+        self.settle(
+            ServiceState::Running,
+            controls_accepted,
+            ServiceExitCode::default(),
+        )
+    }
+
+    /// Report that the service is paused and accepts the given controls.
+    pub fn paused(&self, controls_accepted: ServiceControlAccept) -> Result<()> {
+        self.settle(
+            ServiceState::Paused,
+            controls_accepted,
+            ServiceExitCode::default(),
+        )
+    }
+
+    /// Report that the service stopped with the given exit code. No further status can be
+    /// reported after that.
+    pub fn stopped(&self, exit_code: ServiceExitCode) -> Result<()> {
+        self.settle(
+            ServiceState::Stopped,
+            ServiceControlAccept::empty(),
+            exit_code,
+        )
+    }
+
+    fn settle(
+        &self,
+        state: ServiceState,
+        controls_accepted: ServiceControlAccept,
+        exit_code: ServiceExitCode,
+    ) -> Result<()> {
+        let mut shared = self.shared.lock().unwrap();
+        let current_state = shared.status.current_state;
+        if !is_valid_transition(current_state, state) {
+            Err(ErrorKind::InvalidStateTransition(current_state, state))?;
+        }
+        let status = ServiceStatus {
+            current_state: state,
+            controls_accepted,
+            exit_code,
+            checkpoint: 0,
+            wait_hint: Duration::default(),
+            ..shared.status.clone()
+        };
+        shared.update(status)
+    }
+}
+
+impl Shared {
+    fn progress(&mut self) -> Result<()> {
+        if !is_pending(self.status.current_state) {
+            Err(ErrorKind::ServiceNotPending(self.status.current_state))?;
+        }
+        let status = ServiceStatus {
+            checkpoint: self.status.checkpoint + 1,
+            ..self.status.clone()
+        };
+        self.update(status)
+    }
+
+    /// Report the status, keeping it as the current one if it was reported.
+    fn update(&mut self, status: ServiceStatus) -> Result<()> {
+        self.sink.report(status.clone())?;
+        self.status = status;
+        Ok(())
+    }
+}
+
+/// Reports progress from a background thread, see [`StatusReporter::heartbeat`]. Dropping the
+/// heartbeat stops it.
+#[derive(Debug)]
+pub struct Heartbeat {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        // Disconnecting the channel wakes the thread up.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn is_pending(state: ServiceState) -> bool {
+    !matches!(
+        state,
+        ServiceState::Running | ServiceState::Paused | ServiceState::Stopped
+    )
+}
+
+/// Whether a service may report `to` after reporting `from`, following the service state
+/// transitions documented for the service control manager.
+fn is_valid_transition(from: ServiceState, to: ServiceState) -> bool {
+    use service::ServiceState::*;
+
+    match (from, to) {
+        (Stopped, _) => false,
+        (StopPending, StopPending) | (StopPending, Stopped) => true,
+        (StopPending, _) => false,
+        // A service can stop from any other state, for example when it fails.
+        (_, StopPending) | (_, Stopped) => true,
+        (StartPending, StartPending) | (StartPending, Running) => true,
+        (Running, Running) | (Running, PausePending) => true,
+        (PausePending, PausePending) | (PausePending, Paused) | (PausePending, Running) => true,
+        (Paused, Paused) | (Paused, ContinuePending) => true,
+        (ContinuePending, ContinuePending)
+        | (ContinuePending, Running)
+        | (ContinuePending, Paused) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use service_runner::SimulatedScm;
+
+    fn reporter() -> (SimulatedScm, StatusReporter) {
+        let scm = SimulatedScm::new();
+        let reporter = StatusReporter::new(scm.clone(), ServiceType::OwnProcess);
+        (scm, reporter)
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        let (scm, reporter) = reporter();
+        reporter
+            .pending(ServiceState::StartPending, Duration::from_millis(1500))
+            .unwrap();
+        reporter.progress().unwrap();
+        reporter
+            .pending(ServiceState::StartPending, Duration::from_secs(3))
+            .unwrap();
+        reporter.running(ServiceControlAccept::STOP).unwrap();
+        reporter
+            .pending(ServiceState::StopPending, Duration::from_secs(1))
+            .unwrap();
+        reporter.stopped(ServiceExitCode::Win32(5)).unwrap();
+
+        let reported: Vec<_> = scm
+            .statuses()
+            .into_iter()
+            .map(|status| {
+                (
+                    status.current_state,
+                    status.checkpoint,
+                    status.wait_hint,
+                    status.controls_accepted,
+                )
+            })
+            .collect();
+        let none = ServiceControlAccept::empty();
+        assert_eq!(
+            reported,
+            vec![
+                (
+                    ServiceState::StartPending,
+                    1,
+                    Duration::from_millis(1500),
+                    none
+                ),
+                (
+                    ServiceState::StartPending,
+                    2,
+                    Duration::from_millis(1500),
+                    none
+                ),
+                (ServiceState::StartPending, 3, Duration::from_secs(3), none),
+                (
+                    ServiceState::Running,
+                    0,
+                    Duration::default(),
+                    ServiceControlAccept::STOP
+                ),
+                (ServiceState::StopPending, 1, Duration::from_secs(1), none),
+                (ServiceState::Stopped, 0, Duration::default(), none),
+            ]
+        );
+        assert_eq!(scm.status().unwrap().exit_code, ServiceExitCode::Win32(5));
+    }
+
+    #[test]
+    fn test_invalid_transitions() {
+        let (scm, reporter) = reporter();
+        reporter.running(ServiceControlAccept::STOP).unwrap();
+        match reporter.progress().unwrap_err().kind() {
+            ErrorKind::ServiceNotPending(ServiceState::Running) => (),
+            other => panic!("Unexpected error: {:?}", other),
+        }
+
+        let invalid = [
+            (ServiceState::ContinuePending, Duration::from_secs(1)),
+            (ServiceState::StartPending, Duration::from_secs(1)),
+        ];
+        for &(state, wait_hint) in &invalid {
+            match reporter.pending(state, wait_hint).unwrap_err().kind() {
+                ErrorKind::InvalidStateTransition(ServiceState::Running, to) if *to == state => {}
+                other => panic!("Unexpected error: {:?}", other),
+            }
+        }
+        assert!(reporter.paused(ServiceControlAccept::STOP).is_err());
+        assert!(reporter
+            .pending(ServiceState::Running, Duration::from_secs(1))
+            .is_err());
+
+        reporter.stopped(ServiceExitCode::default()).unwrap();
+        assert!(reporter.stopped(ServiceExitCode::default()).is_err());
+        assert!(reporter.running(ServiceControlAccept::STOP).is_err());
+        assert_eq!(scm.statuses().len(), 2);
+        assert_eq!(reporter.state(), ServiceState::Stopped);
+    }
+
+    #[test]
+    fn test_heartbeat() {
+        let (scm, reporter) = reporter();
+        reporter
+            .pending(ServiceState::StartPending, Duration::from_secs(1))
+            .unwrap();
+        let heartbeat = reporter.heartbeat(Duration::from_millis(10));
+        while scm.status().unwrap().checkpoint < 3 {
+            thread::sleep(Duration::from_millis(5));
+        }
+        reporter.running(ServiceControlAccept::STOP).unwrap();
+        drop(heartbeat);
+
+        // The heartbeat only reports progress while the service is pending.
+        let statuses = scm.statuses();
+        assert_eq!(
+            statuses.last().unwrap().current_state,
+            ServiceState::Running
+        );
+        assert!(statuses[..statuses.len() - 1]
+            .iter()
+            .enumerate()
+            .all(
+                |(i, status)| status.current_state == ServiceState::StartPending
+                    && status.checkpoint == i as u32 + 1
+            ));
+    }
+}
@@ -0,0 +1,330 @@
+//! Raw Win32 constants used by the platform-neutral parts of the crate.
+//!
+//! The constants mirror the ones defined in `winapi` so that the data model can be compiled on
+//! any platform. Their values are verified against `winapi` by the tests on Windows.
+
+#![allow(dead_code)]
+
+pub mod winnt {
+    pub const SERVICE_KERNEL_DRIVER: u32 = 0x00000001;
+    pub const SERVICE_FILE_SYSTEM_DRIVER: u32 = 0x00000002;
+    pub const SERVICE_ADAPTER: u32 = 0x00000004;
+    pub const SERVICE_RECOGNIZER_DRIVER: u32 = 0x00000008;
+    pub const SERVICE_DRIVER: u32 =
+        SERVICE_KERNEL_DRIVER | SERVICE_FILE_SYSTEM_DRIVER | SERVICE_RECOGNIZER_DRIVER;
+    pub const SERVICE_WIN32_OWN_PROCESS: u32 = 0x00000010;
+    pub const SERVICE_WIN32_SHARE_PROCESS: u32 = 0x00000020;
+    pub const SERVICE_WIN32: u32 = SERVICE_WIN32_OWN_PROCESS | SERVICE_WIN32_SHARE_PROCESS;
+    pub const SERVICE_USER_SERVICE: u32 = 0x00000040;
+    pub const SERVICE_USERSERVICE_INSTANCE: u32 = 0x00000080;
+    pub const SERVICE_INTERACTIVE_PROCESS: u32 = 0x00000100;
+    pub const SERVICE_PKG_SERVICE: u32 = 0x00000200;
+
+    pub const SERVICE_BOOT_START: u32 = 0x00000000;
+    pub const SERVICE_SYSTEM_START: u32 = 0x00000001;
+    pub const SERVICE_AUTO_START: u32 = 0x00000002;
+    pub const SERVICE_DEMAND_START: u32 = 0x00000003;
+    pub const SERVICE_DISABLED: u32 = 0x00000004;
+
+    pub const SERVICE_ERROR_IGNORE: u32 = 0x00000000;
+    pub const SERVICE_ERROR_NORMAL: u32 = 0x00000001;
+    pub const SERVICE_ERROR_SEVERE: u32 = 0x00000002;
+    pub const SERVICE_ERROR_CRITICAL: u32 = 0x00000003;
+
+    pub const DELETE: u32 = 0x00010000;
+}
+
+pub mod winsvc {
+    pub const SERVICE_NO_CHANGE: u32 = 0xffffffff;
+
+    /// Prefix of load ordering group names in service dependencies. Not defined by `winapi`.
+    pub const SC_GROUP_IDENTIFIER: u16 = b'+' as u16;
+
+    pub const SERVICE_CONTROL_STOP: u32 = 0x00000001;
+    pub const SERVICE_CONTROL_PAUSE: u32 = 0x00000002;
+    pub const SERVICE_CONTROL_CONTINUE: u32 = 0x00000003;
+    pub const SERVICE_CONTROL_INTERROGATE: u32 = 0x00000004;
+    pub const SERVICE_CONTROL_SHUTDOWN: u32 = 0x00000005;
+    pub const SERVICE_CONTROL_PARAMCHANGE: u32 = 0x00000006;
+    pub const SERVICE_CONTROL_NETBINDADD: u32 = 0x00000007;
+    pub const SERVICE_CONTROL_NETBINDREMOVE: u32 = 0x00000008;
+    pub const SERVICE_CONTROL_NETBINDENABLE: u32 = 0x00000009;
+    pub const SERVICE_CONTROL_NETBINDDISABLE: u32 = 0x0000000A;
+    pub const SERVICE_CONTROL_DEVICEEVENT: u32 = 0x0000000B;
+    pub const SERVICE_CONTROL_HARDWAREPROFILECHANGE: u32 = 0x0000000C;
+    pub const SERVICE_CONTROL_POWEREVENT: u32 = 0x0000000D;
+    pub const SERVICE_CONTROL_SESSIONCHANGE: u32 = 0x0000000E;
+    pub const SERVICE_CONTROL_PRESHUTDOWN: u32 = 0x0000000F;
+    pub const SERVICE_CONTROL_TIMECHANGE: u32 = 0x00000010;
+    pub const SERVICE_CONTROL_TRIGGEREVENT: u32 = 0x00000020;
+
+    // Not defined by `winapi`.
+    pub const SERVICE_CONTROL_USERMODEREBOOT: u32 = 0x00000040;
+    pub const SERVICE_CONTROL_LOWRESOURCES: u32 = 0x00000060;
+    pub const SERVICE_CONTROL_SYSTEMLOWRESOURCES: u32 = 0x00000061;
+
+    /// Range of control codes reserved for services. Not defined by `winapi`.
+    pub const SERVICE_USER_DEFINED_CONTROL_MIN: u32 = 128;
+    pub const SERVICE_USER_DEFINED_CONTROL_MAX: u32 = 255;
+
+    pub const SERVICE_STOPPED: u32 = 0x00000001;
+    pub const SERVICE_START_PENDING: u32 = 0x00000002;
+    pub const SERVICE_STOP_PENDING: u32 = 0x00000003;
+    pub const SERVICE_RUNNING: u32 = 0x00000004;
+    pub const SERVICE_CONTINUE_PENDING: u32 = 0x00000005;
+    pub const SERVICE_PAUSE_PENDING: u32 = 0x00000006;
+    pub const SERVICE_PAUSED: u32 = 0x00000007;
+
+    pub const SERVICE_ACTIVE: u32 = 0x00000001;
+    pub const SERVICE_INACTIVE: u32 = 0x00000002;
+    pub const SERVICE_STATE_ALL: u32 = 0x00000003;
+
+    pub const SERVICE_NOTIFY_STOPPED: u32 = 0x00000001;
+    pub const SERVICE_NOTIFY_START_PENDING: u32 = 0x00000002;
+    pub const SERVICE_NOTIFY_STOP_PENDING: u32 = 0x00000004;
+    pub const SERVICE_NOTIFY_RUNNING: u32 = 0x00000008;
+    pub const SERVICE_NOTIFY_CONTINUE_PENDING: u32 = 0x00000010;
+    pub const SERVICE_NOTIFY_PAUSE_PENDING: u32 = 0x00000020;
+    pub const SERVICE_NOTIFY_PAUSED: u32 = 0x00000040;
+    pub const SERVICE_NOTIFY_CREATED: u32 = 0x00000080;
+    pub const SERVICE_NOTIFY_DELETED: u32 = 0x00000100;
+    pub const SERVICE_NOTIFY_DELETE_PENDING: u32 = 0x00000200;
+
+    pub const SERVICE_ACCEPT_STOP: u32 = 0x00000001;
+    pub const SERVICE_ACCEPT_PAUSE_CONTINUE: u32 = 0x00000002;
+    pub const SERVICE_ACCEPT_SHUTDOWN: u32 = 0x00000004;
+    pub const SERVICE_ACCEPT_PARAMCHANGE: u32 = 0x00000008;
+    pub const SERVICE_ACCEPT_NETBINDCHANGE: u32 = 0x00000010;
+    pub const SERVICE_ACCEPT_HARDWAREPROFILECHANGE: u32 = 0x00000020;
+    pub const SERVICE_ACCEPT_POWEREVENT: u32 = 0x00000040;
+    pub const SERVICE_ACCEPT_SESSIONCHANGE: u32 = 0x00000080;
+    pub const SERVICE_ACCEPT_PRESHUTDOWN: u32 = 0x00000100;
+    pub const SERVICE_ACCEPT_TIMECHANGE: u32 = 0x00000200;
+    pub const SERVICE_ACCEPT_TRIGGEREVENT: u32 = 0x00000400;
+
+    // Not defined by `winapi`.
+    pub const SERVICE_ACCEPT_USERMODEREBOOT: u32 = 0x00000800;
+    pub const SERVICE_ACCEPT_LOWRESOURCES: u32 = 0x00002000;
+    pub const SERVICE_ACCEPT_SYSTEMLOWRESOURCES: u32 = 0x00004000;
+
+    pub const SERVICE_CONFIG_DESCRIPTION: u32 = 1;
+    pub const SERVICE_CONFIG_FAILURE_ACTIONS: u32 = 2;
+    pub const SERVICE_CONFIG_DELAYED_AUTO_START_INFO: u32 = 3;
+    pub const SERVICE_CONFIG_FAILURE_ACTIONS_FLAG: u32 = 4;
+    pub const SERVICE_CONFIG_SERVICE_SID_INFO: u32 = 5;
+    pub const SERVICE_CONFIG_REQUIRED_PRIVILEGES_INFO: u32 = 6;
+    pub const SERVICE_CONFIG_PRESHUTDOWN_INFO: u32 = 7;
+    pub const SERVICE_CONFIG_TRIGGER_INFO: u32 = 8;
+    pub const SERVICE_CONFIG_PREFERRED_NODE: u32 = 9;
+
+    pub const SERVICE_SID_TYPE_NONE: u32 = 0x00000000;
+    pub const SERVICE_SID_TYPE_UNRESTRICTED: u32 = 0x00000001;
+    pub const SERVICE_SID_TYPE_RESTRICTED: u32 = 0x00000003;
+
+    pub const SC_ACTION_NONE: u32 = 0;
+    pub const SC_ACTION_RESTART: u32 = 1;
+    pub const SC_ACTION_REBOOT: u32 = 2;
+    pub const SC_ACTION_RUN_COMMAND: u32 = 3;
+
+    pub const SERVICE_TRIGGER_TYPE_DEVICE_INTERFACE_ARRIVAL: u32 = 1;
+    pub const SERVICE_TRIGGER_TYPE_IP_ADDRESS_AVAILABILITY: u32 = 2;
+    pub const SERVICE_TRIGGER_TYPE_DOMAIN_JOIN: u32 = 3;
+    pub const SERVICE_TRIGGER_TYPE_FIREWALL_PORT_EVENT: u32 = 4;
+    pub const SERVICE_TRIGGER_TYPE_GROUP_POLICY: u32 = 5;
+    pub const SERVICE_TRIGGER_TYPE_NETWORK_ENDPOINT: u32 = 6;
+    pub const SERVICE_TRIGGER_TYPE_CUSTOM_SYSTEM_STATE_CHANGE: u32 = 7;
+    pub const SERVICE_TRIGGER_TYPE_CUSTOM: u32 = 20;
+
+    pub const SERVICE_TRIGGER_DATA_TYPE_BINARY: u32 = 1;
+    pub const SERVICE_TRIGGER_DATA_TYPE_STRING: u32 = 2;
+    pub const SERVICE_TRIGGER_DATA_TYPE_LEVEL: u32 = 3;
+    pub const SERVICE_TRIGGER_DATA_TYPE_KEYWORD_ANY: u32 = 4;
+    pub const SERVICE_TRIGGER_DATA_TYPE_KEYWORD_ALL: u32 = 5;
+
+    // Not defined by `winapi`.
+    pub const SERVICE_TRIGGER_ACTION_SERVICE_START: u32 = 1;
+    pub const SERVICE_TRIGGER_ACTION_SERVICE_STOP: u32 = 2;
+
+    pub const SC_MANAGER_CONNECT: u32 = 0x0001;
+    pub const SC_MANAGER_CREATE_SERVICE: u32 = 0x0002;
+    pub const SC_MANAGER_ENUMERATE_SERVICE: u32 = 0x0004;
+
+    pub const SERVICE_QUERY_CONFIG: u32 = 0x0001;
+    pub const SERVICE_CHANGE_CONFIG: u32 = 0x0002;
+    pub const SERVICE_QUERY_STATUS: u32 = 0x0004;
+    pub const SERVICE_ENUMERATE_DEPENDENTS: u32 = 0x0008;
+    pub const SERVICE_START: u32 = 0x0010;
+    pub const SERVICE_STOP: u32 = 0x0020;
+    pub const SERVICE_PAUSE_CONTINUE: u32 = 0x0040;
+    pub const SERVICE_INTERROGATE: u32 = 0x0080;
+    pub const SERVICE_USER_DEFINED_CONTROL: u32 = 0x0100;
+}
+
+pub mod winbase {
+    pub const INFINITE: u32 = 0xFFFFFFFF;
+}
+
+pub mod winuser {
+    pub const WTS_CONSOLE_CONNECT: u32 = 0x1;
+    pub const WTS_CONSOLE_DISCONNECT: u32 = 0x2;
+    pub const WTS_REMOTE_CONNECT: u32 = 0x3;
+    pub const WTS_REMOTE_DISCONNECT: u32 = 0x4;
+    pub const WTS_SESSION_LOGON: u32 = 0x5;
+    pub const WTS_SESSION_LOGOFF: u32 = 0x6;
+    pub const WTS_SESSION_LOCK: u32 = 0x7;
+    pub const WTS_SESSION_UNLOCK: u32 = 0x8;
+    pub const WTS_SESSION_REMOTE_CONTROL: u32 = 0x9;
+    pub const WTS_SESSION_CREATE: u32 = 0xa;
+    pub const WTS_SESSION_TERMINATE: u32 = 0xb;
+
+    pub const PBT_APMSUSPEND: u32 = 0x0004;
+    pub const PBT_APMRESUMESUSPEND: u32 = 0x0007;
+    pub const PBT_APMPOWERSTATUSCHANGE: u32 = 0x000A;
+    pub const PBT_APMRESUMEAUTOMATIC: u32 = 0x0012;
+    pub const PBT_POWERSETTINGCHANGE: u32 = 0x8013;
+}
+
+pub mod dbt {
+    pub const DBT_QUERYCHANGECONFIG: u32 = 0x0017;
+    pub const DBT_CONFIGCHANGED: u32 = 0x0018;
+    pub const DBT_CONFIGCHANGECANCELED: u32 = 0x0019;
+
+    pub const DBT_DEVICEARRIVAL: u32 = 0x8000;
+    pub const DBT_DEVICEQUERYREMOVE: u32 = 0x8001;
+    pub const DBT_DEVICEQUERYREMOVEFAILED: u32 = 0x8002;
+    pub const DBT_DEVICEREMOVEPENDING: u32 = 0x8003;
+    pub const DBT_DEVICEREMOVECOMPLETE: u32 = 0x8004;
+    pub const DBT_CUSTOMEVENT: u32 = 0x8006;
+
+    pub const DBT_DEVTYP_DEVICEINTERFACE: u32 = 0x00000005;
+    pub const DBT_DEVTYP_HANDLE: u32 = 0x00000006;
+}
+
+pub mod winerror {
+    pub const NO_ERROR: u32 = 0;
+    pub const ERROR_ACCESS_DENIED: u32 = 5;
+    pub const ERROR_CALL_NOT_IMPLEMENTED: u32 = 120;
+    pub const ERROR_DEPENDENT_SERVICES_RUNNING: u32 = 1051;
+    pub const ERROR_INVALID_SERVICE_CONTROL: u32 = 1052;
+    pub const ERROR_SERVICE_CANNOT_ACCEPT_CTRL: u32 = 1061;
+    pub const ERROR_SERVICE_ALREADY_RUNNING: u32 = 1056;
+    pub const ERROR_SERVICE_DISABLED: u32 = 1058;
+    pub const ERROR_SERVICE_DOES_NOT_EXIST: u32 = 1060;
+    pub const ERROR_SERVICE_NOT_ACTIVE: u32 = 1062;
+    pub const ERROR_SERVICE_SPECIFIC_ERROR: u32 = 1066;
+    pub const ERROR_SERVICE_MARKED_FOR_DELETE: u32 = 1072;
+    pub const ERROR_SERVICE_EXISTS: u32 = 1073;
+    pub const ERROR_SERVICE_NEVER_STARTED: u32 = 1077;
+    pub const ERROR_SERVICE_NOT_IN_EXE: u32 = 1083;
+}
+
+#[cfg(all(test, windows))]
+mod tests {
+    use winapi::shared::winerror as raw_winerror;
+    use winapi::um::{
+        dbt as raw_dbt, winbase as raw_winbase, winnt as raw_winnt, winsvc as raw_winsvc,
+        winuser as raw_winuser,
+    };
+
+    macro_rules! assert_constants {
+        ($module:ident, $raw_module:ident, [$($name:ident),* $(,)*]) => {
+            $(assert_eq!(super::$module::$name, $raw_module::$name as u32, stringify!($name));)*
+        };
+    }
+
+    #[test]
+    fn test_winnt_constants() {
+        assert_constants!(winnt, raw_winnt, [
+            SERVICE_KERNEL_DRIVER, SERVICE_FILE_SYSTEM_DRIVER, SERVICE_ADAPTER,
+            SERVICE_RECOGNIZER_DRIVER, SERVICE_DRIVER, SERVICE_WIN32_OWN_PROCESS,
+            SERVICE_WIN32_SHARE_PROCESS, SERVICE_WIN32, SERVICE_USER_SERVICE,
+            SERVICE_USERSERVICE_INSTANCE, SERVICE_INTERACTIVE_PROCESS, SERVICE_PKG_SERVICE,
+            SERVICE_BOOT_START, SERVICE_SYSTEM_START, SERVICE_AUTO_START, SERVICE_DEMAND_START,
+            SERVICE_DISABLED, SERVICE_ERROR_IGNORE, SERVICE_ERROR_NORMAL, SERVICE_ERROR_SEVERE,
+            SERVICE_ERROR_CRITICAL, DELETE,
+        ]);
+    }
+
+    #[test]
+    fn test_winsvc_constants() {
+        assert_constants!(winsvc, raw_winsvc, [
+            SERVICE_NO_CHANGE, SERVICE_CONTROL_STOP, SERVICE_CONTROL_PAUSE,
+            SERVICE_CONTROL_CONTINUE, SERVICE_CONTROL_INTERROGATE, SERVICE_CONTROL_SHUTDOWN,
+            SERVICE_CONTROL_PARAMCHANGE, SERVICE_CONTROL_NETBINDADD,
+            SERVICE_CONTROL_NETBINDREMOVE, SERVICE_CONTROL_NETBINDENABLE,
+            SERVICE_CONTROL_NETBINDDISABLE, SERVICE_CONTROL_DEVICEEVENT,
+            SERVICE_CONTROL_HARDWAREPROFILECHANGE, SERVICE_CONTROL_POWEREVENT,
+            SERVICE_CONTROL_SESSIONCHANGE, SERVICE_CONTROL_PRESHUTDOWN,
+            SERVICE_CONTROL_TIMECHANGE, SERVICE_CONTROL_TRIGGEREVENT, SERVICE_STOPPED,
+            SERVICE_START_PENDING,
+            SERVICE_STOP_PENDING, SERVICE_RUNNING, SERVICE_CONTINUE_PENDING,
+            SERVICE_PAUSE_PENDING, SERVICE_PAUSED, SERVICE_ACTIVE, SERVICE_INACTIVE,
+            SERVICE_STATE_ALL, SERVICE_NOTIFY_STOPPED, SERVICE_NOTIFY_START_PENDING,
+            SERVICE_NOTIFY_STOP_PENDING, SERVICE_NOTIFY_RUNNING, SERVICE_NOTIFY_CONTINUE_PENDING,
+            SERVICE_NOTIFY_PAUSE_PENDING, SERVICE_NOTIFY_PAUSED, SERVICE_NOTIFY_CREATED,
+            SERVICE_NOTIFY_DELETED, SERVICE_NOTIFY_DELETE_PENDING, SERVICE_CONFIG_DESCRIPTION,
+            SERVICE_CONFIG_FAILURE_ACTIONS,
+            SERVICE_CONFIG_DELAYED_AUTO_START_INFO, SERVICE_CONFIG_FAILURE_ACTIONS_FLAG,
+            SERVICE_CONFIG_SERVICE_SID_INFO, SERVICE_CONFIG_REQUIRED_PRIVILEGES_INFO,
+            SERVICE_CONFIG_PRESHUTDOWN_INFO, SERVICE_CONFIG_TRIGGER_INFO,
+            SERVICE_CONFIG_PREFERRED_NODE, SERVICE_SID_TYPE_NONE, SERVICE_SID_TYPE_UNRESTRICTED,
+            SERVICE_SID_TYPE_RESTRICTED, SC_ACTION_NONE, SC_ACTION_RESTART, SC_ACTION_REBOOT,
+            SC_ACTION_RUN_COMMAND, SERVICE_TRIGGER_TYPE_DEVICE_INTERFACE_ARRIVAL,
+            SERVICE_TRIGGER_TYPE_IP_ADDRESS_AVAILABILITY, SERVICE_TRIGGER_TYPE_DOMAIN_JOIN,
+            SERVICE_TRIGGER_TYPE_FIREWALL_PORT_EVENT, SERVICE_TRIGGER_TYPE_GROUP_POLICY,
+            SERVICE_TRIGGER_TYPE_NETWORK_ENDPOINT, SERVICE_TRIGGER_TYPE_CUSTOM_SYSTEM_STATE_CHANGE,
+            SERVICE_TRIGGER_TYPE_CUSTOM, SERVICE_TRIGGER_DATA_TYPE_BINARY,
+            SERVICE_TRIGGER_DATA_TYPE_STRING, SERVICE_TRIGGER_DATA_TYPE_LEVEL,
+            SERVICE_TRIGGER_DATA_TYPE_KEYWORD_ANY, SERVICE_TRIGGER_DATA_TYPE_KEYWORD_ALL,
+            SERVICE_ACCEPT_STOP,
+            SERVICE_ACCEPT_PAUSE_CONTINUE, SERVICE_ACCEPT_SHUTDOWN, SERVICE_ACCEPT_PARAMCHANGE,
+            SERVICE_ACCEPT_NETBINDCHANGE, SERVICE_ACCEPT_HARDWAREPROFILECHANGE,
+            SERVICE_ACCEPT_POWEREVENT, SERVICE_ACCEPT_SESSIONCHANGE, SERVICE_ACCEPT_PRESHUTDOWN,
+            SERVICE_ACCEPT_TIMECHANGE, SERVICE_ACCEPT_TRIGGEREVENT, SC_MANAGER_CONNECT,
+            SC_MANAGER_CREATE_SERVICE, SC_MANAGER_ENUMERATE_SERVICE, SERVICE_QUERY_CONFIG,
+            SERVICE_CHANGE_CONFIG, SERVICE_QUERY_STATUS, SERVICE_ENUMERATE_DEPENDENTS,
+            SERVICE_START, SERVICE_STOP, SERVICE_PAUSE_CONTINUE, SERVICE_INTERROGATE,
+            SERVICE_USER_DEFINED_CONTROL,
+        ]);
+    }
+
+    #[test]
+    fn test_winbase_constants() {
+        assert_constants!(winbase, raw_winbase, [INFINITE]);
+    }
+
+    #[test]
+    fn test_winuser_constants() {
+        assert_constants!(winuser, raw_winuser, [
+            WTS_CONSOLE_CONNECT, WTS_CONSOLE_DISCONNECT, WTS_REMOTE_CONNECT, WTS_REMOTE_DISCONNECT,
+            WTS_SESSION_LOGON, WTS_SESSION_LOGOFF, WTS_SESSION_LOCK, WTS_SESSION_UNLOCK,
+            WTS_SESSION_REMOTE_CONTROL, WTS_SESSION_CREATE, WTS_SESSION_TERMINATE, PBT_APMSUSPEND,
+            PBT_APMRESUMESUSPEND, PBT_APMPOWERSTATUSCHANGE, PBT_APMRESUMEAUTOMATIC,
+            PBT_POWERSETTINGCHANGE,
+        ]);
+    }
+
+    #[test]
+    fn test_dbt_constants() {
+        assert_constants!(dbt, raw_dbt, [
+            DBT_QUERYCHANGECONFIG, DBT_CONFIGCHANGED, DBT_CONFIGCHANGECANCELED, DBT_DEVICEARRIVAL,
+            DBT_DEVICEQUERYREMOVE, DBT_DEVICEQUERYREMOVEFAILED, DBT_DEVICEREMOVEPENDING,
+            DBT_DEVICEREMOVECOMPLETE, DBT_CUSTOMEVENT, DBT_DEVTYP_DEVICEINTERFACE,
+            DBT_DEVTYP_HANDLE,
+        ]);
+    }
+
+    #[test]
+    fn test_winerror_constants() {
+        assert_constants!(winerror, raw_winerror, [
+            NO_ERROR, ERROR_ACCESS_DENIED, ERROR_CALL_NOT_IMPLEMENTED,
+            ERROR_DEPENDENT_SERVICES_RUNNING,
+            ERROR_INVALID_SERVICE_CONTROL,
+            ERROR_SERVICE_CANNOT_ACCEPT_CTRL, ERROR_SERVICE_ALREADY_RUNNING,
+            ERROR_SERVICE_DISABLED, ERROR_SERVICE_DOES_NOT_EXIST,
+            ERROR_SERVICE_NOT_ACTIVE, ERROR_SERVICE_SPECIFIC_ERROR,
+            ERROR_SERVICE_MARKED_FOR_DELETE, ERROR_SERVICE_EXISTS, ERROR_SERVICE_NEVER_STARTED,
+            ERROR_SERVICE_NOT_IN_EXE,
+        ]);
+    }
+}
@@ -0,0 +1,272 @@
+//! Waiting for a service to reach a state, following the algorithm documented for the service
+//! control manager.
+
+use std::cmp;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use service::{ServiceState, ServiceStatus};
+use {ErrorKind, Result};
+
+/// The shortest and longest interval between two status queries.
+const MIN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Source of time for [`wait_for_state`].
+pub(crate) trait Clock {
+    fn now(&self) -> Instant;
+    fn sleep(&self, duration: Duration);
+}
+
+/// Clock that reads the system time and blocks the current thread.
+pub(crate) struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration)
+    }
+}
+
+/// Poll the service status until the service reaches the target state.
+///
+/// While the service is in a pending state, it is polled at a tenth of its wait hint, but not
+/// more often than once a second and not less often than every ten seconds. The service is
+/// considered hung when its checkpoint does not advance within the wait hint.
+pub(crate) fn wait_for_state<Q, C>(
+    mut query_status: Q,
+    clock: &C,
+    target_state: ServiceState,
+    timeout: Duration,
+) -> Result<ServiceStatus>
+where
+    Q: FnMut() -> Result<ServiceStatus>,
+    C: Clock,
+{
+    let mut wait = Wait::new(target_state, timeout, clock.now());
+    loop {
+        let status = query_status()?;
+        match wait.check(status, clock.now())? {
+            WaitStep::Done(status) => return Ok(status),
+            WaitStep::Sleep(duration) => clock.sleep(duration),
+        }
+    }
+}
+
+/// The progress of a wait for a service to reach a state, independent of how the status is
+/// queried and how the time passes between two queries.
+pub(crate) struct Wait {
+    target_state: ServiceState,
+    deadline: Instant,
+    /// The last checkpoint reported by the service and when it was first seen.
+    checkpoint: Option<(u32, Instant)>,
+}
+
+/// What to do after checking a status with [`Wait::check`].
+pub(crate) enum WaitStep {
+    /// The service reached the target state.
+    Done(ServiceStatus),
+    /// Query the status again after sleeping for the duration.
+    Sleep(Duration),
+}
+
+impl Wait {
+    pub(crate) fn new(target_state: ServiceState, timeout: Duration, now: Instant) -> Self {
+        Wait {
+            target_state,
+            deadline: now + timeout,
+            checkpoint: None,
+        }
+    }
+
+    /// Check the status that was queried at `now`.
+    pub(crate) fn check(&mut self, status: ServiceStatus, now: Instant) -> Result<WaitStep> {
+        match status.current_state {
+            state if state == self.target_state => return Ok(WaitStep::Done(status)),
+            ServiceState::Stopped => Err(ErrorKind::ServiceFailed(status.exit_code))?,
+            ServiceState::StartPending
+            | ServiceState::StopPending
+            | ServiceState::ContinuePending
+            | ServiceState::PausePending => (),
+            state => Err(ErrorKind::UnexpectedServiceState(state))?,
+        }
+
+        match self.checkpoint {
+            Some((checkpoint, checkpoint_time)) if checkpoint == status.checkpoint => {
+                if now.duration_since(checkpoint_time) > status.wait_hint {
+                    Err(ErrorKind::ServiceHung(
+                        status.current_state,
+                        status.checkpoint,
+                    ))?;
+                }
+            }
+            _ => self.checkpoint = Some((status.checkpoint, now)),
+        }
+        if now >= self.deadline {
+            Err(ErrorKind::Timeout(status.current_state))?;
+        }
+
+        let poll_interval = cmp::max(
+            MIN_POLL_INTERVAL,
+            cmp::min(MAX_POLL_INTERVAL, status.wait_hint / 10),
+        );
+        Ok(WaitStep::Sleep(cmp::min(
+            poll_interval,
+            self.deadline.duration_since(now),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use service::{ServiceControlAccept, ServiceExitCode, ServiceType};
+    use std::cell::{Cell, RefCell};
+    use std::collections::VecDeque;
+
+    /// Clock that only advances when sleeping.
+    struct ManualClock {
+        start: Instant,
+        elapsed: Cell<Duration>,
+        sleeps: RefCell<Vec<Duration>>,
+    }
+
+    impl ManualClock {
+        fn new() -> Self {
+            ManualClock {
+                start: Instant::now(),
+                elapsed: Cell::new(Duration::default()),
+                sleeps: RefCell::new(Vec::new()),
+            }
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            self.start + self.elapsed.get()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.elapsed.set(self.elapsed.get() + duration);
+            self.sleeps.borrow_mut().push(duration);
+        }
+    }
+
+    fn status(state: ServiceState, checkpoint: u32, wait_hint_secs: u64) -> ServiceStatus {
+        ServiceStatus {
+            service_type: ServiceType::OwnProcess,
+            current_state: state,
+            controls_accepted: ServiceControlAccept::empty(),
+            exit_code: ServiceExitCode::default(),
+            checkpoint,
+            wait_hint: Duration::from_secs(wait_hint_secs),
+        }
+    }
+
+    /// Run the wait against the scripted statuses, repeating the last one once the script is
+    /// exhausted.
+    fn wait(
+        script: Vec<ServiceStatus>,
+        target_state: ServiceState,
+        timeout_secs: u64,
+    ) -> (Result<ServiceStatus>, Vec<Duration>) {
+        let clock = ManualClock::new();
+        let mut script = VecDeque::from(script);
+        let result = wait_for_state(
+            || {
+                if script.len() > 1 {
+                    Ok(script.pop_front().unwrap())
+                } else {
+                    Ok(script[0].clone())
+                }
+            },
+            &clock,
+            target_state,
+            Duration::from_secs(timeout_secs),
+        );
+        (result, clock.sleeps.into_inner())
+    }
+
+    #[test]
+    fn test_reach_target_state() {
+        let (result, sleeps) = wait(
+            vec![
+                status(ServiceState::StopPending, 1, 30),
+                status(ServiceState::StopPending, 2, 200),
+                status(ServiceState::StopPending, 3, 0),
+                status(ServiceState::Stopped, 0, 0),
+            ],
+            ServiceState::Stopped,
+            60,
+        );
+        assert_eq!(result.unwrap().current_state, ServiceState::Stopped);
+        assert_eq!(
+            sleeps,
+            vec![
+                Duration::from_secs(3),
+                Duration::from_secs(10),
+                Duration::from_secs(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hung_service() {
+        let (result, _) = wait(
+            vec![
+                status(ServiceState::StartPending, 1, 2),
+                status(ServiceState::StartPending, 2, 2),
+            ],
+            ServiceState::Running,
+            60,
+        );
+        match *result.unwrap_err().kind() {
+            ErrorKind::ServiceHung(ServiceState::StartPending, 2) => (),
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_failed_service() {
+        let mut stopped = status(ServiceState::Stopped, 0, 0);
+        stopped.exit_code = ServiceExitCode::ServiceSpecific(5);
+        let (result, _) = wait(
+            vec![status(ServiceState::StartPending, 1, 2), stopped],
+            ServiceState::Running,
+            60,
+        );
+        match *result.unwrap_err().kind() {
+            ErrorKind::ServiceFailed(ServiceExitCode::ServiceSpecific(5)) => (),
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_unexpected_state() {
+        let (result, _) = wait(
+            vec![status(ServiceState::Running, 0, 0)],
+            ServiceState::Stopped,
+            60,
+        );
+        match *result.unwrap_err().kind() {
+            ErrorKind::UnexpectedServiceState(ServiceState::Running) => (),
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_timeout_while_making_progress() {
+        let script = (1..100)
+            .map(|checkpoint| status(ServiceState::StartPending, checkpoint, 20))
+            .collect();
+        let (result, sleeps) = wait(script, ServiceState::Running, 5);
+        match *result.unwrap_err().kind() {
+            ErrorKind::Timeout(ServiceState::StartPending) => (),
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+        assert_eq!(sleeps.iter().sum::<Duration>(), Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,142 @@
+//! Notifications about changes to services.
+//!
+//! [`Service::watch`] reports the state transitions of a service, and
+//! [`ServiceManager::watch_created_deleted`] reports the services that are created and deleted.
+//! The system pushes the changes as they happen, so there is no need to poll the service status.
+//! The events are delivered through a [`ServiceWatch`], which stops watching when it is dropped.
+//!
+//! [`Service::watch`]: super::service::Service::watch
+//! [`ServiceManager::watch_created_deleted`]:
+//!     super::service_manager::ServiceManager::watch_created_deleted
+
+use std::ffi::OsString;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use backend::WatchHandle;
+use service::{ServiceState, ServiceStatusExt};
+use sys::winsvc;
+use Result;
+
+bitflags! {
+    /// Flags selecting the events reported by [`Service::watch`].
+    ///
+    /// [`Service::watch`]: super::service::Service::watch
+    pub struct ServiceNotifyMask: u32 {
+        /// The service stopped.
+        const STOPPED = winsvc::SERVICE_NOTIFY_STOPPED;
+
+        /// The service is starting.
+        const START_PENDING = winsvc::SERVICE_NOTIFY_START_PENDING;
+
+        /// The service is stopping.
+        const STOP_PENDING = winsvc::SERVICE_NOTIFY_STOP_PENDING;
+
+        /// The service is running.
+        const RUNNING = winsvc::SERVICE_NOTIFY_RUNNING;
+
+        /// The service is continuing after being paused.
+        const CONTINUE_PENDING = winsvc::SERVICE_NOTIFY_CONTINUE_PENDING;
+
+        /// The service is pausing.
+        const PAUSE_PENDING = winsvc::SERVICE_NOTIFY_PAUSE_PENDING;
+
+        /// The service is paused.
+        const PAUSED = winsvc::SERVICE_NOTIFY_PAUSED;
+
+        /// The service entered any state.
+        const ANY_STATE = Self::STOPPED.bits
+            | Self::START_PENDING.bits
+            | Self::STOP_PENDING.bits
+            | Self::RUNNING.bits
+            | Self::CONTINUE_PENDING.bits
+            | Self::PAUSE_PENDING.bits
+            | Self::PAUSED.bits;
+
+        /// The service was marked for deletion.
+        const DELETE_PENDING = winsvc::SERVICE_NOTIFY_DELETE_PENDING;
+    }
+}
+
+impl ServiceNotifyMask {
+    /// Returns the flag for entering the given state.
+    pub fn from_state(state: ServiceState) -> Self {
+        match state {
+            ServiceState::Stopped => ServiceNotifyMask::STOPPED,
+            ServiceState::StartPending => ServiceNotifyMask::START_PENDING,
+            ServiceState::StopPending => ServiceNotifyMask::STOP_PENDING,
+            ServiceState::Running => ServiceNotifyMask::RUNNING,
+            ServiceState::ContinuePending => ServiceNotifyMask::CONTINUE_PENDING,
+            ServiceState::PausePending => ServiceNotifyMask::PAUSE_PENDING,
+            ServiceState::Paused => ServiceNotifyMask::PAUSED,
+        }
+    }
+}
+
+/// An event reported by a [`ServiceWatch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServiceEvent {
+    /// The watched service entered one of the states selected by the mask.
+    StateChanged(ServiceStatusExt),
+
+    /// The watched service was marked for deletion.
+    DeletePending,
+
+    /// The service with the given name was created.
+    Created(OsString),
+
+    /// The service with the given name was deleted.
+    Deleted(OsString),
+}
+
+/// Receives the events of a watch.
+///
+/// The watch ends when the watched service is marked for deletion, or after reporting an error.
+/// Events that were reported before the watch ended can still be received. Dropping the
+/// `ServiceWatch` stops watching.
+///
+/// Iterating over the watch blocks until the next event, and stops once the watch has ended.
+pub struct ServiceWatch {
+    events: mpsc::Receiver<Result<ServiceEvent>>,
+    _handle: WatchHandle,
+}
+
+impl ServiceWatch {
+    pub(crate) fn new(events: mpsc::Receiver<Result<ServiceEvent>>, handle: WatchHandle) -> Self {
+        ServiceWatch {
+            events,
+            _handle: handle,
+        }
+    }
+
+    /// Wait for the next event. Returns `None` once the watch has ended.
+    pub fn recv(&self) -> Option<Result<ServiceEvent>> {
+        self.events.recv().ok()
+    }
+
+    /// Wait for the next event for at most the given time.
+    pub fn recv_timeout(
+        &self,
+        timeout: Duration,
+    ) -> ::std::result::Result<Result<ServiceEvent>, mpsc::RecvTimeoutError> {
+        self.events.recv_timeout(timeout)
+    }
+
+    /// Returns an iterator over the events that were already reported, without waiting.
+    pub fn try_iter(&self) -> mpsc::TryIter<'_, Result<ServiceEvent>> {
+        self.events.try_iter()
+    }
+
+    /// Return the next event if there is one, without waiting.
+    pub fn try_recv(&self) -> ::std::result::Result<Result<ServiceEvent>, mpsc::TryRecvError> {
+        self.events.try_recv()
+    }
+}
+
+impl Iterator for ServiceWatch {
+    type Item = Result<ServiceEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}